@@ -23,7 +23,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
   let env_features = EnvironmentFeatures::default();
 
   info!("Attempting to launch the game");
-  let mut version_manager = VersionManager::load(&game_dir, &env_features, Some(client.clone())).await?;
+  let version_manager = VersionManager::load(&game_dir, &env_features, Some(client.clone())).await?;
 
   info!("Queuing library & version downloads");
   let manifest = version_manager.resolve_local_version(&version_id, true, true).await?;
@@ -5,6 +5,8 @@ use thiserror::Error;
 use reqwest::Client;
 use uuid::Uuid;
 
+use crate::network::NetworkSettings;
+
 const PROFILE_URL: &str = "https://sessionserver.mojang.com/session/minecraft/profile/";
 
 #[derive(Debug, Clone)]
@@ -25,7 +27,12 @@ impl UserAuthentication {
   }
 
   pub async fn online(access_token: &str) -> Result<Self, UserAuthenticationError> {
-    let profile: ProfileResponse = Client::new().get(PROFILE_URL).bearer_auth(access_token).send().await?.error_for_status()?.json().await?;
+    Self::online_with_client(access_token, &NetworkSettings::default().create_client()?).await
+  }
+
+  /// Like `online`, with a client built from the launcher's `NetworkSettings`
+  pub async fn online_with_client(access_token: &str, client: &Client) -> Result<Self, UserAuthenticationError> {
+    let profile: ProfileResponse = client.get(PROFILE_URL).bearer_auth(access_token).send().await?.error_for_status()?.json().await?;
 
     Ok(Self {
       access_token: Some(access_token.to_string()),
@@ -30,7 +30,7 @@ use crate::json::{
         argument::ArgumentType,
         assets::{AssetIndex, AssetIndexInfo, AssetObject},
         library::ExtractRules,
-        rule::{OperatingSystem, RuleFeatureType},
+        rule::{OperatingSystem, RuleFeatureType, TargetPlatform},
         VersionManifest,
     },
     EnvironmentFeatures, Sha1Sum, VersionInfo,
@@ -289,7 +289,7 @@ impl GameBootstrap {
     }
 
     fn unpack_natives(&self, manifest: &VersionManifest) -> Result<(), UnpackNativesError> {
-        let os = OperatingSystem::get_current_platform();
+        let platform = TargetPlatform::current();
         let natives_dir = &self.options.natives_dir;
         info!("Unpacking natives to {}", natives_dir.display());
         create_dir_all(natives_dir).map_err(UnpackNativesError::CreateNativesFolder)?;
@@ -325,7 +325,7 @@ impl GameBootstrap {
         }
 
         for lib in libs {
-            if let Some(Some(native_id)) = lib.get_artifact_classifier(&os) {
+            if let Some(Some(native_id)) = lib.get_artifact_classifier(platform) {
                 let file = &self.options.game_dir.join("libraries").join(
                     lib.get_artifact_path(Some(native_id.clone()))
                         .replace('/', MAIN_SEPARATOR_STR),
@@ -570,7 +570,11 @@ impl GameBootstrap {
         } else {
             ":"
         };
-        let classpath = manifest.get_classpath(&os, &self.options.game_dir, &self.env_features);
+        let classpath = manifest.get_classpath(
+            TargetPlatform::current(),
+            &self.options.game_dir,
+            &self.env_features,
+        );
 
         let mut vec = vec![];
         for path in &classpath {
@@ -3,7 +3,7 @@ use std::{ path::PathBuf, collections::HashMap, fmt::Debug };
 use derive_builder::Builder;
 use serde_json::json;
 
-use crate::json::{ manifest::rule::RuleFeatureType, EnvironmentFeatures };
+use crate::{ json::{ manifest::rule::RuleFeatureType, EnvironmentFeatures }, network::{ NetworkSettings, ProxyKind, ProxySettings } };
 use super::auth::UserAuthentication;
 
 #[derive(Debug, Clone)]
@@ -31,18 +31,29 @@ pub enum ProxyOptions {
 
 impl ProxyOptions {
   pub fn create_http_proxy(&self) -> Option<reqwest::Proxy> {
+    self.to_proxy_settings()?.create_proxy().ok()
+  }
+
+  pub fn to_proxy_settings(&self) -> Option<ProxySettings> {
     if let ProxyOptions::Proxy { host, port, username, password } = self {
-      let mut proxy = reqwest::Proxy::all(format!("{}:{}", host, port)).ok()?;
-      if let (Some(username), Some(password)) = (username, password) {
-        proxy = proxy.basic_auth(username, password);
-      }
-      Some(proxy)
+      Some(ProxySettings { kind: ProxyKind::Http, host: host.clone(), port: *port, username: username.clone(), password: password.clone() })
     } else {
       None
     }
   }
 }
 
+/// Passes the proxy used by the launcher to the game
+impl From<&NetworkSettings> for ProxyOptions {
+  fn from(settings: &NetworkSettings) -> Self {
+    match &settings.proxy {
+      Some(ProxySettings { host, port, username, password, .. }) =>
+        ProxyOptions::Proxy { host: host.clone(), port: *port, username: username.clone(), password: password.clone() },
+      None => ProxyOptions::NoProxy,
+    }
+  }
+}
+
 #[derive(Debug, Clone, Builder)]
 #[builder(pattern = "owned", setter(strip_option))]
 pub struct GameOptions {
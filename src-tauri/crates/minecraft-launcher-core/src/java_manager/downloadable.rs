@@ -1,4 +1,4 @@
-use std::{ fs::{ self, File }, io::{ BufReader, Write }, path::{ Path, PathBuf }, sync::{ Arc, Mutex } };
+use std::{ fs::{ self, File }, io::BufReader, path::{ Path, PathBuf }, sync::{ Arc, Mutex } };
 
 use async_trait::async_trait;
 use log::{ info, warn };
@@ -10,7 +10,19 @@ use sha1::Sha1;
 use crate::{
   java_manager::{ error::CompressedRuntimeFileError, manifest::Download },
   json::Sha1Sum,
-  version_manager::downloader::downloadables::{ DownloadError, Downloadable, DownloadableMonitor, HashError },
+  version_manager::{
+    downloader::downloadables::{
+      commit_verified,
+      hash_file,
+      AtomicFile,
+      DownloadError,
+      Downloadable,
+      DownloadableMonitor,
+      HashError,
+      HashingWriter,
+    },
+    endpoints::Service,
+  },
 };
 
 use super::manifest::Downloads;
@@ -49,48 +61,50 @@ impl RuntimeFileDownloadable {
     }
   }
 
-  fn unpack_lzma(&self, file: &Path) -> Result<Sha1Sum, DownloadError> {
+  async fn unpack_lzma(&self, file: &Path) -> Result<(AtomicFile, Sha1Sum), DownloadError> {
     let target = self.get_target_file();
     self.set_status(RuntimeFileDownloadableStatus::Decompressing);
 
-    self.prepare_destination(target).map_err(DownloadError::PrepareDestination)?;
+    self.prepare_destination(target).await.map_err(DownloadError::PrepareDestination)?;
 
     let compressed_file = File::open(file).map_err(CompressedRuntimeFileError::ReadLzma)?;
-    let mut reader = BufReader::new(compressed_file);
+    let mut target_file = AtomicFile::create(target).await.map_err(DownloadError::WriteFile)?;
 
-    let mut bytes = vec![];
-    lzma_decompress(&mut reader, &mut bytes).map_err(CompressedRuntimeFileError::Decompress)?;
-
-    fs::write(target, &bytes).map_err(DownloadError::WriteFile)?;
-    let _ = set_executable(target);
-
-    let mut sha1 = Sha1::new();
-    sha1.update(bytes);
-    Ok(Sha1Sum::from(sha1))
+    // Stream the decompressed file to disk instead of buffering it in memory
+    let sha1 = target_file
+      .write_blocking(move |writer| {
+        let mut reader = BufReader::new(compressed_file);
+        let mut writer = HashingWriter::new(writer);
+        lzma_decompress(&mut reader, &mut writer)?;
+        Ok(writer.finish())
+      }).await
+      .map_err(CompressedRuntimeFileError::Decompress)?;
+    Ok((target_file, sha1))
   }
 
   async fn try_download_lzma(
     &self,
     client: &Client,
     compressed_url: &str,
-    compressed_target: &PathBuf
-  ) -> Result<Sha1Sum, CompressedRuntimeFileError> {
+    compressed_target: &Path
+  ) -> Result<(AtomicFile, Sha1Sum), CompressedRuntimeFileError> {
     let monitor = self.get_monitor();
 
-    self.prepare_destination(compressed_target).map_err(CompressedRuntimeFileError::PrepareDestination)?;
-    let mut response = client.get(compressed_url).send().await?.error_for_status()?;
+    self.prepare_destination(compressed_target).await.map_err(CompressedRuntimeFileError::PrepareDestination)?;
+    let mut response = monitor.fetch(client, self.get_service(), compressed_url).await?.error_for_status()?;
     if let Some(content_len) = response.content_length() {
       monitor.set_total(content_len as usize);
     }
 
     let mut sha1 = Sha1::new();
-    let mut file = File::create(compressed_target).map_err(CompressedRuntimeFileError::WriteLzma)?;
+    let mut file = AtomicFile::create(compressed_target).await.map_err(CompressedRuntimeFileError::WriteLzma)?;
     while let Some(bytes) = response.chunk().await? {
-      file.write_all(&bytes).map_err(CompressedRuntimeFileError::WriteLzma)?;
-      file.flush().map_err(CompressedRuntimeFileError::WriteLzma)?;
+      monitor.checkpoint().await.map_err(|_| CompressedRuntimeFileError::Cancelled)?;
+      monitor.throttle(bytes.len()).await;
+      file.write_all(&bytes).await.map_err(CompressedRuntimeFileError::WriteLzma)?;
       sha1.update(&bytes);
     }
-    Ok(Sha1Sum::from(sha1))
+    Ok((file, Sha1Sum::from(sha1)))
   }
 
   fn set_status(&self, status: RuntimeFileDownloadableStatus) {
@@ -117,6 +131,10 @@ impl Downloadable for RuntimeFileDownloadable {
     &self.monitor
   }
 
+  fn get_service(&self) -> Option<Service> {
+    Some(Service::JavaRuntime)
+  }
+
   fn get_start_time(&self) -> Option<u64> {
     *self.start_time.lock().unwrap()
   }
@@ -145,13 +163,13 @@ impl Downloadable for RuntimeFileDownloadable {
     // 1. Check if the file is already downloaded
     if target.is_file() {
       if let Some(expected) = &expected_hash {
-        let actual = self.calculate_local_hash()?;
+        let actual = self.calculate_local_hash().await?;
         if actual == *expected {
           info!("Local file matches hash, using it");
           return Ok(());
         }
         // Hash mismatch, remove the file
-        fs::remove_file(target).map_err(DownloadError::RemoveFile)?;
+        tokio::fs::remove_file(target).await.map_err(DownloadError::RemoveFile)?;
       } else {
         info!("Local file exists, but no hash, assuming it's good");
         return Ok(());
@@ -162,44 +180,32 @@ impl Downloadable for RuntimeFileDownloadable {
     //    - If the file has no compressed version, download normally
     self.set_status(RuntimeFileDownloadableStatus::Downloading);
     let actual_hash = match &self.downloads.lzma {
-      None => {
-        let hash = self.try_download(client).await;
-        let _ = set_executable(&self.target_file);
-        hash
-      }
+      None => self.download_to_temp(client).await,
       Some(Download { url, sha1, .. }) => {
         let object_loc = self.object_loc.as_ref().unwrap();
 
         if object_loc.is_file() {
-          let mut compressed_file = File::open(object_loc).map_err(CompressedRuntimeFileError::ReadLzma)?;
-          let actual_compressed_hash = Sha1Sum::from_reader(&mut compressed_file).map_err(CompressedRuntimeFileError::ReadLzma)?;
+          let actual_compressed_hash = hash_file(object_loc).await.map_err(CompressedRuntimeFileError::ReadLzma)?;
           if &actual_compressed_hash != sha1 {
             warn!("Had local compressed but it was the wrong hash... expected {} but had {}", sha1, actual_compressed_hash);
-            fs::remove_file(object_loc).map_err(CompressedRuntimeFileError::RemoveLzma)?;
+            tokio::fs::remove_file(object_loc).await.map_err(CompressedRuntimeFileError::RemoveLzma)?;
           }
         }
 
         if !object_loc.is_file() {
-          let actual_compressed_hash = self.try_download_lzma(client, url, object_loc).await?;
-          // If compressed hash matches
-          if &actual_compressed_hash != sha1 {
-            let _ = fs::remove_file(object_loc);
-            return Err(DownloadError::ChecksumMismatch { expected: sha1.clone().into(), actual: actual_compressed_hash.into() });
-          }
+          let (compressed_file, actual_compressed_hash) = self.try_download_lzma(client, url, object_loc).await?;
+          // Only keep the compressed file if its hash matches
+          commit_verified(compressed_file, actual_compressed_hash, Some(sha1.as_slice())).await?;
         }
-        self.unpack_lzma(object_loc)
+        self.unpack_lzma(object_loc).await
       }
     };
-    let actual_hash = actual_hash?;
-
-    // 3. Verify download
-    if let Some(expected) = &expected_hash {
-      if actual_hash.as_slice() != expected {
-        // Try to remove. If it fails, we don't care
-        let _ = fs::remove_file(target);
-        return Err(DownloadError::ChecksumMismatch { expected: expected.clone(), actual: actual_hash.into() });
-      }
+    let (file, actual_hash) = actual_hash?;
 
+    // 3. Verify download and move it into place
+    let _ = set_executable(file.get_temp_path());
+    commit_verified(file, actual_hash, expected_hash.as_deref()).await?;
+    if expected_hash.is_some() {
       info!("Downloaded file and hash matched successfully");
     } else {
       info!("Successfully downloaded file but no checksum provided, assuming it's good");
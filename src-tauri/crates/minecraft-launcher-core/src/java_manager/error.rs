@@ -2,7 +2,10 @@ use std::path::PathBuf;
 
 use thiserror::Error;
 
-use crate::{ json::Sha1Sum, version_manager::downloader::{ downloadables::DownloadError, error::Error } };
+use crate::{
+  json::Sha1Sum,
+  version_manager::{ downloader::{ downloadables::DownloadError, error::Error }, error::MetadataError },
+};
 
 #[derive(Debug, Error)]
 pub enum InstallRuntimeError {
@@ -11,8 +14,12 @@ pub enum InstallRuntimeError {
     component: String,
   },
   #[error("Failed to install runtime")] InstallFailure,
+  #[error("Installing the '{component}' runtime needs the network, but offline mode is enabled")] Offline {
+    component: String,
+  },
   #[error("Failed to download manifest: {0}")] ManifestDownload(#[from] reqwest::Error),
   #[error("Failed to parse manifest: {0}")] ManifestParse(#[from] serde_json::Error),
+  #[error("Failed to fetch manifest: {0}")] ManifestFetch(#[from] MetadataError),
   #[error("Checksum mismatch. Expected {expected}, got {actual}")] ChecksumMismatch {
     expected: Sha1Sum,
     actual: Sha1Sum,
@@ -37,6 +44,7 @@ pub enum CompressedRuntimeFileError {
   #[error("Failed to read compressed file: {0}")] ReadLzma(#[source] std::io::Error),
   #[error("Couldn't prepare the destination folder for the compressed asset: {0}")] PrepareDestination(#[source] std::io::Error),
   #[error("Failed to download compressed asset: {0}")] Download(#[from] reqwest::Error),
+  #[error("Download was cancelled")] Cancelled,
   #[error(
     "Hash did not match downloaded compressed asset (Expected {}, downloaded {})",
     hex::encode(expected),
@@ -50,6 +58,11 @@ pub enum CompressedRuntimeFileError {
 
 impl From<CompressedRuntimeFileError> for DownloadError {
   fn from(val: CompressedRuntimeFileError) -> Self {
-    DownloadError::Other(Box::new(val))
+    match val {
+      CompressedRuntimeFileError::Cancelled => DownloadError::Cancelled,
+      CompressedRuntimeFileError::Download(err) => DownloadError::Download(err),
+      CompressedRuntimeFileError::ChecksumMismatch { expected, actual } => DownloadError::CompressedChecksumMismatch { expected, actual },
+      val => DownloadError::Other(Box::new(val)),
+    }
   }
 }
@@ -2,12 +2,17 @@ use std::{ collections::HashMap, ops::Deref };
 
 use serde::{ Deserialize, Serialize };
 
-use crate::json::{ manifest::rule::OperatingSystem, Date, Sha1Sum };
+use crate::{
+  json::{ manifest::rule::OperatingSystem, Date, Sha1Sum },
+  version_manager::{
+    endpoints::{ Endpoints, Service, JRE_INDEX_URL },
+    error::MetadataError,
+    metadata_cache::MetadataCache,
+  },
+};
 
 use super::jvm_platform_string;
 
-const JRE_INDEX_URL: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct JreIndex(pub HashMap<String, JreIndexEntry>);
@@ -21,8 +26,12 @@ impl Deref for JreIndex {
 }
 
 impl JreIndex {
-  pub async fn fetch(client: &reqwest::Client) -> reqwest::Result<JreIndex> {
-    client.get(JRE_INDEX_URL).send().await?.error_for_status()?.json().await
+  pub async fn fetch(client: &reqwest::Client) -> Result<JreIndex, MetadataError> {
+    Self::fetch_with(client, &Endpoints::default(), &MetadataCache::disabled()).await
+  }
+
+  pub async fn fetch_with(client: &reqwest::Client, endpoints: &Endpoints, cache: &MetadataCache) -> Result<JreIndex, MetadataError> {
+    cache.fetch_json(client, endpoints, Service::JavaRuntime, JRE_INDEX_URL, None).await
   }
 
   pub fn find(&self, os: &OperatingSystem, arch: Option<&str>) -> Option<&JreIndexEntry> {
@@ -5,13 +5,16 @@ use error::InstallRuntimeError;
 use index::{ JreIndex, RuntimeInfo };
 use log::{ debug, error };
 use manifest::{ JavaRuntimeFile, JreManifest };
-use md5::Digest;
 use reqwest::Client;
-use sha1::Sha1;
 
 use crate::{
-  json::{ manifest::rule::OperatingSystem, Sha1Sum },
-  version_manager::downloader::{ download_job::DownloadJob, downloadables::Downloadable, progress::ProgressReporter },
+  json::manifest::rule::OperatingSystem,
+  version_manager::{
+    downloader::{ download_job::DownloadJob, downloadables::Downloadable, progress::ProgressReporter },
+    endpoints::{ Endpoints, Service },
+    error::MetadataError,
+    metadata_cache::MetadataCache,
+  },
 };
 
 pub mod index;
@@ -27,6 +30,9 @@ pub struct JavaRuntimeManager {
   pub jre_manifest: Option<JreIndex>,
   pub os: OperatingSystem,
   pub arch: String,
+  pub endpoints: Endpoints,
+  /// Where the runtime index and manifests are cached. Disabled by default
+  pub metadata_cache: MetadataCache,
 }
 
 impl JavaRuntimeManager {
@@ -38,17 +44,33 @@ impl JavaRuntimeManager {
 
       os: OperatingSystem::get_current_platform(),
       arch: ARCH.to_string(),
+      endpoints: Endpoints::default(),
+      metadata_cache: MetadataCache::disabled(),
     }
   }
 
-  pub async fn load(runtimes_dir: &Path, client: &Client) -> Result<Self, reqwest::Error> {
+  /// Mirrors used for the runtime index, manifests and files. Call `refresh` afterwards
+  /// to load the index from them
+  pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+    self.endpoints = endpoints;
+    self
+  }
+
+  /// Caches the runtime index and manifests in `metadata_cache`, so `refresh` works offline.
+  /// Installing a runtime always needs the network
+  pub fn with_metadata_cache(mut self, metadata_cache: MetadataCache) -> Self {
+    self.metadata_cache = metadata_cache;
+    self
+  }
+
+  pub async fn load(runtimes_dir: &Path, client: &Client) -> Result<Self, MetadataError> {
     let mut manager = Self::new(runtimes_dir, client);
     manager.refresh().await?;
     Ok(manager)
   }
 
-  pub async fn refresh(&mut self) -> Result<(), reqwest::Error> {
-    self.jre_manifest = Some(JreIndex::fetch(&self.client).await?);
+  pub async fn refresh(&mut self) -> Result<(), MetadataError> {
+    self.jre_manifest = Some(JreIndex::fetch_with(&self.client, &self.endpoints, &self.metadata_cache).await?);
     Ok(())
   }
 }
@@ -66,6 +88,10 @@ impl JavaRuntimeManager {
   }
 
   pub async fn install_runtime(&self, objects_dir: &Path, component: &str, reporter: &ProgressReporter) -> Result<(), InstallRuntimeError> {
+    if self.metadata_cache.is_offline() {
+      return Err(InstallRuntimeError::Offline { component: component.to_string() });
+    }
+
     if let Some(info) = &self.jre_manifest {
       let entry = info.find(&self.os, Some(&self.arch)).ok_or(InstallRuntimeError::UnsupportedOS)?;
       let runtimes = entry.get(component).ok_or(InstallRuntimeError::RuntimeNotFound { component: component.to_string() })?;
@@ -95,17 +121,14 @@ impl JavaRuntimeManager {
 
     debug!("Downloading {}", manifest.url);
     let manifest: JreManifest = {
-      let mut bytes = vec![];
-      let mut response = self.client.get(&manifest.url).send().await?.error_for_status()?;
-      let mut sha1 = Sha1::new();
-      while let Some(chunk) = response.chunk().await? {
-        bytes.extend_from_slice(&chunk);
-        sha1.update(&chunk);
-      }
-      let sha1 = Sha1Sum::from(sha1);
-      if sha1 != manifest.sha1 {
-        return Err(InstallRuntimeError::ChecksumMismatch { expected: manifest.sha1.clone(), actual: sha1 });
-      }
+      let bytes = self.metadata_cache
+        .fetch(&self.client, &self.endpoints, Service::JavaRuntime, &manifest.url, Some(&manifest.sha1)).await
+        .map_err(|err| {
+          match err {
+            MetadataError::ChecksumMismatch { expected, actual, .. } => InstallRuntimeError::ChecksumMismatch { expected, actual },
+            err => err.into(),
+          }
+        })?;
       serde_json::from_slice(&bytes)?
     };
 
@@ -113,7 +136,7 @@ impl JavaRuntimeManager {
     debug!("Downloaded java manifest. Installing {}", runtime_dir.display());
 
     fs::create_dir_all(&runtime_dir).map_err(|err| InstallRuntimeError::CreateFolder { folder: runtime_dir.clone(), source: err })?;
-    let job = DownloadJob::new("Java Runtime").with_progress_reporter(reporter).with_client(self.client.clone());
+    let job = DownloadJob::new("Java Runtime").with_progress_reporter(reporter).with_client(self.client.clone()).with_endpoints(&self.endpoints);
     let mut downloadables: Vec<Box<dyn Downloadable + Send + Sync>> = vec![];
     for (name, file) in &manifest.files {
       let target = runtime_dir.join(name);
@@ -2,7 +2,7 @@ use serde::{ Deserialize, Serialize };
 
 use crate::json::EnvironmentFeatures;
 
-use super::rule::{ Rule, RuleAction };
+use super::rule::{ Rule, RuleAction, TargetPlatform };
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -33,7 +33,15 @@ impl Argument {
     }
   }
 
+  pub fn apply_for_platform(&self, platform: &TargetPlatform, env_features: &EnvironmentFeatures) -> Option<Vec<&String>> {
+    if self.applies_to_platform(platform, env_features) { Some(self.value()) } else { None }
+  }
+
   pub fn applies_to_current_environment(&self, env_features: &EnvironmentFeatures) -> bool {
+    self.applies_to_platform(TargetPlatform::current(), env_features)
+  }
+
+  pub fn applies_to_platform(&self, platform: &TargetPlatform, env_features: &EnvironmentFeatures) -> bool {
     if let Argument::Object { rules, .. } = self {
       // TODO: needed?
       if rules.is_empty() {
@@ -42,7 +50,7 @@ impl Argument {
 
       let mut action = RuleAction::Disallow;
       for rule in rules {
-        if let Some(applied_action) = rule.get_applied_action(env_features) {
+        if let Some(applied_action) = rule.get_applied_action_for_platform(platform, env_features) {
           action = applied_action;
         }
       }
@@ -2,7 +2,7 @@ use std::{ fmt::{ Debug, Display }, path::{ Path, PathBuf } };
 
 use serde::{ Deserialize, Serialize };
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(try_from = "String", into = "String")]
 pub struct Artifact {
   original_descriptor: Option<String>,
@@ -55,6 +55,27 @@ impl Artifact {
     root
   }
 
+  /// Returns a copy of the artifact pointing to another version and classifier
+  pub fn with_coordinates(&self, version: &str, classifier: Option<String>) -> Self {
+    Self {
+      original_descriptor: None,
+      group_id: self.group_id.clone(),
+      artifact_id: self.artifact_id.clone(),
+      version: version.to_string(),
+      classifier,
+      ext: self.ext.clone(),
+    }
+  }
+
+  /// Returns `group:artifact(:classifier)`, which identifies the library regardless of its version
+  pub fn get_versionless_descriptor(&self) -> String {
+    let mut descriptor = format!("{}:{}", self.group_id.join("."), self.artifact_id);
+    if let Some(classifier) = &self.classifier {
+      descriptor.push_str(&format!(":{}", classifier));
+    }
+    descriptor
+  }
+
   pub fn get_descriptor(&self) -> String {
     if let Some(original_descriptor) = &self.original_descriptor {
       original_descriptor.clone()
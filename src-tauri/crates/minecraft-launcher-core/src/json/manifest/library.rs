@@ -1,10 +1,10 @@
-use std::{ collections::HashMap, env::consts::ARCH, path::Path };
+use std::{ collections::HashMap, path::Path };
 
 use serde::{ Deserialize, Serialize };
 
 use crate::json::EnvironmentFeatures;
 
-use super::{ artifact::Artifact, rule::{ OperatingSystem, Rule, RuleAction }, DownloadInfo };
+use super::{ artifact::Artifact, rule::{ OperatingSystem, Rule, RuleAction, TargetPlatform }, DownloadInfo };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -24,13 +24,17 @@ pub struct Library {
 
 impl Library {
   pub fn applies_to_current_environment(&self, env_features: &EnvironmentFeatures) -> bool {
+    self.applies_to_platform(TargetPlatform::current(), env_features)
+  }
+
+  pub fn applies_to_platform(&self, platform: &TargetPlatform, env_features: &EnvironmentFeatures) -> bool {
     if self.rules.is_empty() {
       return true;
     }
 
     let mut action = RuleAction::Disallow;
     for rule in &self.rules {
-      if let Some(applied_action) = rule.get_applied_action(env_features) {
+      if let Some(applied_action) = rule.get_applied_action_for_platform(platform, env_features) {
         action = applied_action;
       }
     }
@@ -46,13 +50,13 @@ impl Library {
     new_artifact.get_path_string()
   }
 
-  pub fn get_artifact_classifier(&self, os: &OperatingSystem) -> Option<Option<String>> {
+  pub fn get_artifact_classifier(&self, platform: &TargetPlatform) -> Option<Option<String>> {
     if self.natives.is_empty() {
       return Some(None);
     }
 
-    if let Some(classifier) = self.natives.get(os) {
-      let arch = if ARCH == "x86" { "32" } else { "64" };
+    if let Some(classifier) = self.natives.get(&platform.os) {
+      let arch = if platform.is_32_bit() { "32" } else { "64" };
       let classifier = classifier.replace("${arch}", arch);
       return Some(Some(classifier));
     }
@@ -60,8 +64,8 @@ impl Library {
     None
   }
 
-  pub fn get_download_info(&self, os: &OperatingSystem) -> Option<DownloadInfo> {
-    let classifier = self.get_artifact_classifier(os)?;
+  pub fn get_download_info(&self, platform: &TargetPlatform) -> Option<DownloadInfo> {
+    let classifier = self.get_artifact_classifier(platform)?;
 
     if let Some(downloads) = &self.downloads {
       downloads.get_download_info(classifier)
@@ -6,7 +6,7 @@ use download::{ DownloadInfo, DownloadType };
 use java::JavaVersionInfo;
 use library::Library;
 use logging::LoggingEntry;
-use rule::{ OperatingSystem, Rule, RuleAction };
+use rule::{ Rule, RuleAction, TargetPlatform };
 use serde::{ Deserialize, Serialize };
 
 use super::{ Date, EnvironmentFeatures, MCVersion, ReleaseType, VersionInfo };
@@ -61,22 +61,23 @@ pub struct VersionManifest {
 
 impl VersionManifest {
   pub fn get_relevant_libraries(&self, env_features: &EnvironmentFeatures) -> Vec<&Library> {
+    self.get_relevant_libraries_for_platform(TargetPlatform::current(), env_features)
+  }
+
+  pub fn get_relevant_libraries_for_platform(&self, platform: &TargetPlatform, env_features: &EnvironmentFeatures) -> Vec<&Library> {
     self.libraries
       .iter()
-      .filter(|lib| lib.applies_to_current_environment(env_features))
+      .filter(|lib| lib.applies_to_platform(platform, env_features))
       .collect()
   }
 
-  pub fn get_required_files(&self, os: &OperatingSystem, env_features: &EnvironmentFeatures) -> HashSet<String> {
+  pub fn get_required_files(&self, platform: &TargetPlatform, env_features: &EnvironmentFeatures) -> HashSet<String> {
     let mut set = HashSet::new();
-    let libraries = self.get_relevant_libraries(env_features);
+    let libraries = self.get_relevant_libraries_for_platform(platform, env_features);
     for library in libraries {
-      if !library.natives.is_empty() {
-        if let Some(native) = library.natives.get(os) {
-          set.insert(format!("libraries/{}", library.get_artifact_path(Some(native.clone()))));
-        }
-      } else {
-        set.insert(format!("libraries/{}", library.get_artifact_path(None)));
+      // Libraries with natives for other operating systems only are skipped
+      if let Some(classifier) = library.get_artifact_classifier(platform) {
+        set.insert(format!("libraries/{}", library.get_artifact_path(classifier)));
       }
     }
     set
@@ -95,13 +96,17 @@ impl VersionManifest {
   }
 
   pub fn applies_to_current_environment(&self, env_features: &EnvironmentFeatures) -> bool {
+    self.applies_to_platform(TargetPlatform::current(), env_features)
+  }
+
+  pub fn applies_to_platform(&self, platform: &TargetPlatform, env_features: &EnvironmentFeatures) -> bool {
     if self.compatibility_rules.is_empty() {
       return true;
     }
 
     let mut action = RuleAction::Disallow;
     for rule in &self.compatibility_rules {
-      if let Some(applied_action) = rule.get_applied_action(env_features) {
+      if let Some(applied_action) = rule.get_applied_action_for_platform(platform, env_features) {
         action = applied_action;
       }
     }
@@ -109,9 +114,9 @@ impl VersionManifest {
     action == RuleAction::Allow
   }
 
-  pub fn get_classpath(&self, _os: &OperatingSystem, mc_dir: &Path, env_features: &EnvironmentFeatures) -> Vec<PathBuf> {
+  pub fn get_classpath(&self, platform: &TargetPlatform, mc_dir: &Path, env_features: &EnvironmentFeatures) -> Vec<PathBuf> {
     let mut vec = vec![];
-    let libraries = self.get_relevant_libraries(env_features);
+    let libraries = self.get_relevant_libraries_for_platform(platform, env_features);
     for library in libraries {
       if library.natives.is_empty() {
         vec.push(mc_dir.join("libraries").join(library.get_artifact_path(None).replace('/', MAIN_SEPARATOR_STR)));
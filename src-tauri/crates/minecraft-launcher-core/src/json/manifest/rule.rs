@@ -1,4 +1,4 @@
-use std::{ collections::HashMap, env::consts::{ OS, ARCH }, fmt::Debug };
+use std::{ collections::HashMap, env::consts::{ OS, ARCH }, fmt::Debug, sync::OnceLock };
 
 use os_info::Version;
 use regex::Regex;
@@ -36,9 +36,15 @@ impl Rule {
   /// otherwise, it returns `None` if any condition is not met.
 
   pub fn get_applied_action(&self, env_features: &EnvironmentFeatures) -> Option<RuleAction> {
+    self.get_applied_action_for_platform(TargetPlatform::current(), env_features)
+  }
+
+  /// Same as `Rule::get_applied_action`, but evaluates the operating system restrictions
+  /// against `platform` instead of the host.
+  pub fn get_applied_action_for_platform(&self, platform: &TargetPlatform, env_features: &EnvironmentFeatures) -> Option<RuleAction> {
     // If there's some operating system restriction, check if it's met
     if let Some(os) = &self.os {
-      if !os.is_current_operating_system() {
+      if !os.matches(platform) {
         return None;
       }
     }
@@ -86,23 +92,27 @@ pub struct OsRestriction {
 
 impl OsRestriction {
   pub fn is_current_operating_system(&self) -> bool {
+    self.matches(TargetPlatform::current())
+  }
+
+  pub fn matches(&self, platform: &TargetPlatform) -> bool {
     let OsRestriction { name, arch, version } = &self;
 
     if let Some(name) = name {
-      if &OperatingSystem::get_current_platform() != name {
+      if &platform.os != name {
         return false;
       }
     }
 
     if let Some(arch) = arch {
-      if &get_arch() != arch {
+      if &platform.arch != arch {
         return false;
       }
     }
 
     if let Some(version) = version {
       if let Ok(regex) = Regex::new(version) {
-        if !regex.is_match(&get_os_version()) {
+        if !regex.is_match(&platform.os_version) {
           return false;
         }
       }
@@ -112,6 +122,34 @@ impl OsRestriction {
   }
 }
 
+/// The platform rules are evaluated against.
+///
+/// Defaults to the host (see `TargetPlatform::current`), but can describe any other platform
+/// in order to compute the files a version needs there.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TargetPlatform {
+  pub os: OperatingSystem,
+  /// Architecture, as named in version manifests (`x64`, `x86`, `arm64`, ...)
+  pub arch: String,
+  pub os_version: String,
+}
+
+impl TargetPlatform {
+  pub fn new(os: OperatingSystem, arch: impl Into<String>, os_version: impl Into<String>) -> Self {
+    Self { os, arch: arch.into(), os_version: os_version.into() }
+  }
+
+  /// The platform the launcher is running on
+  pub fn current() -> &'static Self {
+    static CURRENT: OnceLock<TargetPlatform> = OnceLock::new();
+    CURRENT.get_or_init(|| Self::new(OperatingSystem::get_current_platform(), get_arch(), get_os_version()))
+  }
+
+  pub fn is_32_bit(&self) -> bool {
+    self.arch == "x86"
+  }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Eq, Clone, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum OperatingSystem {
@@ -168,6 +206,8 @@ pub fn get_arch() -> String {
   let arch = match ARCH {
     "x86_64" => "x64",
     "x86" => "x86",
+    "aarch64" => "arm64",
+    "arm" => "arm32",
     s => s,
   };
   arch.to_string()
@@ -180,3 +220,35 @@ pub fn get_os_version() -> String {
     _ => "unknown".to_string(),
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  #[test]
+  fn test_rule_for_other_platform() {
+    let rule: Rule = serde_json::from_value(json!({ "action": "allow", "os": { "name": "osx", "version": "^10\\.5\\.\\d$" } })).unwrap();
+    let env_features = EnvironmentFeatures::default();
+
+    let mac = TargetPlatform::new(OperatingSystem::Osx, "x64", "10.5.8");
+    let new_mac = TargetPlatform::new(OperatingSystem::Osx, "arm64", "14.1.0");
+    let windows = TargetPlatform::new(OperatingSystem::Windows, "x64", "10.0.19045");
+
+    assert_eq!(rule.get_applied_action_for_platform(&mac, &env_features), Some(RuleAction::Allow));
+    assert_eq!(rule.get_applied_action_for_platform(&new_mac, &env_features), None);
+    assert_eq!(rule.get_applied_action_for_platform(&windows, &env_features), None);
+  }
+
+  #[test]
+  fn test_rule_arch() {
+    let rule: Rule = serde_json::from_value(json!({ "action": "disallow", "os": { "arch": "x86" } })).unwrap();
+    let env_features = EnvironmentFeatures::default();
+
+    let x86 = TargetPlatform::new(OperatingSystem::Windows, "x86", "10.0");
+    let x64 = TargetPlatform::new(OperatingSystem::Windows, "x64", "10.0");
+    assert_eq!(rule.get_applied_action_for_platform(&x86, &env_features), Some(RuleAction::Disallow));
+    assert_eq!(rule.get_applied_action_for_platform(&x64, &env_features), None);
+  }
+}
@@ -1,8 +1,10 @@
-use std::fmt::Display;
+use std::{ cmp::Ordering, collections::HashMap, fmt::Display };
 
 use regex::Regex;
 use serde::{ Deserialize, Serialize };
 
+use super::VersionInfo;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 #[serde(untagged, from = "String", into = "String")]
 pub enum MCVersion {
@@ -18,6 +20,70 @@ impl MCVersion {
   pub fn new(value: impl AsRef<str>) -> MCVersion {
     MCVersion::from(value.as_ref().to_string())
   }
+
+  /// The release this version belongs to and how far along it is, for releases, pre-releases and RCs
+  fn get_numbered(&self) -> Option<((i32, i32, i32), Stage, i32)> {
+    match *self {
+      Self::Release(major, minor, patch) => Some(((major, minor, patch.unwrap_or(0)), Stage::Release, 0)),
+      Self::PreReleaseNew(major, minor, patch, pre) | Self::PreReleaseOld(major, minor, patch, pre) => {
+        Some(((major, minor, patch.unwrap_or(0)), Stage::PreRelease, pre))
+      }
+      Self::ReleaseCandidate(major, minor, patch, rc) => Some(((major, minor, patch.unwrap_or(0)), Stage::ReleaseCandidate, rc)),
+      Self::Snapshot(..) | Self::Other(_) => None,
+    }
+  }
+
+  /// Without a timeline, only the ids are used
+  fn get_sort_key(&self, timeline: Option<&VersionTimeline>) -> SortKey {
+    let variant = match self {
+      Self::Release(..) => 0,
+      Self::Snapshot(..) => 1,
+      Self::PreReleaseNew(..) => 2,
+      Self::PreReleaseOld(..) => 3,
+      Self::ReleaseCandidate(..) => 4,
+      Self::Other(_) => 5,
+    };
+    let mut key = SortKey {
+      group: Group::Placed,
+      release: (0, 0, 0),
+      stage: Stage::Release,
+      number: 0,
+      time: None,
+      variant,
+      id: self.to_string(),
+    };
+    if let Some((release, stage, number)) = self.get_numbered() {
+      (key.release, key.stage, key.number) = (release, stage, number);
+    } else if let Some((release, stage)) = timeline.and_then(|timeline| timeline.placements.get(self)) {
+      (key.release, key.stage) = (*release, *stage);
+      key.time = timeline.and_then(|timeline| timeline.release_times.get(self)).copied();
+    } else if let Self::Snapshot(year, week, _) = self {
+      // Without a release time, a snapshot is most likely newer than anything known
+      key.group = Group::UnplacedSnapshot;
+      key.release = (*year, *week, 0);
+    } else {
+      key.group = Group::Unplaced;
+    }
+    key
+  }
+}
+
+impl PartialOrd for MCVersion {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+/// Only the ids are compared: `Other` ids go first, then releases, pre-releases and RCs by their
+/// numbers, then snapshots by their year and week. Ids of the same version are ordered by their
+/// text (`1.20` < `1.20.0`) to agree with `Eq`.
+///
+/// Snapshots and `Other` ids can only be placed among numbered versions by their release time,
+/// see `VersionTimeline::cmp`.
+impl Ord for MCVersion {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.get_sort_key(None).cmp(&other.get_sort_key(None))
+  }
 }
 
 impl From<String> for MCVersion {
@@ -184,3 +250,129 @@ impl From<MCVersion> for String {
     val.to_string()
   }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Group {
+  Unplaced,
+  Placed,
+  UnplacedSnapshot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+  /// Snapshots and specials released before the release they lead up to
+  Development,
+  PreRelease,
+  ReleaseCandidate,
+  Release,
+  /// Snapshots newer than every known release
+  Upcoming,
+}
+
+/// The fields are compared in order; `variant` and `id` only break ties so the order agrees with `Eq`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+  group: Group,
+  release: (i32, i32, i32),
+  stage: Stage,
+  number: i32,
+  time: Option<i64>,
+  variant: u8,
+  id: String,
+}
+
+/// When versions were released, so snapshots and `Other` ids (old alphas and betas, april fools
+/// versions, modded ids) can be ordered against numbered versions. They are placed right before
+/// the lowest release published after them, or after the latest release if there is none yet.
+///
+/// `VersionManager` keeps one with every remote and installed version, see `VersionManager::get_timeline`.
+#[derive(Debug, Clone, Default)]
+pub struct VersionTimeline {
+  /// Release times in milliseconds
+  release_times: HashMap<MCVersion, i64>,
+  /// The release each snapshot or `Other` id with a known release time is placed at
+  placements: HashMap<MCVersion, ((i32, i32, i32), Stage)>,
+}
+
+impl VersionTimeline {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Records when each version was released
+  pub fn register<'a, T: VersionInfo + 'a>(&mut self, versions: impl IntoIterator<Item = &'a T>) {
+    for version in versions {
+      let time = version.get_release_time().inner().timestamp_millis();
+      self.release_times.insert(version.get_id().clone(), time);
+    }
+    self.place_unnumbered();
+  }
+
+  /// Orders the versions like `Ord`, placing snapshots and `Other` ids by their release time.
+  /// Snapshots without one go after every other version, and `Other` ids without one before every other version.
+  pub fn cmp(&self, a: &MCVersion, b: &MCVersion) -> Ordering {
+    a.get_sort_key(Some(self)).cmp(&b.get_sort_key(Some(self)))
+  }
+
+  /// Like `cmp`, except that ids of the same numbered version are equal, like `1.20` and `1.20.0`.
+  /// Used by `VersionRange`
+  pub fn cmp_release(&self, a: &MCVersion, b: &MCVersion) -> Ordering {
+    let (a_key, b_key) = (a.get_sort_key(Some(self)), b.get_sort_key(Some(self)));
+    let ordering = (a_key.group, a_key.release, a_key.stage, a_key.number, a_key.time).cmp(
+      &(b_key.group, b_key.release, b_key.stage, b_key.number, b_key.time)
+    );
+    // Without numbers, different ids are different versions
+    if ordering.is_eq() && (a.get_numbered().is_none() || b.get_numbered().is_none()) {
+      return a_key.cmp(&b_key);
+    }
+    ordering
+  }
+
+  /// The `(major, minor, patch)` of the release `version` leads up to, if it's known.
+  /// Snapshots newer than every known release don't have one
+  pub fn get_release_line(&self, version: &MCVersion) -> Option<(i32, i32, i32)> {
+    if let Some((release, _, _)) = version.get_numbered() {
+      return Some(release);
+    }
+    match self.placements.get(version) {
+      Some((release, Stage::Development)) => Some(*release),
+      _ => None,
+    }
+  }
+
+  fn place_unnumbered(&mut self) {
+    let mut releases: Vec<_> = self.release_times
+      .iter()
+      .filter(|(version, _)| matches!(version, MCVersion::Release(..)))
+      .filter_map(|(version, time)| Some((*time, version.get_numbered()?.0)))
+      .collect();
+    releases.sort();
+    // The lowest release published at or after each one, as patches of older versions
+    // can come out after newer snapshots (e.g. 1.16.5 after 20w45a)
+    let mut lowest_after: Vec<_> = releases
+      .iter()
+      .map(|(_, release)| *release)
+      .collect();
+    for i in (0..lowest_after.len().saturating_sub(1)).rev() {
+      lowest_after[i] = lowest_after[i].min(lowest_after[i + 1]);
+    }
+    let latest = releases
+      .iter()
+      .map(|(_, release)| *release)
+      .max();
+
+    self.placements = self.release_times
+      .iter()
+      .filter(|(version, _)| version.get_numbered().is_none())
+      .filter_map(|(version, time)| {
+        let next = releases.partition_point(|(release_time, _)| release_time <= time);
+        let placement = match lowest_after.get(next) {
+          Some(release) => (*release, Stage::Development),
+          None => (latest?, Stage::Upcoming),
+        };
+        Some((version.clone(), placement))
+      })
+      .collect();
+  }
+}
+
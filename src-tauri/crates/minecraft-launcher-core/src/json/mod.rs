@@ -4,12 +4,14 @@ mod sha1_checksum;
 mod release_type;
 mod version_info;
 mod env_features;
+mod version_range;
 
 pub mod manifest;
 
-pub use minecraft_version::MCVersion;
+pub use minecraft_version::{ MCVersion, VersionTimeline };
 pub use date::Date;
 pub use sha1_checksum::Sha1Sum;
 pub use release_type::ReleaseType;
 pub use version_info::VersionInfo;
 pub use env_features::EnvironmentFeatures;
+pub use version_range::{ VersionRange, VersionRangeError };
@@ -0,0 +1,334 @@
+use std::{ fmt::Display, str::FromStr };
+
+use serde::{ Deserialize, Serialize };
+use thiserror::Error;
+
+use super::{ MCVersion, VersionTimeline };
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VersionRangeError {
+  #[error("Missing version after {0:?}")] MissingVersion(String),
+  #[error("Invalid wildcard {0:?}, expected something like 1.20.x")] InvalidWildcard(String),
+  #[error("Unclosed interval {0:?}")] UnclosedInterval(String),
+  #[error("Invalid interval {0:?}")] InvalidInterval(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+  Eq,
+  Gt,
+  Ge,
+  Lt,
+  Le,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Constraint {
+  Compare(Operator, MCVersion),
+  /// `1.20.x`, matches the versions leading up to and released as part of that line
+  Line(Vec<i32>),
+}
+
+impl Constraint {
+  fn matches(&self, version: &MCVersion, timeline: &VersionTimeline) -> bool {
+    match self {
+      Self::Compare(operator, other) => {
+        let ordering = timeline.cmp_release(version, other);
+        match operator {
+          Operator::Eq => ordering.is_eq(),
+          Operator::Gt => ordering.is_gt(),
+          Operator::Ge => ordering.is_ge(),
+          Operator::Lt => ordering.is_lt(),
+          Operator::Le => ordering.is_le(),
+        }
+      }
+      Self::Line(prefix) => {
+        let Some((major, minor, patch)) = timeline.get_release_line(version) else {
+          return false;
+        };
+        prefix.iter().zip([major, minor, patch]).all(|(a, b)| *a == b)
+      }
+    }
+  }
+}
+
+/// A set of versions, compared with `VersionTimeline::cmp_release` (so `1.20` and `1.20.0` are the
+/// same version). Accepts:
+/// - Comparators separated by spaces, which must all match: `>=1.16 <1.20`, `=1.20.1`, `1.20.1`
+/// - Wildcards: `1.20.x` (or `1.20.*`), `1.x`, `*`
+/// - Maven intervals, joined with commas to match any of them: `[1.18,1.19)`, `(,1.12.2]`, `[1.20.1]`
+/// - Any of the above separated by `||`, to match any of them
+///
+/// Ids with spaces (like `1.14 Pre-Release 1`) can only be used inside Maven intervals.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionRange {
+  source: String,
+  /// Matches if all the constraints of any of them match
+  alternatives: Vec<Vec<Constraint>>,
+}
+
+impl VersionRange {
+  /// Snapshots and `Other` ids are placed with `timeline`, see `VersionManager::get_timeline`
+  pub fn contains(&self, version: &MCVersion, timeline: &VersionTimeline) -> bool {
+    self.alternatives.iter().any(|constraints| constraints.iter().all(|constraint| constraint.matches(version, timeline)))
+  }
+}
+
+impl FromStr for VersionRange {
+  type Err = VersionRangeError;
+
+  fn from_str(source: &str) -> Result<Self, Self::Err> {
+    let mut alternatives = vec![];
+    for alternative in source.split("||").map(str::trim) {
+      if alternative.starts_with(['[', '(']) {
+        alternatives.extend(parse_intervals(alternative)?);
+      } else {
+        alternatives.push(parse_comparators(alternative)?);
+      }
+    }
+    Ok(Self { source: source.to_string(), alternatives })
+  }
+}
+
+fn parse_comparators(value: &str) -> Result<Vec<Constraint>, VersionRangeError> {
+  let mut constraints = vec![];
+  let mut tokens = value.split_whitespace();
+  while let Some(token) = tokens.next() {
+    if token == "*" {
+      continue;
+    }
+    let (operator, version) = [(">=", Operator::Ge), ("<=", Operator::Le), (">", Operator::Gt), ("<", Operator::Lt), ("=", Operator::Eq)]
+      .into_iter()
+      .find_map(|(prefix, operator)| token.strip_prefix(prefix).map(|version| (Some(operator), version)))
+      .unwrap_or((None, token));
+    // Allows `>= 1.16`
+    let version = match version {
+      "" => tokens.next().ok_or_else(|| VersionRangeError::MissingVersion(token.to_string()))?,
+      version => version,
+    };
+
+    if let Some(prefix) = version.strip_suffix(".x").or(version.strip_suffix(".X")).or(version.strip_suffix(".*")) {
+      let prefix = prefix
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<i32>>>()
+        .filter(|prefix| prefix.len() <= 2 && operator.is_none())
+        .ok_or_else(|| VersionRangeError::InvalidWildcard(token.to_string()))?;
+      constraints.push(Constraint::Line(prefix));
+    } else {
+      constraints.push(Constraint::Compare(operator.unwrap_or(Operator::Eq), MCVersion::new(version)));
+    }
+  }
+  Ok(constraints)
+}
+
+fn parse_intervals(mut value: &str) -> Result<Vec<Vec<Constraint>>, VersionRangeError> {
+  let mut intervals = vec![];
+  while !value.is_empty() {
+    let end = value.find([']', ')']).ok_or_else(|| VersionRangeError::UnclosedInterval(value.to_string()))?;
+    let (interval, rest) = value.split_at(end + 1);
+    intervals.push(parse_interval(interval)?);
+    value = rest.trim_start().trim_start_matches(',').trim_start();
+  }
+  Ok(intervals)
+}
+
+fn parse_interval(interval: &str) -> Result<Vec<Constraint>, VersionRangeError> {
+  let invalid = || VersionRangeError::InvalidInterval(interval.to_string());
+  let inclusive_start = match interval.chars().next() {
+    Some('[') => true,
+    Some('(') => false,
+    _ => {
+      return Err(invalid());
+    }
+  };
+  let inclusive_end = interval.ends_with(']');
+  let inner = &interval[1..interval.len() - 1];
+
+  let Some((start, end)) = inner.split_once(',') else {
+    // `[1.20.1]` is an exact version
+    let version = inner.trim();
+    if !inclusive_start || !inclusive_end || version.is_empty() {
+      return Err(invalid());
+    }
+    return Ok(vec![Constraint::Compare(Operator::Eq, MCVersion::new(version))]);
+  };
+  if end.contains(',') {
+    return Err(invalid());
+  }
+
+  let mut constraints = vec![];
+  if !start.trim().is_empty() {
+    let operator = if inclusive_start { Operator::Ge } else { Operator::Gt };
+    constraints.push(Constraint::Compare(operator, MCVersion::new(start.trim())));
+  }
+  if !end.trim().is_empty() {
+    let operator = if inclusive_end { Operator::Le } else { Operator::Lt };
+    constraints.push(Constraint::Compare(operator, MCVersion::new(end.trim())));
+  }
+  Ok(constraints)
+}
+
+impl TryFrom<String> for VersionRange {
+  type Error = VersionRangeError;
+  fn try_from(value: String) -> Result<Self, Self::Error> {
+    value.parse()
+  }
+}
+
+impl From<VersionRange> for String {
+  fn from(val: VersionRange) -> Self {
+    val.source
+  }
+}
+
+impl Display for VersionRange {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.source)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::json::{ Date, ReleaseType, VersionInfo };
+
+  use super::*;
+
+  struct Released(MCVersion, Date);
+
+  impl VersionInfo for Released {
+    fn get_id(&self) -> &MCVersion {
+      &self.0
+    }
+    fn get_type(&self) -> &ReleaseType {
+      &ReleaseType::Release
+    }
+    fn get_updated_time(&self) -> &Date {
+      &self.1
+    }
+    fn get_release_time(&self) -> &Date {
+      &self.1
+    }
+  }
+
+  fn timeline(versions: &[(&str, &str)]) -> VersionTimeline {
+    let versions: Vec<_> = versions
+      .iter()
+      .map(|(id, time)| Released(MCVersion::new(id), chrono::DateTime::parse_from_rfc3339(time).unwrap().into()))
+      .collect();
+    let mut timeline = VersionTimeline::new();
+    timeline.register(&versions);
+    timeline
+  }
+
+  #[test]
+  fn test_version_order() {
+    let timeline = timeline(
+      &[
+        ("b1.7.3", "2011-07-08T00:00:00+00:00"),
+        ("1.0", "2011-11-18T00:00:00+00:00"),
+        ("1.RV-Pre1", "2016-03-31T00:00:00+00:00"),
+        ("1.9.3", "2016-05-10T00:00:00+00:00"),
+        ("1.16.4", "2020-11-02T00:00:00+00:00"),
+        ("20w45a", "2020-11-04T00:00:00+00:00"),
+        ("1.16.5", "2021-01-15T00:00:00+00:00"),
+        ("1.17", "2021-06-08T00:00:00+00:00"),
+        ("24w14potato", "2024-04-01T00:00:00+00:00"),
+        ("1.21", "2024-06-13T00:00:00+00:00"),
+      ]
+    );
+    let mut versions: Vec<_> = [
+      "1.21",
+      "1.21-pre1",
+      "24w14potato",
+      "1.17",
+      "1.16.5",
+      "20w45a",
+      "1.16.4",
+      "1.14 Pre-Release 4",
+      "1.14",
+      "1.9.3",
+      "1.RV-Pre1",
+      "1.0",
+      "b1.7.3",
+      "1.21.5-rc1",
+      "1.21.5-pre1",
+      "1.21.5",
+      "99w01a",
+      "custom",
+    ]
+      .iter()
+      .map(MCVersion::new)
+      .collect();
+    versions.sort_by(|a, b| timeline.cmp(a, b));
+    let sorted: Vec<_> = versions
+      .iter()
+      .map(ToString::to_string)
+      .collect();
+    assert_eq!(sorted, [
+      "custom",
+      "b1.7.3",
+      "1.0",
+      "1.RV-Pre1",
+      "1.9.3",
+      "1.14 Pre-Release 4",
+      "1.14",
+      "1.16.4",
+      "20w45a",
+      "1.16.5",
+      "1.17",
+      "24w14potato",
+      "1.21-pre1",
+      "1.21",
+      "1.21.5-pre1",
+      "1.21.5-rc1",
+      "1.21.5",
+      "99w01a",
+    ]);
+    assert!(MCVersion::new("1.20") < MCVersion::new("1.20.0"));
+    assert!(timeline.cmp(&MCVersion::new("1.20"), &MCVersion::new("1.20.0")).is_lt());
+    assert!(timeline.cmp_release(&MCVersion::new("1.20"), &MCVersion::new("1.20.0")).is_eq());
+
+    // `Ord` only looks at the ids: snapshots after every numbered version, `Other` ids first
+    assert!(MCVersion::new("20w45a") > MCVersion::new("1.21.5"));
+    assert!(MCVersion::new("20w45a") < MCVersion::new("24w14a"));
+    assert!(MCVersion::new("b1.7.3") < MCVersion::new("1.0"));
+    assert!(MCVersion::new("b1.7.3") > MCVersion::new("a1.0.4"));
+    assert!(MCVersion::new("1.13") >= MCVersion::new("1.13"));
+  }
+
+  #[test]
+  fn test_version_range() {
+    let range = |range: &str| range.parse::<VersionRange>().unwrap();
+    let timeline = VersionTimeline::new();
+    let contains = |range: &VersionRange, version: &str| range.contains(&MCVersion::new(version), &timeline);
+
+    let bounded = range(">=1.16 <1.20");
+    assert!(contains(&bounded, "1.16") && contains(&bounded, "1.19.4") && contains(&bounded, "1.20-pre1"));
+    assert!(!contains(&bounded, "1.15.2") && !contains(&bounded, "1.20"));
+    assert_eq!(range(">= 1.16 < 1.20"), VersionRange { source: ">= 1.16 < 1.20".to_string(), ..bounded });
+
+    let line = range("1.20.x");
+    assert!(contains(&line, "1.20") && contains(&line, "1.20.6") && contains(&line, "1.20.1-rc1"));
+    assert!(!contains(&line, "1.2") && !contains(&line, "1.21") && !contains(&line, "23w13a_or_b"));
+    assert!(contains(&range("1.*"), "1.8.9") && contains(&range("*"), "custom"));
+
+    let maven = range("[1.18,1.19),(,1.12.2]");
+    assert!(contains(&maven, "1.18") && contains(&maven, "1.18.2") && contains(&maven, "1.12.2"));
+    assert!(!contains(&maven, "1.19") && !contains(&maven, "1.13"));
+    assert!(contains(&range("[1.14 Pre-Release 1]"), "1.14 Pre-Release 1"));
+    assert!(contains(&range("1.7.10 || [1.12,1.12.2]"), "1.12.1"));
+
+    // Only `Ord` tells `1.20` and `1.20.0` apart
+    for release in [">=1.20.0", "=1.20.0", "1.20.0", "[1.20.0,1.21)", "<=1.20.0"] {
+      assert!(contains(&range(release), "1.20"), "{} should contain 1.20", release);
+    }
+    assert!(!contains(&range(">1.20.0"), "1.20") && !contains(&range("<1.20"), "1.20.0"));
+    assert!(contains(&range("=custom"), "custom") && !contains(&range("=custom"), "other"));
+
+    assert_eq!("[1.18,1.19".parse::<VersionRange>(), Err(VersionRangeError::UnclosedInterval("[1.18,1.19".to_string())));
+    assert_eq!(">=1.x".parse::<VersionRange>(), Err(VersionRangeError::InvalidWildcard(">=1.x".to_string())));
+    assert_eq!(">=".parse::<VersionRange>(), Err(VersionRangeError::MissingVersion(">=".to_string())));
+  }
+}
@@ -6,6 +6,7 @@ pub mod java_manager;
 pub mod version_manager;
 #[cfg(feature = "bootstrap")]
 pub mod bootstrap;
+pub mod network;
 
 #[cfg(test)]
 mod tests;
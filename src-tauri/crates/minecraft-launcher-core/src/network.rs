@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use reqwest::{ header::{ HeaderMap, HeaderValue }, Certificate, Client, ClientBuilder, Proxy };
+
+pub const DEFAULT_USER_AGENT: &str = concat!("minecraft-launcher-core/", env!("CARGO_PKG_VERSION"));
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProxyKind {
+  #[default] Http,
+  /// DNS is resolved by the proxy, so it also works when the local resolver is blocked
+  Socks5,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxySettings {
+  pub kind: ProxyKind,
+  pub host: String,
+  pub port: u16,
+  pub username: Option<String>,
+  pub password: Option<String>,
+}
+
+impl ProxySettings {
+  pub fn http(host: &str, port: u16) -> Self {
+    Self { kind: ProxyKind::Http, host: host.to_string(), port, username: None, password: None }
+  }
+
+  pub fn socks5(host: &str, port: u16) -> Self {
+    Self { kind: ProxyKind::Socks5, ..Self::http(host, port) }
+  }
+
+  pub fn with_auth(mut self, username: &str, password: &str) -> Self {
+    self.username = Some(username.to_string());
+    self.password = Some(password.to_string());
+    self
+  }
+
+  /// The proxy URL, without credentials
+  pub fn url(&self) -> String {
+    let scheme = match self.kind {
+      ProxyKind::Http => "http",
+      ProxyKind::Socks5 => "socks5h",
+    };
+    format!("{}://{}:{}", scheme, self.host, self.port)
+  }
+
+  pub fn create_proxy(&self) -> reqwest::Result<Proxy> {
+    let mut proxy = Proxy::all(self.url())?;
+    // Applies to SOCKS5 too, as username/password authentication
+    if let (Some(username), Some(password)) = (&self.username, &self.password) {
+      proxy = proxy.basic_auth(username, password);
+    }
+    Ok(proxy)
+  }
+}
+
+/// Everything needed to build the HTTP clients used by the launcher: downloads, metadata
+/// and authentication. The game receives the proxy through its own arguments.
+#[derive(Debug, Clone)]
+pub struct NetworkSettings {
+  pub proxy: Option<ProxySettings>,
+  pub connect_timeout: Option<Duration>,
+  /// How long a response can go without receiving data. Large downloads aren't limited as a whole
+  pub read_timeout: Option<Duration>,
+  pub user_agent: Option<String>,
+  /// Extra trusted root certificates, in PEM (one or more) or DER format
+  pub root_certificates: Vec<Vec<u8>>,
+}
+
+impl Default for NetworkSettings {
+  fn default() -> Self {
+    Self {
+      proxy: None,
+      connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+      read_timeout: Some(DEFAULT_READ_TIMEOUT),
+      user_agent: None,
+      root_certificates: vec![],
+    }
+  }
+}
+
+impl NetworkSettings {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_proxy(mut self, proxy: Option<ProxySettings>) -> Self {
+    self.proxy = proxy;
+    self
+  }
+
+  pub fn with_connect_timeout(mut self, connect_timeout: Option<Duration>) -> Self {
+    self.connect_timeout = connect_timeout;
+    self
+  }
+
+  pub fn with_read_timeout(mut self, read_timeout: Option<Duration>) -> Self {
+    self.read_timeout = read_timeout;
+    self
+  }
+
+  pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+    self.user_agent = Some(user_agent.to_string());
+    self
+  }
+
+  pub fn with_root_certificate(mut self, certificate: &[u8]) -> Self {
+    self.root_certificates.push(certificate.to_vec());
+    self
+  }
+
+  pub fn get_user_agent(&self) -> &str {
+    self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT)
+  }
+
+  /// A client builder with every setting applied, for callers that need to customize it further
+  pub fn client_builder(&self) -> reqwest::Result<ClientBuilder> {
+    let mut headers = HeaderMap::new();
+    headers.append("Cache-Control", HeaderValue::from_static("no-store,max-age=0,no-cache"));
+    headers.append("Expires", HeaderValue::from_static("0"));
+    headers.append("Pragma", HeaderValue::from_static("no-cache"));
+
+    let mut builder = Client::builder().default_headers(headers).user_agent(self.get_user_agent());
+    if let Some(connect_timeout) = self.connect_timeout {
+      builder = builder.connect_timeout(connect_timeout);
+    }
+    if let Some(read_timeout) = self.read_timeout {
+      builder = builder.read_timeout(read_timeout);
+    }
+    if let Some(proxy) = &self.proxy {
+      builder = builder.proxy(proxy.create_proxy()?);
+    }
+    for certificate in &self.root_certificates {
+      for certificate in parse_certificates(certificate)? {
+        builder = builder.add_root_certificate(certificate);
+      }
+    }
+    Ok(builder)
+  }
+
+  pub fn create_client(&self) -> reqwest::Result<Client> {
+    self.client_builder()?.build()
+  }
+}
+
+fn parse_certificates(certificate: &[u8]) -> reqwest::Result<Vec<Certificate>> {
+  if certificate.starts_with(b"-----BEGIN") {
+    Certificate::from_pem_bundle(certificate)
+  } else {
+    Ok(vec![Certificate::from_der(certificate)?])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::{ io::{ AsyncReadExt, AsyncWriteExt }, net::TcpListener };
+
+  use super::*;
+
+  #[test]
+  fn test_proxy_url() {
+    assert_eq!(ProxySettings::http("127.0.0.1", 8080).url(), "http://127.0.0.1:8080");
+    assert_eq!(ProxySettings::socks5("proxy.local", 1080).with_auth("user", "pass").url(), "socks5h://proxy.local:1080");
+    assert!(NetworkSettings::new().with_proxy(Some(ProxySettings::socks5("proxy.local", 1080))).create_client().is_ok());
+  }
+
+  #[tokio::test]
+  async fn test_requests_go_through_proxy() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut request = vec![];
+      let mut buffer = [0u8; 1024];
+      while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = socket.read(&mut buffer).await.unwrap();
+        request.extend_from_slice(&buffer[..n]);
+      }
+      let _ = socket.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").await;
+      String::from_utf8_lossy(&request).to_lowercase()
+    });
+
+    let client = NetworkSettings::new()
+      .with_proxy(Some(ProxySettings::http("127.0.0.1", port).with_auth("user", "pass")))
+      .with_user_agent("test-agent")
+      .create_client()
+      .unwrap();
+    let response = client.get("http://example.invalid/file").send().await.unwrap();
+    assert_eq!(response.status(), 204);
+
+    let request = server.await.unwrap();
+    assert!(request.starts_with("get http://example.invalid/file http/1.1"));
+    assert!(request.contains("proxy-authorization: basic "));
+    assert!(request.contains("user-agent: test-agent"));
+  }
+}
@@ -83,7 +83,7 @@ async fn test_version_manager() -> Result<(), Box<dyn std::error::Error>> {
   let mc_version = MCVersion::new("1.20.1");
   let game_dir = temp_dir().join(".minecraft-test-rust");
 
-  let mut version_manager = VersionManager::load(&game_dir, &EnvironmentFeatures::default(), None).await?;
+  let version_manager = VersionManager::load(&game_dir, &EnvironmentFeatures::default(), None).await?;
   let resolved = version_manager.resolve_local_version(&mc_version, true, false).await?;
   info!("Resolved: {:?}", resolved);
   Ok(())
@@ -122,7 +122,7 @@ async fn test_game() -> Result<(), Box<dyn std::error::Error>> {
   let env_features = game_options.env_features();
 
   reporter.setup("Fetching version manifest", Some(2));
-  let mut version_manager = VersionManager::load(&game_options.game_dir, &env_features, Some(client.clone())).await?;
+  let version_manager = VersionManager::load(&game_options.game_dir, &env_features, Some(client.clone())).await?;
 
   info!("Queuing library & version downloads");
   reporter.status("Resolving local version");
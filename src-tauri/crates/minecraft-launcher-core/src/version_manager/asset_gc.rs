@@ -0,0 +1,250 @@
+use std::{ collections::HashSet, fs, io, path::{ Path, PathBuf }, time::{ Duration, SystemTime } };
+
+use chrono::DateTime;
+use log::{ info, warn };
+
+use crate::json::{ manifest::assets::AssetIndex, Sha1Sum };
+
+use super::{ downloader::get_asset_index_path, error::AssetGcError, object_store::ObjectStore };
+
+/// Virtual asset folders not used for this long are removed, they are rebuilt on the next launch
+pub const DEFAULT_VIRTUAL_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct AssetGcOptions {
+  /// Only report what would be removed
+  pub dry_run: bool,
+  /// How long since its `.lastused` stamp a virtual asset folder is kept
+  pub virtual_max_age: Duration,
+}
+
+impl Default for AssetGcOptions {
+  fn default() -> Self {
+    Self { dry_run: false, virtual_max_age: DEFAULT_VIRTUAL_MAX_AGE }
+  }
+}
+
+impl AssetGcOptions {
+  pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+    self.dry_run = dry_run;
+    self
+  }
+
+  pub fn with_virtual_max_age(mut self, virtual_max_age: Duration) -> Self {
+    self.virtual_max_age = virtual_max_age;
+    self
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AssetGcReport {
+  /// Objects in `assets/objects` no asset index in use references
+  pub objects: Vec<PathBuf>,
+  /// Folders in `assets/virtual` that are stale or belong to an index that isn't used
+  pub virtual_dirs: Vec<PathBuf>,
+  /// Bytes freed, or that would be freed in a dry run
+  pub bytes: u64,
+  pub dry_run: bool,
+}
+
+/// Removes the asset objects that none of the asset indexes in `live_indexes` reference, and the
+/// virtual asset folders that are unused or older than `options.virtual_max_age`.
+///
+/// If an index in use can't be read, nothing is removed. Indexes that were never downloaded are skipped.
+/// The references of `object_store`'s instance to the removed objects are released, so the
+/// store's own garbage collection can remove them once no other instance uses them.
+pub fn collect_asset_garbage(
+  game_dir: &Path,
+  live_indexes: &HashSet<String>,
+  object_store: Option<&ObjectStore>,
+  options: &AssetGcOptions
+) -> Result<AssetGcReport, AssetGcError> {
+  let assets_dir = game_dir.join("assets");
+  let mut live_objects = HashSet::new();
+  for id in live_indexes {
+    let bytes = match fs::read(get_asset_index_path(game_dir, id)) {
+      Ok(bytes) => bytes,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => {
+        warn!("Asset index {} isn't downloaded, skipping it", id);
+        continue;
+      }
+      Err(source) => {
+        return Err(AssetGcError::ReadIndex { id: id.clone(), source });
+      }
+    };
+    let index: AssetIndex = serde_json::from_slice(&bytes).map_err(|source| AssetGcError::ParseIndex { id: id.clone(), source })?;
+    for object in index.objects.values() {
+      live_objects.insert(object.hash.clone());
+      live_objects.extend(object.compressed_hash.clone());
+    }
+  }
+
+  let mut report = AssetGcReport { dry_run: options.dry_run, ..Default::default() };
+  let mut removed_objects = vec![];
+  for prefix in list_dir(&assets_dir.join("objects"))? {
+    for object in list_dir(&prefix)? {
+      // Anything that isn't named after its hash (like a download in progress) is left alone
+      let Some(sha1) = object
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| Sha1Sum::try_from(name.to_string()).ok()) else {
+        continue;
+      };
+      if live_objects.contains(&sha1) {
+        continue;
+      }
+      report.bytes += fs::metadata(&object)?.len();
+      if !options.dry_run {
+        fs::remove_file(&object)?;
+        removed_objects.push(sha1);
+      }
+      report.objects.push(object);
+    }
+  }
+  if let Some(object_store) = object_store {
+    // The objects are already gone, so this only leaves stale references behind
+    if let Err(err) = object_store.release_objects(&removed_objects) {
+      warn!("Couldn't release the removed assets from the object store: {}", err);
+    }
+  }
+
+  let now = SystemTime::now();
+  for dir in list_dir(&assets_dir.join("virtual"))? {
+    let is_live = dir
+      .file_name()
+      .and_then(|name| name.to_str())
+      .is_some_and(|id| live_indexes.contains(id));
+    let is_stale = get_last_used(&dir)
+      .and_then(|last_used| now.duration_since(last_used).ok())
+      .is_some_and(|age| age > options.virtual_max_age);
+    if is_live && !is_stale {
+      continue;
+    }
+    report.bytes += dir_size(&dir)?;
+    if !options.dry_run {
+      fs::remove_dir_all(&dir)?;
+    }
+    report.virtual_dirs.push(dir);
+  }
+
+  info!(
+    "{} {} asset objects and {} virtual folders ({} bytes)",
+    if options.dry_run { "Would remove" } else { "Removed" },
+    report.objects.len(),
+    report.virtual_dirs.len(),
+    report.bytes
+  );
+  Ok(report)
+}
+
+/// The time in the folder's `.lastused` stamp, or when the stamp was last modified if it can't be parsed
+fn get_last_used(dir: &Path) -> Option<SystemTime> {
+  let stamp = dir.join(".lastused");
+  let parsed = fs
+    ::read_to_string(&stamp)
+    .ok()
+    .and_then(|stamp| DateTime::parse_from_rfc3339(stamp.trim()).ok())
+    .map(SystemTime::from);
+  parsed.or_else(|| fs::metadata(&stamp).and_then(|metadata| metadata.modified()).ok())
+}
+
+fn dir_size(dir: &Path) -> io::Result<u64> {
+  let mut size = 0;
+  for entry in list_dir(dir)? {
+    let metadata = fs::symlink_metadata(&entry)?;
+    size += if metadata.is_dir() { dir_size(&entry)? } else { metadata.len() };
+  }
+  Ok(size)
+}
+
+/// The entries of `dir`, or nothing if it doesn't exist
+fn list_dir(dir: &Path) -> io::Result<Vec<PathBuf>> {
+  match fs::read_dir(dir) {
+    Ok(entries) => entries.map(|entry| entry.map(|entry| entry.path())).collect(),
+    Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+    Err(err) => Err(err),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env::temp_dir;
+
+  use chrono::Utc;
+
+  use crate::{ json::EnvironmentFeatures, version_manager::VersionManager };
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_collect_asset_garbage() {
+    let game_dir = temp_dir().join("minecraft-launcher-core-asset-gc");
+    let _ = fs::remove_dir_all(&game_dir);
+    let sha1 = |bytes: &[u8]| Sha1Sum::from_reader(&mut &bytes[..]).unwrap();
+    let object = |contents: &[u8]| {
+      let hash = sha1(contents).to_string();
+      let path = game_dir.join("assets").join("objects").join(&hash[..2]).join(&hash);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(&path, contents).unwrap();
+      path
+    };
+
+    let (live, compressed, dead) = (object(b"live"), object(b"compressed"), object(b"dead"));
+    let index = format!(
+      r#"{{"objects":{{"a.ogg":{{"hash":"{}","size":4,"compressedHash":"{}"}}}}}}"#,
+      sha1(b"live"),
+      sha1(b"compressed")
+    );
+    let index_path = get_asset_index_path(&game_dir, "17");
+    fs::create_dir_all(index_path.parent().unwrap()).unwrap();
+    fs::write(&index_path, index).unwrap();
+
+    let virtual_dir = |id: &str, last_used: &str| {
+      let dir = game_dir.join("assets").join("virtual").join(id);
+      fs::create_dir_all(&dir).unwrap();
+      fs::write(dir.join(".lastused"), last_used).unwrap();
+      dir
+    };
+    let fresh = virtual_dir("17", &Utc::now().to_rfc3339());
+    let unused = virtual_dir("legacy", &Utc::now().to_rfc3339());
+    let stale = virtual_dir("pre-1.6", "2013-06-25T00:00:00+00:00");
+
+    let live_indexes = HashSet::from(["17".to_string(), "pre-1.6".to_string(), "missing".to_string()]);
+    let store = ObjectStore::new(&game_dir.join("store")).for_instance(&game_dir);
+    store.insert(&live, &sha1(b"live")).await.unwrap();
+    store.insert(&dead, &sha1(b"dead")).await.unwrap();
+    let report = collect_asset_garbage(&game_dir, &live_indexes, Some(&store), &AssetGcOptions::default().with_dry_run(true)).unwrap();
+    assert_eq!(report.objects, vec![dead.clone()]);
+    let mut virtual_dirs = report.virtual_dirs.clone();
+    virtual_dirs.sort();
+    assert_eq!(virtual_dirs, vec![unused.clone(), stale.clone()]);
+    assert!(dead.is_file() && stale.is_dir());
+
+    assert_eq!(store.ref_count(&sha1(b"dead")).await.unwrap(), 1);
+
+    let removed = collect_asset_garbage(&game_dir, &live_indexes, Some(&store), &AssetGcOptions::default()).unwrap();
+    assert_eq!(removed.bytes, report.bytes);
+    assert!(live.is_file() && compressed.is_file() && fresh.is_dir());
+    assert!(!dead.exists() && !unused.exists() && !stale.exists());
+    assert_eq!(store.ref_count(&sha1(b"live")).await.unwrap(), 1);
+    assert_eq!(store.ref_count(&sha1(b"dead")).await.unwrap(), 0);
+  }
+
+  #[test]
+  fn test_unreadable_version_keeps_assets() {
+    let game_dir = temp_dir().join("minecraft-launcher-core-asset-gc-unreadable");
+    let _ = fs::remove_dir_all(&game_dir);
+    let json = game_dir.join("versions").join("1.20").join("1.20.json");
+    fs::create_dir_all(json.parent().unwrap()).unwrap();
+    fs::write(&json, r#"{ "id": "1.20", "time": "2023-06-07T09:35:25+00:00", "releaseTime": "2023-06-02T08:36:17+00:00", "type": "release" }"#).unwrap();
+    let object = game_dir.join("assets").join("objects").join("ab").join("ab".repeat(20));
+    fs::create_dir_all(object.parent().unwrap()).unwrap();
+    fs::write(&object, b"sound").unwrap();
+
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    version_manager.refresh_local_versions().unwrap();
+    fs::write(&json, "{").unwrap();
+    let report = version_manager.collect_asset_garbage(&AssetGcOptions::default()).unwrap();
+    assert!(report.objects.is_empty() && object.is_file());
+  }
+}
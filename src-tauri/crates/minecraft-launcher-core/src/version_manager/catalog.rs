@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+
+use chrono::{ DateTime, FixedOffset };
+use serde::{ Deserialize, Serialize };
+
+use crate::json::{ manifest::{ java::JavaVersionInfo, VersionManifest }, Date, MCVersion, ReleaseType, VersionRange, VersionTimeline };
+
+/// A version from the remote version list, the installed versions, or both
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogEntry {
+  pub id: MCVersion,
+  pub release_type: ReleaseType,
+  pub release_time: Date,
+  pub installed: bool,
+  /// Whether it's in the remote version list, custom and modded versions aren't
+  pub remote: bool,
+}
+
+/// Filters for `VersionManager::query_versions`. Every filter that is set must match
+#[derive(Debug, Clone, Default)]
+pub struct VersionQuery {
+  /// Only versions of these types, or any type if empty
+  pub release_types: HashSet<ReleaseType>,
+  /// Only versions released at or after this time
+  pub released_after: Option<DateTime<FixedOffset>>,
+  /// Only versions released before this time
+  pub released_before: Option<DateTime<FixedOffset>>,
+  pub installed: Option<bool>,
+  pub range: Option<VersionRange>,
+}
+
+impl VersionQuery {
+  /// Adds `release_type` to the accepted types
+  pub fn with_release_type(mut self, release_type: ReleaseType) -> Self {
+    self.release_types.insert(release_type);
+    self
+  }
+
+  pub fn with_released_after(mut self, released_after: DateTime<FixedOffset>) -> Self {
+    self.released_after = Some(released_after);
+    self
+  }
+
+  pub fn with_released_before(mut self, released_before: DateTime<FixedOffset>) -> Self {
+    self.released_before = Some(released_before);
+    self
+  }
+
+  /// Only installed versions, or only versions that aren't installed
+  pub fn with_installed(mut self, installed: bool) -> Self {
+    self.installed = Some(installed);
+    self
+  }
+
+  pub fn with_range(mut self, range: VersionRange) -> Self {
+    self.range = Some(range);
+    self
+  }
+
+  /// Versions are placed in the range with `timeline`, see `VersionManager::get_timeline`
+  pub fn matches(&self, entry: &CatalogEntry, timeline: &VersionTimeline) -> bool {
+    let release_time = entry.release_time.inner();
+    (self.release_types.is_empty() || self.release_types.contains(&entry.release_type)) &&
+      self.released_after.is_none_or(|after| *release_time >= after) &&
+      self.released_before.is_none_or(|before| *release_time < before) &&
+      self.installed.is_none_or(|installed| entry.installed == installed) &&
+      self.range.as_ref().is_none_or(|range| range.contains(&entry.id, timeline))
+  }
+}
+
+/// What a version needs to be launched, taking its parents into account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionRequirements {
+  pub id: MCVersion,
+  /// Versions that don't specify one run on Java 8
+  pub java_version: JavaVersionInfo,
+  /// The id of the asset index, if the version has assets
+  pub asset_index: Option<String>,
+}
+
+impl From<&VersionManifest> for VersionRequirements {
+  fn from(manifest: &VersionManifest) -> Self {
+    Self {
+      id: manifest.id.clone(),
+      java_version: manifest.java_version.clone().unwrap_or_default(),
+      asset_index: manifest.asset_index
+        .as_ref()
+        .map(|asset_index| asset_index.id.clone())
+        .or_else(|| manifest.assets.clone()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{ env::temp_dir, fs };
+
+  use serde_json::json;
+
+  use crate::{ json::EnvironmentFeatures, version_manager::{ custom::CUSTOM_VERSION_MARKER, remote::RawVersionList, VersionManager } };
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_query_versions() {
+    let game_dir = temp_dir().join("minecraft-launcher-core-catalog");
+    let _ = fs::remove_dir_all(&game_dir);
+    let remote = |id: &str, release_type: &str, time: &str| {
+      json!({
+        "id": id,
+        "type": release_type,
+        "url": format!("https://piston-meta.mojang.com/v1/packages/{}.json", id),
+        "time": time,
+        "releaseTime": time,
+        "sha1": "0000000000000000000000000000000000000000",
+        "complianceLevel": 1,
+      })
+    };
+    let list: RawVersionList = serde_json
+      ::from_value(
+        json!({
+        "latest": { "release": "1.20.4", "snapshot": "24w03a" },
+        "versions": [
+          remote("24w03a", "snapshot", "2024-01-17T13:22:44+00:00"),
+          remote("1.20.4", "release", "2023-12-07T12:56:20+00:00"),
+          remote("1.20.3", "release", "2023-12-05T12:10:22+00:00"),
+          remote("b1.7.3", "old_beta", "2011-07-07T22:00:00+00:00"),
+        ],
+      })
+      )
+      .unwrap();
+
+    let dir = game_dir.join("versions").join("fabric-1.20.4");
+    fs::create_dir_all(&dir).unwrap();
+    let custom =
+      json!({
+      "id": "fabric-1.20.4",
+      "inheritsFrom": "1.20.4",
+      "time": "2023-12-08T00:00:00+00:00",
+      "releaseTime": "2023-12-08T00:00:00+00:00",
+      "type": "release",
+      "javaVersion": { "component": "java-runtime-gamma", "majorVersion": 17 },
+      "assets": "12",
+    });
+    fs::write(dir.join("fabric-1.20.4.json"), custom.to_string()).unwrap();
+
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    // As `VersionManager::refresh` does, so the range below can place the old beta
+    version_manager.timeline.write().unwrap().register(&list.versions);
+    *version_manager.remote_cache.write().unwrap() = Some(list);
+    version_manager.refresh_local_versions().unwrap();
+
+    let ids = |query: &VersionQuery| {
+      version_manager
+        .query_versions(query)
+        .into_iter()
+        .map(|entry| entry.id.to_string())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&VersionQuery::default()), ["24w03a", "fabric-1.20.4", "1.20.4", "1.20.3", "b1.7.3"]);
+    assert_eq!(ids(&VersionQuery::default().with_release_type(ReleaseType::Release).with_installed(false)), ["1.20.4", "1.20.3"]);
+    let december = DateTime::parse_from_rfc3339("2023-12-06T00:00:00+00:00").unwrap();
+    let january = DateTime::parse_from_rfc3339("2024-01-01T00:00:00+00:00").unwrap();
+    assert_eq!(ids(&VersionQuery::default().with_released_after(december).with_released_before(january)), ["fabric-1.20.4", "1.20.4"]);
+    assert_eq!(ids(&VersionQuery::default().with_range("<1.20.4".parse().unwrap())), ["1.20.3", "b1.7.3"]);
+
+    assert_eq!(version_manager.get_latest_version(&ReleaseType::Snapshot), Some(MCVersion::new("24w03a")));
+    *version_manager.remote_cache.write().unwrap() = None;
+    // Without the remote list, only installed vanilla versions can be the latest one
+    assert_eq!(version_manager.get_latest_version(&ReleaseType::Release), None);
+    assert_eq!(version_manager.get_latest_version(&ReleaseType::Snapshot), None);
+    for (id, time) in [("1.20.2", "2023-09-21T00:00:00+00:00"), ("my-pack", "2023-12-09T00:00:00+00:00")] {
+      let dir = game_dir.join("versions").join(id);
+      fs::create_dir_all(&dir).unwrap();
+      let manifest = json!({ "id": id, "time": time, "releaseTime": time, "type": "release" });
+      fs::write(dir.join(format!("{}.json", id)), manifest.to_string()).unwrap();
+    }
+    fs::write(game_dir.join("versions").join("my-pack").join(CUSTOM_VERSION_MARKER), "").unwrap();
+    version_manager.refresh_local_versions().unwrap();
+    assert_eq!(version_manager.get_latest_version(&ReleaseType::Release), Some(MCVersion::new("1.20.2")));
+
+    // Without an `assetIndex`, the `assets` id is used
+    let manifest: VersionManifest = serde_json::from_value(custom).unwrap();
+    let requirements = VersionRequirements::from(&manifest);
+    assert_eq!(requirements.java_version.major_version, 17);
+    assert_eq!(requirements.asset_index.as_deref(), Some("12"));
+  }
+}
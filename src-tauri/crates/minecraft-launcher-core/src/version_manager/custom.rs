@@ -0,0 +1,211 @@
+use std::{ fs::{ self, File }, path::{ Path, PathBuf } };
+
+use reqwest::Url;
+
+use crate::json::{ MCVersion, Sha1Sum };
+
+use super::{ downloader::downloadables::AtomicFile, error::CustomVersionError, object_store::place };
+
+/// The file in `versions/<id>/` that marks a version installed with `VersionManager::install_custom_version`
+pub const CUSTOM_VERSION_MARKER: &str = ".custom";
+
+/// The files of a version that isn't in the version list, like an internal build of the game or a mod
+#[derive(Debug, Clone)]
+pub struct CustomVersion {
+  pub json: PathBuf,
+  /// Optional if the version inherits from another one, which provides the jar
+  pub jar: Option<PathBuf>,
+}
+
+/// How the client jar of a custom version is placed in `versions/<id>/`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CustomFileMode {
+  #[default]
+  Copy,
+  /// Hardlink, falling back to a reflink or a copy. A linked jar follows changes to the source,
+  /// so the version has to be installed again after rebuilding it
+  Link,
+}
+
+impl CustomVersion {
+  pub fn new(json: &Path, jar: Option<&Path>) -> Self {
+    Self { json: json.to_path_buf(), jar: jar.map(Path::to_path_buf) }
+  }
+
+  /// Takes the files from a directory laid out like `versions/<id>/`: `<id>.json` and `<id>.jar`.
+  /// Otherwise the directory must contain a single JSON, and the jar is used if there is only one
+  pub fn from_dir(dir: &Path) -> Result<Self, CustomVersionError> {
+    let files: Vec<PathBuf> = fs
+      ::read_dir(dir)?
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.path())
+      .filter(|path| path.is_file())
+      .collect();
+    let with_extension = |extension: &str| -> Vec<&PathBuf> {
+      files
+        .iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+        .collect()
+    };
+
+    let dir_name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let jsons = with_extension("json");
+    let json = match jsons.iter().find(|path| path.file_stem().is_some_and(|stem| *stem == *dir_name)) {
+      Some(json) => *json,
+      None if jsons.len() == 1 => jsons[0],
+      None if jsons.is_empty() => {
+        return Err(CustomVersionError::ManifestNotFound(dir.to_path_buf()));
+      }
+      None => {
+        return Err(CustomVersionError::AmbiguousManifest(dir.to_path_buf()));
+      }
+    };
+    let jar = json.with_extension("jar");
+    let jars = with_extension("jar");
+    let jar = if jar.is_file() { Some(jar) } else if jars.len() == 1 { Some(jars[0].clone()) } else { None };
+    Ok(Self { json: json.clone(), jar })
+  }
+}
+
+/// Places the jar in `target_dir` and adds its `downloads.client` entry to `value`, then writes the JSON
+pub(super) async fn write_custom_version(
+  target_dir: &Path,
+  version_id: &MCVersion,
+  custom_version: &CustomVersion,
+  mode: CustomFileMode,
+  value: &mut serde_json::Value
+) -> Result<(), CustomVersionError> {
+  fs::create_dir_all(target_dir)?;
+  if let Some(jar) = &custom_version.jar {
+    let target_jar = target_dir.join(format!("{}.jar", version_id));
+    match mode {
+      CustomFileMode::Copy => {
+        fs::copy(jar, &target_jar)?;
+      }
+      CustomFileMode::Link => {
+        place(jar, &target_jar)?;
+      }
+    }
+    let sha1 = Sha1Sum::from_reader(&mut File::open(&target_jar)?)?;
+    let url = Url::from_file_path(&target_jar).map(String::from).unwrap_or_default();
+    value["downloads"]["client"] = serde_json::json!({ "sha1": sha1, "size": fs::metadata(&target_jar)?.len(), "url": url });
+  }
+
+  // Marked before the JSON makes it an installed version, so it's never taken for a remote version
+  File::create(target_dir.join(CUSTOM_VERSION_MARKER))?;
+  let mut file = AtomicFile::create(&target_dir.join(format!("{}.json", version_id))).await?;
+  file.write_all(&serde_json::to_vec_pretty(value)?).await?;
+  file.commit().await?;
+  Ok(())
+}
+
+/// Ids are used as directory and file names
+pub fn validate_id(version_id: &MCVersion) -> Result<(), CustomVersionError> {
+  let id = version_id.to_string();
+  if id.trim().is_empty() || id == "." || id == ".." || id.contains(['/', '\\', ':']) {
+    return Err(CustomVersionError::InvalidId(id));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env::temp_dir;
+
+  use serde_json::json;
+
+  use crate::{
+    json::{ manifest::download::DownloadType, EnvironmentFeatures, Sha1Sum },
+    version_manager::{ remote::RawVersionList, VersionManager },
+  };
+
+  use super::*;
+
+  fn write_json(path: &Path, id: &str, inherits_from: Option<&str>) {
+    let mut manifest = json!({
+      "id": id,
+      "time": "2024-05-01T00:00:00+00:00",
+      "releaseTime": "2024-05-01T00:00:00+00:00",
+      "type": "snapshot",
+      "mainClass": "net.minecraft.client.main.Main",
+      "customField": true,
+    });
+    if let Some(inherits_from) = inherits_from {
+      manifest["inheritsFrom"] = inherits_from.into();
+    }
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, manifest.to_string()).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_install_custom_version() {
+    let root = temp_dir().join("minecraft-launcher-core-custom");
+    let _ = fs::remove_dir_all(&root);
+    let game_dir = root.join("game");
+    let build_dir = root.join("internal-1.21");
+    write_json(&build_dir.join("internal-1.21.json"), "internal-1.21", None);
+    fs::write(build_dir.join("internal-1.21.jar"), b"client").unwrap();
+    let mod_json = root.join("mod.json");
+    write_json(&mod_json, "internal-1.21-mod", Some("internal-1.21"));
+    let mod_jar = root.join("patched.jar");
+    fs::write(&mod_jar, b"patched client").unwrap();
+
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    version_manager.refresh_local_versions().unwrap();
+    // The parent must be registered first
+    let err = version_manager.install_custom_version(&CustomVersion::new(&mod_json, None), CustomFileMode::Copy).await.unwrap_err();
+    assert!(matches!(err, CustomVersionError::ParentNotFound { .. }));
+    let err = version_manager.install_custom_version(&CustomVersion::new(&build_dir.join("internal-1.21.json"), None), CustomFileMode::Copy).await.unwrap_err();
+    assert!(matches!(err, CustomVersionError::MissingJar(_)));
+
+    let parent = version_manager.install_custom_version(&CustomVersion::from_dir(&build_dir).unwrap(), CustomFileMode::Link).await.unwrap();
+    let version_dir = game_dir.join("versions").join("internal-1.21");
+    assert_eq!(fs::read(version_dir.join("internal-1.21.jar")).unwrap(), b"client");
+    let client = parent.get_download_url(DownloadType::Client).unwrap();
+    assert_eq!(client.sha1, Sha1Sum::from_reader(&mut &b"client"[..]).unwrap());
+    let installed: serde_json::Value = serde_json::from_slice(&fs::read(version_dir.join("internal-1.21.json")).unwrap()).unwrap();
+    assert_eq!(installed["customField"], json!(true));
+
+    let child = version_manager.install_custom_version(&CustomVersion::new(&mod_json, Some(&mod_jar)), CustomFileMode::Copy).await.unwrap();
+    assert!(version_manager.is_installed(&child.id) && version_manager.is_custom_version(&child.id));
+
+    // A remote version with the same id and a newer JSON doesn't replace it
+    let list: RawVersionList = serde_json
+      ::from_value(
+        json!({
+        "latest": { "release": "internal-1.21", "snapshot": "internal-1.21" },
+        "versions": [{
+          "id": "internal-1.21",
+          "type": "release",
+          "url": "https://piston-meta.mojang.com/v1/packages/internal-1.21.json",
+          "time": "2025-01-01T00:00:00+00:00",
+          "releaseTime": "2025-01-01T00:00:00+00:00",
+          "sha1": "0000000000000000000000000000000000000000",
+          "complianceLevel": 1,
+        }],
+      })
+      )
+      .unwrap();
+    *version_manager.remote_cache.write().unwrap() = Some(list);
+    assert!(version_manager.is_up_to_date(&parent).await);
+
+    // The child's own jar replaces the inherited one
+    let resolved = version_manager.resolve_local_version(&child.id, true, true).await.unwrap();
+    let client = resolved.get_download_url(DownloadType::Client).unwrap();
+    assert_eq!(client.sha1, Sha1Sum::from_reader(&mut &b"patched client"[..]).unwrap());
+
+    write_json(&game_dir.join("versions").join("1.20.1").join("1.20.1.json"), "1.20.1", None);
+    version_manager.refresh_local_versions().unwrap();
+    write_json(&mod_json, "1.20.1", Some("internal-1.21"));
+    let err = version_manager.install_custom_version(&CustomVersion::new(&mod_json, None), CustomFileMode::Copy).await.unwrap_err();
+    assert!(matches!(err, CustomVersionError::AlreadyInstalled(_)));
+
+    // Nothing is left behind if the jar can't be placed
+    write_json(&mod_json, "internal-1.21-broken", Some("internal-1.21"));
+    let broken = CustomVersion::new(&mod_json, Some(&root.join("missing.jar")));
+    let err = version_manager.install_custom_version(&broken, CustomFileMode::Copy).await.unwrap_err();
+    assert!(matches!(err, CustomVersionError::IoError(_)));
+    assert!(!game_dir.join("versions").join("internal-1.21-broken").exists());
+    assert!(!version_manager.is_installed(&MCVersion::new("internal-1.21-broken")));
+  }
+}
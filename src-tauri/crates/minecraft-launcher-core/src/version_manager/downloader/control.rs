@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use super::downloadables::DownloadError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadState {
+  Running,
+  Paused,
+  Cancelled,
+}
+
+/// Cancels, pauses and resumes downloads. Clones share the same state.
+///
+/// Downloads only react at checkpoints (before each file and between chunks),
+/// so a paused download finishes writing its current chunk before waiting.
+#[derive(Debug, Clone)]
+pub struct DownloadControl {
+  state: Arc<watch::Sender<DownloadState>>,
+}
+
+impl Default for DownloadControl {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl DownloadControl {
+  pub fn new() -> Self {
+    Self { state: Arc::new(watch::Sender::new(DownloadState::Running)) }
+  }
+
+  pub fn get_state(&self) -> DownloadState {
+    *self.state.borrow()
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.get_state() == DownloadState::Cancelled
+  }
+
+  /// Cancels the downloads. A cancelled control can't be resumed
+  pub fn cancel(&self) {
+    self.state.send_replace(DownloadState::Cancelled);
+  }
+
+  pub fn pause(&self) {
+    self.transition(DownloadState::Running, DownloadState::Paused);
+  }
+
+  pub fn resume(&self) {
+    self.transition(DownloadState::Paused, DownloadState::Running);
+  }
+
+  fn transition(&self, from: DownloadState, to: DownloadState) {
+    self.state.send_if_modified(|state| {
+      if *state == from {
+        *state = to;
+        return true;
+      }
+      false
+    });
+  }
+
+  /// Waits while the downloads are paused, and fails if they were cancelled
+  pub async fn checkpoint(&self) -> Result<(), DownloadError> {
+    let mut receiver = self.state.subscribe();
+    loop {
+      match *receiver.borrow_and_update() {
+        DownloadState::Running => {
+          return Ok(());
+        }
+        DownloadState::Cancelled => {
+          return Err(DownloadError::Cancelled);
+        }
+        DownloadState::Paused => {}
+      }
+      // The sender lives as long as `self`, so this can't fail
+      let _ = receiver.changed().await;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_pause_resume_cancel() {
+    let control = DownloadControl::new();
+    assert!(control.checkpoint().await.is_ok());
+
+    control.pause();
+    let waiting = tokio::spawn({
+      let control = control.clone();
+      async move { control.checkpoint().await }
+    });
+    for _ in 0..10 {
+      tokio::task::yield_now().await;
+    }
+    assert!(!waiting.is_finished());
+
+    control.resume();
+    assert!(waiting.await.unwrap().is_ok());
+
+    control.pause();
+    control.cancel();
+    control.resume();
+    assert_eq!(control.get_state(), DownloadState::Cancelled);
+    assert!(matches!(control.checkpoint().await, Err(DownloadError::Cancelled)));
+  }
+}
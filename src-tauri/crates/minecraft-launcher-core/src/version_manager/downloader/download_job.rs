@@ -1,24 +1,38 @@
-use std::{ mem::take, sync::Arc, time::Duration };
+use std::{ fmt::Debug, mem::take, path::PathBuf, sync::Arc };
 
 use chrono::Utc;
 use futures::{ stream::iter, StreamExt };
 use log::{ error, info, warn };
-use reqwest::{ header::{ HeaderMap, HeaderValue }, Client, Proxy };
+use reqwest::{ Client, Proxy };
+use tokio::time::sleep;
 
-use super::{ downloadables::{ DownloadError, Downloadable }, error::Error, progress::{ CallbackReporter, EmptyReporter, ProgressReporter } };
+use crate::{ network::NetworkSettings, version_manager::{ endpoints::Endpoints, object_store::ObjectStore } };
+
+use super::{
+  control::DownloadControl,
+  downloadables::{ DownloadError, Downloadable },
+  error::Error,
+  limiter::NetworkLimiter,
+  progress::{ CallbackReporter, EmptyReporter, ProgressReporter },
+  retry::RetryPolicy,
+};
 
 type DownloadableSync = Arc<dyn Downloadable + Send + Sync>;
 
 pub struct DownloadJob {
   name: String,
   client: Client,
-  all_files: Vec<Box<dyn Downloadable + Send + Sync>>,
+  all_files: Vec<DownloadableSync>,
   ignore_failures: bool,
   parallel_downloads: usize,
-  retries: usize,
+  retry_policy: RetryPolicy,
 
   // Tracks progress of the entire download job
   progress_reporter: ProgressReporter,
+  control: DownloadControl,
+  limiter: NetworkLimiter,
+  endpoints: Endpoints,
+  object_store: Option<ObjectStore>,
 }
 
 impl Default for DownloadJob {
@@ -29,10 +43,14 @@ impl Default for DownloadJob {
       client: Self::create_http_client(None).unwrap_or_default(),
       ignore_failures: false,
       parallel_downloads: 16,
-      retries: 5,
+      retry_policy: RetryPolicy::default(),
 
       all_files: vec![],
       progress_reporter: Arc::new(EmptyReporter),
+      control: DownloadControl::new(),
+      limiter: NetworkLimiter::global(),
+      endpoints: Endpoints::default(),
+      object_store: None,
     }
   }
 }
@@ -61,7 +79,12 @@ impl DownloadJob {
   }
 
   pub fn with_retries(mut self, max_download_attempts: usize) -> Self {
-    self.retries = max_download_attempts;
+    self.retry_policy.max_attempts = max_download_attempts;
+    self
+  }
+
+  pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+    self.retry_policy = retry_policy;
     self
   }
 
@@ -70,17 +93,46 @@ impl DownloadJob {
     self
   }
 
-  pub fn add_downloadables(mut self, mut downloadables: Vec<Box<dyn Downloadable + Send + Sync>>) -> Self {
-    self.all_files.append(&mut downloadables);
+  /// Lets the caller cancel, pause or resume the job while it runs
+  pub fn with_control(mut self, control: &DownloadControl) -> Self {
+    self.control = control.clone();
+    self
+  }
+
+  /// Bandwidth and per-host connection limits, shared with any other job using the same limiter
+  pub fn with_limiter(mut self, limiter: &NetworkLimiter) -> Self {
+    self.limiter = limiter.clone();
+    self
+  }
+
+  /// Mirrors tried before the official servers for every file of the job
+  pub fn with_endpoints(mut self, endpoints: &Endpoints) -> Self {
+    self.endpoints = endpoints.clone();
+    self
+  }
+
+  /// Files with a known SHA-1 are taken from (and added to) the store. References are recorded
+  /// for the store's instance, see `ObjectStore::for_instance`
+  pub fn with_object_store(mut self, object_store: &ObjectStore) -> Self {
+    self.object_store = Some(object_store.clone());
+    self
+  }
+
+  pub fn add_downloadables(mut self, downloadables: Vec<Box<dyn Downloadable + Send + Sync>>) -> Self {
+    self.all_files.extend(downloadables.into_iter().map(Arc::from));
     self
   }
 
   fn prepare_downloadables(&mut self) -> Vec<DownloadableSync> {
-    let all_files: Vec<DownloadableSync> = take(&mut self.all_files).into_iter().map(Arc::from).collect();
+    let all_files = take(&mut self.all_files);
     let monitor = JobMonitor::new(self.progress_reporter.clone(), &all_files);
 
     all_files.iter().for_each(|downloadable| {
       downloadable.get_monitor().set_reporter(monitor.download_reporter());
+      downloadable.get_monitor().set_control(self.control.clone());
+      downloadable.get_monitor().set_limiter(self.limiter.clone());
+      downloadable.get_monitor().set_endpoints(self.endpoints.clone());
+      downloadable.get_monitor().set_object_store(self.object_store.clone());
     });
 
     all_files
@@ -88,39 +140,63 @@ impl DownloadJob {
 }
 
 impl DownloadJob {
-  pub async fn start(mut self) -> Result<(), Error> {
+  /// Downloads every file added to the job. The job can be started again
+  /// afterwards, see `DownloadJob::retry_failed`.
+  ///
+  /// Failed files don't stop the job. If `ignore_failures` is set, they're
+  /// listed in the returned report instead of failing the job.
+  pub async fn start(&mut self) -> Result<JobReport, Error> {
     // self.progress_reporter.clear();
 
     let start_time = Utc::now();
     let downloadables = self.prepare_downloadables();
+    let total = downloadables.len();
 
     self.progress_reporter.setup(&format!("Starting \"{}\"", self.name), None);
     let results = self.run(downloadables).await;
 
     let total_time = Utc::now().signed_duration_since(start_time).num_seconds();
-    let failures = results
-      .iter()
-      .filter(|r| r.is_err())
-      .collect::<Vec<_>>();
+    let failures: Vec<FailedDownload> = results.into_iter().filter_map(Result::err).collect();
+    let report = JobReport { name: self.name.clone(), downloaded: total - failures.len(), failures, total_time };
 
     self.progress_reporter.done();
 
-    if self.ignore_failures || failures.is_empty() {
+    if self.control.is_cancelled() {
+      info!("Job '{}' was cancelled (took {}s)", self.name, total_time);
+      return Err(Error::Cancelled { name: self.name.clone() });
+    }
+
+    if report.failures.is_empty() {
       info!("Job '{}' finished successfully (took {}s)", self.name, total_time);
-      return Ok(());
+      return Ok(report);
+    }
+
+    for failure in &report.failures {
+      error!("Job '{}' couldn't download {} after {} attempt(s): {}", self.name, failure.url, failure.attempts, failure.error);
     }
-    Err(Error::JobFailed { name: self.name, failures: failures.len(), total_time })
+    if self.ignore_failures {
+      warn!("Job '{}' finished with {} failure(s) (took {}s)", self.name, report.failures.len(), total_time);
+      return Ok(report);
+    }
+    Err(Error::JobFailed(report))
+  }
+
+  /// Runs the job again, only with the files that failed in `report`
+  pub async fn retry_failed(&mut self, report: JobReport) -> Result<JobReport, Error> {
+    self.all_files.extend(report.failures.into_iter().map(|failure| failure.downloadable));
+    self.start().await
   }
 
-  async fn run(&self, downloads: Vec<DownloadableSync>) -> Vec<Result<DownloadableSync, DownloadError>> {
+  async fn run(&self, downloads: Vec<DownloadableSync>) -> Vec<Result<DownloadableSync, FailedDownload>> {
     let job_name = self.name.clone();
     let client = self.client.clone();
-    let retries = self.retries;
+    let retry_policy = self.retry_policy.clone();
     let parallel_downloads = self.parallel_downloads;
+    let control = self.control.clone();
 
     let iter = iter(downloads)
-      .map(move |downloadable| (downloadable, job_name.clone(), client.clone(), retries))
-      .map(|(downloadable, job_name, client, retries)| download(job_name, client, retries, downloadable))
+      .map(move |downloadable| (downloadable, job_name.clone(), client.clone(), retry_policy.clone(), control.clone()))
+      .map(|(downloadable, job_name, client, retry_policy, control)| download(job_name, client, retry_policy, control, downloadable))
       .buffer_unordered(parallel_downloads);
 
     // FIXME: currently, this was the only way i've found to make the future returned by the function implement `Send`
@@ -128,15 +204,47 @@ impl DownloadJob {
   }
 }
 
+/// Outcome of a download job
+#[derive(Debug)]
+pub struct JobReport {
+  pub name: String,
+  /// Amount of files that were downloaded, or were already present
+  pub downloaded: usize,
+  pub failures: Vec<FailedDownload>,
+  pub total_time: i64,
+}
+
+impl JobReport {
+  pub fn is_success(&self) -> bool {
+    self.failures.is_empty()
+  }
+}
+
+/// A file a job gave up on
+pub struct FailedDownload {
+  pub url: String,
+  pub target: PathBuf,
+  pub attempts: usize,
+  pub error: DownloadError,
+  downloadable: DownloadableSync,
+}
+
+impl Debug for FailedDownload {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("FailedDownload")
+      .field("url", &self.url)
+      .field("target", &self.target)
+      .field("attempts", &self.attempts)
+      .field("error", &self.error)
+      .finish()
+  }
+}
+
 impl DownloadJob {
+  /// A client with the default `NetworkSettings` and `proxy`. Use `NetworkSettings::create_client`
+  /// for anything else
   pub fn create_http_client(proxy: Option<Proxy>) -> Result<Client, reqwest::Error> {
-    let mut client = Client::builder();
-    let mut headers = HeaderMap::new();
-    headers.append("Cache-Control", HeaderValue::from_static("no-store,max-age=0,no-cache"));
-    headers.append("Expires", HeaderValue::from_static("0"));
-    headers.append("Pragma", HeaderValue::from_static("no-cache"));
-
-    client = client.default_headers(headers).connect_timeout(Duration::from_secs(30)).timeout(Duration::from_secs(15));
+    let mut client = NetworkSettings::default().client_builder()?;
     if let Some(proxy) = proxy {
       client = client.proxy(proxy);
     }
@@ -144,7 +252,13 @@ impl DownloadJob {
   }
 }
 
-async fn download(job_name: String, client: Client, retries: usize, downloadable: DownloadableSync) -> Result<DownloadableSync, DownloadError> {
+async fn download(
+  job_name: String,
+  client: Client,
+  retry_policy: RetryPolicy,
+  control: DownloadControl,
+  downloadable: DownloadableSync
+) -> Result<DownloadableSync, FailedDownload> {
   if downloadable.get_start_time().is_none() {
     downloadable.set_start_time(Utc::now().timestamp_millis() as u64);
   }
@@ -152,12 +266,20 @@ async fn download(job_name: String, client: Client, retries: usize, downloadable
   let target_file = downloadable.get_target_file();
 
   let mut last_error = None;
-  for attempt in 0..retries {
-    info!("Attempting to download {} for job '{}'... (try {})", target_file.display(), job_name, attempt);
+  let mut attempts = 0;
+  loop {
+    if let Err(err) = control.checkpoint().await {
+      last_error.replace(err);
+      break;
+    }
+    attempts += 1;
+    info!("Attempting to download {} for job '{}'... (try {})", target_file.display(), job_name, attempts);
 
+    let monitor = downloadable.get_monitor();
+    let connection = monitor.acquire_connection(downloadable.url()).await;
     let download_result = downloadable.download(&client).await;
+    drop(connection);
 
-    let monitor = downloadable.get_monitor();
     monitor.set_current(monitor.get_total());
 
     match download_result {
@@ -166,16 +288,36 @@ async fn download(job_name: String, client: Client, retries: usize, downloadable
         downloadable.set_end_time(Utc::now().timestamp_millis() as u64);
         return Ok(downloadable);
       }
+      Err(DownloadError::Cancelled) => {
+        info!("Cancelled download of {} for job '{}'", target_file.display(), job_name);
+        last_error.replace(DownloadError::Cancelled);
+        break;
+      }
       Err(err) => {
         warn!("Couldn't download {} for job '{}': {}", downloadable.url(), job_name, err);
+        let delay = retry_policy.next_delay(attempts, err.failure_kind());
         last_error.replace(err);
+        match delay {
+          Some(delay) => sleep(delay).await,
+          None => {
+            break;
+          }
+        }
       }
     }
   }
 
-  error!("Gave up trying to download {} for job '{}'", downloadable.url(), job_name);
   match last_error {
-    Some(err) => Err(err),
+    Some(error) => {
+      error!("Gave up trying to download {} for job '{}'", downloadable.url(), job_name);
+      Err(FailedDownload {
+        url: downloadable.url().clone(),
+        target: target_file.clone(),
+        attempts,
+        error,
+        downloadable,
+      })
+    }
     None => Ok(downloadable),
   }
 }
@@ -226,3 +368,109 @@ impl JobMonitor {
     Arc::new(CallbackReporter::new(move |_| monitor.fire_update()))
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{ sync::{ atomic::{ AtomicBool, Ordering }, Mutex }, time::Duration };
+
+  use async_trait::async_trait;
+
+  use super::*;
+  use crate::version_manager::downloader::downloadables::{ DownloadableMonitor, HashError };
+
+  struct FlakyDownloadable {
+    url: String,
+    target_file: PathBuf,
+    fail: Arc<AtomicBool>,
+    monitor: Arc<DownloadableMonitor>,
+    start_time: Mutex<Option<u64>>,
+    end_time: Mutex<Option<u64>>,
+  }
+
+  impl FlakyDownloadable {
+    fn boxed(url: &str, fail: &Arc<AtomicBool>) -> Box<dyn Downloadable + Send + Sync> {
+      Box::new(Self {
+        url: url.to_string(),
+        target_file: PathBuf::from(url),
+        fail: Arc::clone(fail),
+        monitor: Arc::new(DownloadableMonitor::new(0, 0)),
+        start_time: Mutex::new(None),
+        end_time: Mutex::new(None),
+      })
+    }
+  }
+
+  #[async_trait]
+  impl Downloadable for FlakyDownloadable {
+    fn url(&self) -> &String {
+      &self.url
+    }
+
+    fn get_target_file(&self) -> &PathBuf {
+      &self.target_file
+    }
+
+    fn get_status(&self) -> String {
+      self.url.clone()
+    }
+
+    fn get_monitor(&self) -> &Arc<DownloadableMonitor> {
+      &self.monitor
+    }
+
+    fn get_start_time(&self) -> Option<u64> {
+      *self.start_time.lock().unwrap()
+    }
+
+    fn set_start_time(&self, start_time: u64) {
+      self.start_time.lock().unwrap().replace(start_time);
+    }
+
+    fn get_end_time(&self) -> Option<u64> {
+      *self.end_time.lock().unwrap()
+    }
+
+    fn set_end_time(&self, end_time: u64) {
+      self.end_time.lock().unwrap().replace(end_time);
+    }
+
+    async fn get_expected_hash(&self, _: &Client) -> Result<Option<Vec<u8>>, HashError> {
+      Ok(None)
+    }
+
+    async fn download(&self, _: &Client) -> Result<(), DownloadError> {
+      if self.fail.load(Ordering::SeqCst) {
+        return Err(DownloadError::Other("offline".into()));
+      }
+      Ok(())
+    }
+  }
+
+  #[tokio::test]
+  async fn test_report_and_retry_failed() {
+    let never_fails = Arc::new(AtomicBool::new(false));
+    let offline = Arc::new(AtomicBool::new(true));
+    let mut job = DownloadJob::new("Test")
+      .with_retry_policy(RetryPolicy::default().with_max_attempts(2).with_base_delay(Duration::from_millis(1)))
+      .ignore_failures(true)
+      .add_downloadables(vec![FlakyDownloadable::boxed("good", &never_fails), FlakyDownloadable::boxed("flaky", &offline)]);
+
+    let report = job.start().await.unwrap();
+    assert_eq!(report.downloaded, 1);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].url, "flaky");
+    assert_eq!(report.failures[0].attempts, 2);
+
+    offline.store(false, Ordering::SeqCst);
+    let report = job.retry_failed(report).await.unwrap();
+    assert_eq!(report.downloaded, 1);
+    assert!(report.is_success());
+  }
+
+  #[tokio::test]
+  async fn test_failures_fail_the_job() {
+    let offline = Arc::new(AtomicBool::new(true));
+    let result = DownloadJob::new("Test").with_retries(1).add_downloadables(vec![FlakyDownloadable::boxed("flaky", &offline)]).start().await;
+    assert!(matches!(result, Err(Error::JobFailed(report)) if report.failures.len() == 1));
+  }
+}
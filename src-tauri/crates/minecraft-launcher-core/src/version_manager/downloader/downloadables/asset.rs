@@ -1,15 +1,16 @@
-use std::{ fs::{ self, File }, io::{ Read, Write }, path::{ Path, PathBuf, MAIN_SEPARATOR_STR }, sync::{ Arc, Mutex } };
+use std::{ fs::File, io::{ self, BufReader }, path::{ Path, PathBuf, MAIN_SEPARATOR_STR }, sync::{ Arc, Mutex } };
 
 use async_trait::async_trait;
-use libflate::non_blocking::gzip::Decoder as GzipDecoder;
+use libflate::gzip::Decoder as GzipDecoder;
 use log::{ info, warn };
 use reqwest::{ Client, Url };
 use sha1::{ Digest, Sha1 };
 use thiserror::Error;
+use tokio::fs;
 
 use crate::json::{ manifest::assets::AssetObject, Sha1Sum };
 
-use super::{ error::HashError, DownloadError, Downloadable, DownloadableMonitor };
+use super::{ commit_verified, error::HashError, hash_file, AtomicFile, DownloadError, Downloadable, DownloadableMonitor, HashingWriter };
 
 pub struct AssetDownloadable {
   pub asset_name: String,
@@ -52,53 +53,50 @@ impl AssetDownloadable {
     }
   }
 
-  fn decompress_asset(&self, compressed_target: &PathBuf) -> Result<Sha1Sum, DownloadError> {
+  async fn decompress_asset(&self, compressed_target: &Path) -> Result<(AtomicFile, Sha1Sum), DownloadError> {
     let target = self.get_target_file();
     self.set_status(AssetDownloadableStatus::Extracting);
 
-    self.prepare_destination(target).map_err(DownloadError::PrepareDestination)?;
-
-    let mut compressed_file = File::open(compressed_target).map_err(CompressedAssetError::ReadAsset)?;
-    let mut decoder = GzipDecoder::new(&mut compressed_file);
-
-    let mut sha1 = Sha1::new();
-    let mut file = File::create(target).map_err(DownloadError::WriteFile)?;
-
-    let mut read_buf = [0; 8192];
-    while let Ok(size) = decoder.read(&mut read_buf) {
-      if size == 0 {
-        break;
-      }
-      let buf = &read_buf[..size];
-      file.write_all(buf).map_err(DownloadError::WriteFile)?;
-      file.flush().map_err(DownloadError::WriteFile)?;
-      sha1.update(buf);
-    }
-    Ok(Sha1Sum::from(sha1))
+    self.prepare_destination(target).await.map_err(DownloadError::PrepareDestination)?;
+
+    let compressed_file = File::open(compressed_target).map_err(CompressedAssetError::ReadAsset)?;
+    let mut file = AtomicFile::create(target).await.map_err(DownloadError::WriteFile)?;
+
+    // Stream the decompressed asset into the file, without holding it in memory
+    let sha1 = file
+      .write_blocking(move |writer| {
+        let mut decoder = GzipDecoder::new(BufReader::new(compressed_file))?;
+        let mut writer = HashingWriter::new(writer);
+        io::copy(&mut decoder, &mut writer)?;
+        Ok(writer.finish())
+      }).await
+      .map_err(CompressedAssetError::Decompress)?;
+    Ok((file, sha1))
   }
 
   async fn try_download_compressed(
     &self,
     client: &Client,
     compressed_url: &str,
-    compressed_target: &PathBuf
-  ) -> Result<Sha1Sum, CompressedAssetError> {
+    compressed_target: &Path
+  ) -> Result<(AtomicFile, Sha1Sum), CompressedAssetError> {
     let monitor = self.get_monitor();
 
-    self.prepare_destination(compressed_target).map_err(CompressedAssetError::PrepareDestination)?;
-    let mut response = client.get(compressed_url).send().await?.error_for_status()?;
+    self.prepare_destination(compressed_target).await.map_err(CompressedAssetError::PrepareDestination)?;
+    let mut response = monitor.fetch(client, self.get_service(), compressed_url).await?.error_for_status()?;
     if let Some(content_len) = response.content_length() {
       monitor.set_total(content_len as usize);
     }
 
     let mut sha1 = Sha1::new();
-    let mut file = File::create(compressed_target).map_err(CompressedAssetError::WriteFile)?;
+    let mut file = AtomicFile::create(compressed_target).await.map_err(CompressedAssetError::WriteFile)?;
     while let Some(bytes) = response.chunk().await? {
-      file.write_all(&bytes).map_err(CompressedAssetError::WriteFile)?;
-      file.flush().map_err(CompressedAssetError::WriteFile)?;
+      monitor.checkpoint().await.map_err(|_| CompressedAssetError::Cancelled)?;
+      monitor.throttle(bytes.len()).await;
+      file.write_all(&bytes).await.map_err(CompressedAssetError::WriteFile)?;
       sha1.update(&bytes);
     }
-    Ok(Sha1Sum::from(sha1))
+    Ok((file, Sha1Sum::from(sha1)))
   }
 
   fn set_status(&self, status: AssetDownloadableStatus) {
@@ -152,24 +150,29 @@ impl Downloadable for AssetDownloadable {
     // 1. Check if the asset is already downloaded
     if target.is_file() {
       if let Some(expected) = &expected_hash {
-        let actual = self.calculate_local_hash()?;
+        let actual = self.calculate_local_hash().await?;
         if actual == *expected {
           info!("Local file matches hash, using it");
+          self.get_monitor().add_to_store(Some(expected), target).await;
           return Ok(());
         }
         // Hash mismatch, remove the file
-        fs::remove_file(target).map_err(DownloadError::RemoveFile)?;
+        fs::remove_file(target).await.map_err(DownloadError::RemoveFile)?;
       } else {
         info!("Local file exists, but no hash, assuming it's good");
         return Ok(());
       }
     }
 
+    if self.get_monitor().link_from_store(expected_hash.as_deref(), target).await {
+      return Ok(());
+    }
+
     // 2. - If the asset has a compressed version, download (if needed) and unpack
     //    - If the asset has no compressed version, download normally
     self.set_status(AssetDownloadableStatus::Downloading);
     let actual_hash = match &self.asset.compressed_hash {
-      None => self.try_download(client).await,
+      None => self.download_to_temp(client).await,
       Some(expected_compressed_hash) => {
         let hash_path = AssetObject::create_path_from_hash(expected_compressed_hash);
         let compressed_target = self.destination_dir.join(&hash_path);
@@ -181,35 +184,27 @@ impl Downloadable for AssetDownloadable {
         };
 
         if compressed_target.is_file() {
-          let mut compressed_file = File::open(&compressed_target).map_err(CompressedAssetError::ReadAsset)?;
-          let actual_compressed_hash = Sha1Sum::from_reader(&mut compressed_file).map_err(CompressedAssetError::ReadAsset)?;
+          let actual_compressed_hash = hash_file(&compressed_target).await.map_err(CompressedAssetError::ReadAsset)?;
           if &actual_compressed_hash != expected_compressed_hash {
             warn!("Had local compressed but it was the wrong hash... expected {} but had {}", expected_compressed_hash, actual_compressed_hash);
-            fs::remove_file(&compressed_target).map_err(CompressedAssetError::RemoveFile)?;
+            fs::remove_file(&compressed_target).await.map_err(CompressedAssetError::RemoveFile)?;
           }
         }
 
         if !compressed_target.is_file() {
-          let actual_compressed_hash = self.try_download_compressed(client, &compressed_url, &compressed_target).await?;
-          // If compressed hash matches
-          if &actual_compressed_hash != expected_compressed_hash {
-            let _ = fs::remove_file(compressed_target);
-            return Err(DownloadError::ChecksumMismatch { expected: expected_compressed_hash.clone().into(), actual: actual_compressed_hash.into() });
-          }
+          let (compressed_file, actual_compressed_hash) = self.try_download_compressed(client, &compressed_url, &compressed_target).await?;
+          // Only keep the compressed asset if its hash matches
+          commit_verified(compressed_file, actual_compressed_hash, Some(expected_compressed_hash.as_slice())).await?;
         }
-        self.decompress_asset(&compressed_target)
+        self.decompress_asset(&compressed_target).await
       }
     };
-    let actual_hash = actual_hash?;
-
-    // 3. Verify download
-    if let Some(expected) = &expected_hash {
-      if actual_hash.as_slice() != expected {
-        // Try to remove. If it fails, we don't care
-        let _ = fs::remove_file(target);
-        return Err(DownloadError::ChecksumMismatch { expected: expected.clone(), actual: actual_hash.into() });
-      }
+    let (file, actual_hash) = actual_hash?;
 
+    // 3. Verify download and move it into place
+    commit_verified(file, actual_hash, expected_hash.as_deref()).await?;
+    self.get_monitor().add_to_store(expected_hash.as_deref(), target).await;
+    if expected_hash.is_some() {
       info!("Downloaded asset and hash matched successfully");
     } else {
       info!("Downloaded successfully asset but no checksum provided, assuming it's good");
@@ -239,8 +234,10 @@ pub enum CompressedAssetError {
   #[error("Failed to write compressed asset: {0}")] WriteFile(#[source] std::io::Error),
   #[error("Failed to remove compressed asset: {0}")] RemoveFile(#[source] std::io::Error),
   #[error("Failed to read compressed asset: {0}")] ReadAsset(#[source] std::io::Error),
+  #[error("Failed to decompress asset: {0}")] Decompress(#[source] std::io::Error),
   #[error("Couldn't prepare the destination folder for the compressed asset: {0}")] PrepareDestination(#[source] std::io::Error),
   #[error("Failed to download compressed asset: {0}")] Download(#[from] reqwest::Error),
+  #[error("Download was cancelled")] Cancelled,
   #[error(
     "Hash did not match downloaded compressed asset (Expected {}, downloaded {})",
     hex::encode(expected),
@@ -253,6 +250,11 @@ pub enum CompressedAssetError {
 
 impl From<CompressedAssetError> for DownloadError {
   fn from(val: CompressedAssetError) -> Self {
-    DownloadError::Other(Box::new(val))
+    match val {
+      CompressedAssetError::Cancelled => DownloadError::Cancelled,
+      CompressedAssetError::Download(err) => DownloadError::Download(err),
+      CompressedAssetError::ChecksumMismatch { expected, actual } => DownloadError::CompressedChecksumMismatch { expected, actual },
+      val => DownloadError::Other(Box::new(val)),
+    }
   }
 }
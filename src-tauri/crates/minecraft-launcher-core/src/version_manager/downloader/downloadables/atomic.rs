@@ -0,0 +1,197 @@
+use std::{ fs, io::{ self, Write }, path::{ Path, PathBuf }, process, sync::atomic::{ AtomicUsize, Ordering } };
+
+use sha1::{ Digest, Sha1 };
+use tokio::{ fs::File, io::{ AsyncWriteExt, BufWriter } };
+
+use crate::json::Sha1Sum;
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A file that is written next to its target and only renamed into place once complete,
+/// so an interrupted download never leaves a truncated file at the target path.
+///
+/// Writes are buffered and don't block the runtime. The temporary file is removed if this
+/// is dropped without calling `commit`.
+pub struct AtomicFile {
+  target: PathBuf,
+  temp: PathBuf,
+  writer: Option<BufWriter<File>>,
+  committed: bool,
+}
+
+impl AtomicFile {
+  pub async fn create(target: &Path) -> io::Result<Self> {
+    let temp = temp_path(target);
+    let file = File::create(&temp).await?;
+    Ok(Self { target: target.to_path_buf(), temp, writer: Some(BufWriter::new(file)), committed: false })
+  }
+
+  pub fn get_target(&self) -> &Path {
+    &self.target
+  }
+
+  pub fn get_temp_path(&self) -> &Path {
+    &self.temp
+  }
+
+  pub async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+    self.writer()?.write_all(buf).await
+  }
+
+  /// Writes the buffered contents to the temporary file, e.g. to hash it before committing
+  pub async fn flush(&mut self) -> io::Result<()> {
+    self.writer()?.flush().await
+  }
+
+  /// Runs blocking code (like a decompressor) that writes into the file, on the blocking thread pool
+  pub async fn write_blocking<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+      T: Send + 'static,
+      E: From<io::Error> + Send + 'static,
+      F: FnOnce(&mut io::BufWriter<fs::File>) -> Result<T, E> + Send + 'static
+  {
+    let mut writer = self.writer.take().ok_or_else(already_committed)?;
+    writer.flush().await?;
+    let file = writer.into_inner().into_std().await;
+
+    let (file, value) = tokio::task
+      ::spawn_blocking(move || {
+        let mut writer = io::BufWriter::new(file);
+        let value = f(&mut writer)?;
+        let file = writer.into_inner().map_err(|err| err.into_error())?;
+        Ok::<_, E>((file, value))
+      }).await
+      .map_err(io::Error::other)??;
+
+    self.writer = Some(BufWriter::new(File::from_std(file)));
+    Ok(value)
+  }
+
+  /// Syncs the contents to disk and renames the file into place, replacing the target
+  pub async fn commit(mut self) -> io::Result<()> {
+    let mut writer = self.writer.take().ok_or_else(already_committed)?;
+    writer.flush().await?;
+    let file = writer.into_inner();
+    file.sync_all().await?;
+    // Windows can't rename a file that is still open
+    drop(file);
+
+    tokio::fs::rename(&self.temp, &self.target).await?;
+    self.committed = true;
+    sync_parent(&self.target).await;
+    Ok(())
+  }
+
+  fn writer(&mut self) -> io::Result<&mut BufWriter<File>> {
+    self.writer.as_mut().ok_or_else(already_committed)
+  }
+}
+
+impl Drop for AtomicFile {
+  fn drop(&mut self) {
+    if !self.committed {
+      self.writer.take();
+      let _ = fs::remove_file(&self.temp);
+    }
+  }
+}
+
+fn already_committed() -> io::Error {
+  io::Error::other("File was already committed or failed")
+}
+
+/// `<dir>/.<name>.<pid>-<n>.tmp`, unique between concurrent downloads of the same file
+pub(crate) fn temp_path(target: &Path) -> PathBuf {
+  let name = target.file_name().unwrap_or_default().to_string_lossy();
+  let n = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+  target.with_file_name(format!(".{}.{}-{}.tmp", name, process::id(), n))
+}
+
+/// Makes the rename durable. Not every platform lets us open a directory, so errors are ignored
+async fn sync_parent(target: &Path) {
+  #[cfg(unix)]
+  if let Some(parent) = target.parent() {
+    if let Ok(dir) = File::open(parent).await {
+      let _ = dir.sync_all().await;
+    }
+  }
+  #[cfg(not(unix))]
+  let _ = target;
+}
+
+/// Hashes everything written through it with SHA-1
+pub struct HashingWriter<W> {
+  inner: W,
+  sha1: Sha1,
+}
+
+impl<W: Write> HashingWriter<W> {
+  pub fn new(inner: W) -> Self {
+    Self { inner, sha1: Sha1::new() }
+  }
+
+  pub fn finish(self) -> Sha1Sum {
+    self.sha1.into()
+  }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let written = self.inner.write(buf)?;
+    self.sha1.update(&buf[..written]);
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.flush()
+  }
+}
+
+/// Hashes a file with SHA-1 on the blocking thread pool
+pub async fn hash_file(path: &Path) -> io::Result<Sha1Sum> {
+  let path = path.to_path_buf();
+  tokio::task
+    ::spawn_blocking(move || {
+      let mut reader = io::BufReader::new(fs::File::open(path)?);
+      Sha1Sum::from_reader(&mut reader)
+    }).await
+    .map_err(io::Error::other)?
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{ env::temp_dir, io::Write };
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_commit_and_discard() {
+    let dir = temp_dir().join("minecraft-launcher-core-atomic");
+    fs::create_dir_all(&dir).unwrap();
+    let target = dir.join("file.txt");
+    fs::write(&target, b"old").unwrap();
+
+    let mut file = AtomicFile::create(&target).await.unwrap();
+    file.write_all(b"interrupted").await.unwrap();
+    let temp = file.get_temp_path().to_path_buf();
+    assert!(temp.is_file());
+    drop(file);
+    assert!(!temp.exists());
+    assert_eq!(fs::read(&target).unwrap(), b"old");
+
+    let mut file = AtomicFile::create(&target).await.unwrap();
+    file.write_all(b"new ").await.unwrap();
+    let sha1 = file
+      .write_blocking(|writer| {
+        let mut writer = HashingWriter::new(writer);
+        writer.write_all(b"contents")?;
+        Ok::<_, io::Error>(writer.finish())
+      }).await
+      .unwrap();
+    file.commit().await.unwrap();
+    assert_eq!(fs::read(&target).unwrap(), b"new contents");
+    assert_eq!(sha1, Sha1Sum::from_reader(&mut &b"contents"[..]).unwrap());
+    assert_eq!(hash_file(&target).await.unwrap(), Sha1Sum::from_reader(&mut &b"new contents"[..]).unwrap());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+  }
+}
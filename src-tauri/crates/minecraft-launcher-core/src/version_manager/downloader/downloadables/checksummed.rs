@@ -65,7 +65,7 @@ impl Downloadable for ChecksummedDownloadable {
 
   async fn get_expected_hash(&self, client: &Client) -> Result<Option<Vec<u8>>, HashError> {
     let url = format!("{}.sha1", self.url);
-    let response = client.get(url).send().await?.error_for_status()?;
+    let response = self.monitor.fetch(client, self.get_service(), &url).await?.error_for_status()?;
     let hash = response.text().await?;
     Ok(Some(hex::decode(hash)?))
   }
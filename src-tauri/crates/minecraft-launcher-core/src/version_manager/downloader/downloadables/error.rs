@@ -1,19 +1,52 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
 use thiserror::Error;
 
+use crate::version_manager::downloader::retry::FailureKind;
+
 #[derive(Debug, Error)]
 pub enum DownloadError {
   #[error("Failed to write destination file: {0}")] WriteFile(#[source] std::io::Error),
   #[error("Failed to remove invalid file: {0}")] RemoveFile(#[source] std::io::Error),
   #[error("Couldn't prepare destination folder: {0}")] PrepareDestination(#[source] std::io::Error),
   #[error("Failed to download: {0}")] Download(#[from] reqwest::Error),
+  #[error("Server responded with {status} for {url}")] HttpStatus {
+    url: String,
+    status: StatusCode,
+    retry_after: Option<Duration>,
+  },
   #[error("Checksum did not match downloaded file (Checksum was {}, downloaded {})", hex::encode(actual), hex::encode(expected))] ChecksumMismatch {
     expected: Vec<u8>,
     actual: Vec<u8>,
   },
+  /// The compressed file (an LZMA asset or runtime file) was corrupted, it's downloaded again
+  #[error("Checksum did not match the downloaded compressed file (Expected {}, downloaded {})", hex::encode(expected), hex::encode(actual))] CompressedChecksumMismatch {
+    expected: Vec<u8>,
+    actual: Vec<u8>,
+  },
+  #[error("Download was cancelled")] Cancelled,
   #[error(transparent)] HashError(#[from] HashError),
   #[error(transparent)] Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
+impl DownloadError {
+  /// Classifies the error for the retry policy
+  pub fn failure_kind(&self) -> FailureKind {
+    match self {
+      DownloadError::HttpStatus { status, retry_after, .. } =>
+        match FailureKind::from_status(*status, None) {
+          FailureKind::RateLimited(_) => FailureKind::RateLimited(*retry_after),
+          kind => kind,
+        }
+      DownloadError::Download(err) | DownloadError::HashError(HashError::FetchHash(err)) => FailureKind::from_reqwest(err),
+      DownloadError::ChecksumMismatch { .. } | DownloadError::CompressedChecksumMismatch { .. } => FailureKind::Checksum,
+      DownloadError::Other(_) => FailureKind::Transient,
+      _ => FailureKind::Permanent,
+    }
+  }
+}
+
 #[derive(Debug, Error)]
 pub enum HashError {
   #[error("Failed to calculate hash for existing destination file: {0}")] ChecksumFile(#[from] std::io::Error),
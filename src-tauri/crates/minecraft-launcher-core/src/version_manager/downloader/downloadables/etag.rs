@@ -3,7 +3,7 @@ use std::{ ffi::OsStr, fs::File, io, path::{ Path, PathBuf }, sync::{ Arc, Mutex
 use async_trait::async_trait;
 use log::info;
 use md5::{ Digest, Md5 };
-use reqwest::{ header::HeaderValue, Client };
+use reqwest::{ header::{ HeaderValue, ETAG }, Client };
 
 use super::{ error::HashError, DownloadError, Downloadable, DownloadableMonitor };
 
@@ -30,6 +30,30 @@ impl EtagDownloadable {
     }
   }
 
+  /// The MD5 of a file, which is what Mojang's legacy servers send as the ETag
+  async fn md5_file(path: &Path) -> Result<Vec<u8>, HashError> {
+    let path = path.to_path_buf();
+    let md5 = tokio::task
+      ::spawn_blocking(move || {
+        let mut reader = io::BufReader::new(File::open(path)?);
+        let mut md5 = Md5::new();
+        io::copy(&mut reader, &mut md5)?;
+        Ok::<_, io::Error>(md5.finalize().to_vec())
+      }).await
+      .map_err(io::Error::other)??;
+    Ok(md5)
+  }
+
+  /// The ETag as an MD5, or `None` if it isn't one (multipart uploads have a `-` in it)
+  fn parse_etag(etag: Option<&HeaderValue>) -> Result<Option<Vec<u8>>, HashError> {
+    let hash = Self::get_etag(etag);
+    if hash.contains('-') {
+      Ok(None)
+    } else {
+      Ok(Some(hex::decode(hash)?))
+    }
+  }
+
   fn get_etag(etag: Option<&HeaderValue>) -> String {
     let etag = etag.and_then(|v| v.to_str().ok()).unwrap_or("-");
     if etag.starts_with('"') && etag.ends_with('"') {
@@ -75,21 +99,12 @@ impl Downloadable for EtagDownloadable {
   }
 
   async fn get_expected_hash(&self, client: &Client) -> Result<Option<Vec<u8>>, HashError> {
-    let response = client.get(self.url()).send().await?.error_for_status()?;
-    let hash = Self::get_etag(response.headers().get("ETag"));
-    if hash.contains('-') {
-      Ok(None)
-    } else {
-      Ok(Some(hex::decode(hash)?))
-    }
+    let response = self.get_monitor().fetch(client, self.get_service(), self.url()).await?.error_for_status()?;
+    Self::parse_etag(response.headers().get(ETAG))
   }
 
-  fn calculate_local_hash(&self) -> Result<Vec<u8>, HashError> {
-    let target_file = self.get_target_file();
-    let mut reader = File::open(target_file)?;
-    let mut md5 = Md5::new();
-    io::copy(&mut reader, &mut md5)?;
-    Ok(md5.finalize().to_vec())
+  async fn calculate_local_hash(&self) -> Result<Vec<u8>, HashError> {
+    Self::md5_file(self.get_target_file()).await
   }
 
   async fn download(&self, client: &Client) -> Result<(), DownloadError> {
@@ -100,11 +115,15 @@ impl Downloadable for EtagDownloadable {
       return Ok(());
     }
 
-    self.try_download(client).await?;
-    let expected = self.get_expected_hash(client).await?;
+    // The ETag of the response that is downloaded, and the file is only committed if it matches.
+    // Otherwise a corrupted file would be kept, and skipped as good by the next run
+    let response = self.request_download(client).await?;
+    let expected = Self::parse_etag(response.headers().get(ETAG))?;
+    let (mut file, _) = self.download_response_to_temp(response).await?;
+    file.flush().await.map_err(DownloadError::WriteFile)?;
 
     if let Some(etag) = expected {
-      let md5 = self.calculate_local_hash()?;
+      let md5 = Self::md5_file(file.get_temp_path()).await?;
       if etag != md5 {
         return Err(DownloadError::ChecksumMismatch { expected: etag, actual: md5 });
       }
@@ -112,6 +131,49 @@ impl Downloadable for EtagDownloadable {
     } else {
       info!("Didn't have etag so assuming our copy is good");
     }
-    Ok(())
+    file.commit().await.map_err(DownloadError::WriteFile)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{ env::temp_dir, fs };
+
+  use tokio::{ io::{ AsyncReadExt, AsyncWriteExt }, net::TcpListener };
+
+  use super::*;
+
+  /// Serves `body` with `etag` to every request
+  async fn serve(body: &'static [u8], etag: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/client.jar", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+      loop {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buffer = [0u8; 1024];
+        let _ = socket.read(&mut buffer).await;
+        let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nETag: \"{}\"\r\nConnection: close\r\n\r\n", body.len(), etag);
+        let _ = socket.write_all(head.as_bytes()).await;
+        let _ = socket.write_all(body).await;
+      }
+    });
+    url
+  }
+
+  #[tokio::test]
+  async fn test_etag_checked_before_commit() {
+    let dir = temp_dir().join("minecraft-launcher-core-etag");
+    let _ = fs::remove_dir_all(&dir);
+    let target = dir.join("client.jar");
+    let client = Client::new();
+
+    let url = serve(b"corrupted", hex::encode(Md5::digest(b"client"))).await;
+    let err = EtagDownloadable::new(&url, &target, false).download(&client).await.unwrap_err();
+    assert!(matches!(err, DownloadError::ChecksumMismatch { .. }));
+    assert!(!target.exists());
+
+    let url = serve(b"client", hex::encode(Md5::digest(b"client"))).await;
+    EtagDownloadable::new(&url, &target, false).download(&client).await.unwrap();
+    assert_eq!(fs::read(&target).unwrap(), b"client");
   }
 }
@@ -1,27 +1,36 @@
-use std::{ fs::{ self, create_dir_all, File }, io::Write, path::{ Path, PathBuf }, sync::{ Arc, Mutex } };
+use std::{ path::{ Path, PathBuf }, sync::{ Arc, Mutex } };
 
 use async_trait::async_trait;
-use log::info;
-use reqwest::Client;
+use log::{ info, warn };
+use reqwest::{ Client, Response };
 use sha1::{ Digest, Sha1 };
+use tokio::fs;
 
-use crate::json::Sha1Sum;
+use crate::{ json::Sha1Sum, version_manager::{ endpoints::{ Endpoints, Service }, object_store::ObjectStore } };
 
 mod checksummed;
 mod prehashed;
 mod etag;
 mod asset;
 
+mod atomic;
 mod error;
 
+pub use atomic::{ hash_file, AtomicFile, HashingWriter };
+pub(crate) use atomic::temp_path;
 pub use error::{ DownloadError, HashError };
 
 pub use checksummed::ChecksummedDownloadable;
 pub use prehashed::PreHashedDownloadable;
 pub use etag::EtagDownloadable;
-pub use asset::{ AssetDownloadable, AssetDownloadableStatus };
+pub use asset::{ AssetDownloadable, AssetDownloadableStatus, CompressedAssetError };
 
-use super::progress::{ EmptyReporter, ProgressReporter };
+use super::{
+  control::DownloadControl,
+  limiter::{ ConnectionPermit, NetworkLimiter },
+  progress::{ EmptyReporter, ProgressReporter },
+  retry::parse_retry_after,
+};
 
 #[async_trait]
 pub trait Downloadable: Send + Sync {
@@ -30,17 +39,22 @@ pub trait Downloadable: Send + Sync {
   fn get_status(&self) -> String;
   fn get_monitor(&self) -> &Arc<DownloadableMonitor>;
 
+  /// The service whose mirrors are tried before `url`, if it points to an official server
+  fn get_service(&self) -> Option<Service> {
+    Service::from_url(self.url())
+  }
+
   fn get_start_time(&self) -> Option<u64>;
   fn set_start_time(&self, start_time: u64);
   fn get_end_time(&self) -> Option<u64>;
   fn set_end_time(&self, end_time: u64);
 
   // TODO: remove?
-  fn prepare_destination(&self, file: &Path) -> Result<(), std::io::Error> {
+  async fn prepare_destination(&self, file: &Path) -> Result<(), std::io::Error> {
     if let Some(parent) = file.parent() {
       if !parent.is_dir() {
         info!("Making directory {}", parent.display());
-        create_dir_all(parent)?;
+        fs::create_dir_all(parent).await?;
       }
     }
 
@@ -49,34 +63,49 @@ pub trait Downloadable: Send + Sync {
 
   async fn get_expected_hash(&self, client: &Client) -> Result<Option<Vec<u8>>, HashError>;
 
-  fn calculate_local_hash(&self) -> Result<Vec<u8>, HashError> {
-    let target_file = self.get_target_file();
-    let mut reader = File::open(target_file)?;
-    Ok(Sha1Sum::from_reader(&mut reader)?.into())
+  async fn calculate_local_hash(&self) -> Result<Vec<u8>, HashError> {
+    Ok(hash_file(self.get_target_file()).await?.into())
   }
 
-  // Forces the download even if the target file already exists
-  async fn try_download(&self, client: &Client) -> Result<Sha1Sum, DownloadError> {
-    let target_file = self.get_target_file();
-    let monitor = self.get_monitor();
+  // Streams the download into a temporary file next to the target, which is left untouched.
+  // The caller decides whether to commit it
+  async fn download_to_temp(&self, client: &Client) -> Result<(AtomicFile, Sha1Sum), DownloadError> {
+    let response = self.request_download(client).await?;
+    self.download_response_to_temp(response).await
+  }
 
-    self.prepare_destination(target_file).map_err(DownloadError::PrepareDestination)?;
+  // Prepares the destination folder and requests the file, for downloadables that need the response headers
+  async fn request_download(&self, client: &Client) -> Result<Response, DownloadError> {
+    self.prepare_destination(self.get_target_file()).await.map_err(DownloadError::PrepareDestination)?;
+    check_status(self.get_monitor().fetch(client, self.get_service(), self.url()).await?)
+  }
 
-    let mut response = client.get(self.url()).send().await?.error_for_status()?;
+  // Like `download_to_temp`, with a response from `request_download`
+  async fn download_response_to_temp(&self, mut response: Response) -> Result<(AtomicFile, Sha1Sum), DownloadError> {
+    let monitor = self.get_monitor();
     if let Some(content_len) = response.content_length() {
       monitor.set_total(content_len as usize);
     }
 
-    let mut file = File::create(target_file).map_err(DownloadError::WriteFile)?;
+    // Returning early drops the file, which removes it
+    let mut file = AtomicFile::create(self.get_target_file()).await.map_err(DownloadError::WriteFile)?;
     let mut sha1 = Sha1::new();
 
     while let Some(chunk) = response.chunk().await? {
-      file.write_all(&chunk).map_err(DownloadError::WriteFile)?;
-      file.flush().map_err(DownloadError::WriteFile)?;
+      monitor.checkpoint().await?;
+      monitor.throttle(chunk.len()).await;
+      file.write_all(&chunk).await.map_err(DownloadError::WriteFile)?;
       sha1.update(&chunk);
     }
 
-    Ok(sha1.into())
+    Ok((file, sha1.into()))
+  }
+
+  // Forces the download even if the target file already exists
+  async fn try_download(&self, client: &Client) -> Result<Sha1Sum, DownloadError> {
+    let (file, sha1) = self.download_to_temp(client).await?;
+    file.commit().await.map_err(DownloadError::WriteFile)?;
+    Ok(sha1)
   }
 
   // async fn download(&self, client: &Client) -> Result<(), DownloadError>;
@@ -86,27 +115,28 @@ pub trait Downloadable: Send + Sync {
 
     if target.is_file() {
       if let Some(expected) = &expected {
-        let actual = self.calculate_local_hash()?;
+        let actual = self.calculate_local_hash().await?;
         if actual == *expected {
           info!("Local file matches hash, using it");
+          self.get_monitor().add_to_store(Some(expected), target).await;
           return Ok(());
         }
         // Hash mismatch, remove the file
-        fs::remove_file(target).map_err(DownloadError::RemoveFile)?;
+        fs::remove_file(target).await.map_err(DownloadError::RemoveFile)?;
       } else {
         info!("Local file exists, but no hash, assuming it's good");
         return Ok(());
       }
     }
 
-    let actual = self.try_download(client).await?;
-    if let Some(expected) = &expected {
-      if actual.as_slice() != expected {
-        // Try to remove. If it fails, we don't care
-        let _ = fs::remove_file(target);
-        return Err(DownloadError::ChecksumMismatch { expected: expected.clone(), actual: actual.into() });
-      }
+    if self.get_monitor().link_from_store(expected.as_deref(), target).await {
+      return Ok(());
+    }
 
+    let (file, actual) = self.download_to_temp(client).await?;
+    commit_verified(file, actual, expected.as_deref()).await?;
+    self.get_monitor().add_to_store(expected.as_deref(), target).await;
+    if expected.is_some() {
       info!("Downloaded successfully and checksum matched");
     } else {
       info!("Downloaded successfully but no checksum provided, assuming it's good");
@@ -115,10 +145,39 @@ pub trait Downloadable: Send + Sync {
   }
 }
 
+/// Renames a finished download into place if it matches the expected hash, or discards it otherwise
+pub async fn commit_verified(file: AtomicFile, actual: Sha1Sum, expected: Option<&[u8]>) -> Result<(), DownloadError> {
+  if let Some(expected) = expected {
+    if actual.as_slice() != expected {
+      return Err(DownloadError::ChecksumMismatch { expected: expected.to_vec(), actual: actual.into() });
+    }
+  }
+  file.commit().await.map_err(DownloadError::WriteFile)
+}
+
+/// Like `Response::error_for_status`, but keeps the `Retry-After` header for the retry policy
+pub fn check_status(response: Response) -> Result<Response, DownloadError> {
+  let status = response.status();
+  if !status.is_client_error() && !status.is_server_error() {
+    return Ok(response);
+  }
+
+  let retry_after = response
+    .headers()
+    .get(reqwest::header::RETRY_AFTER)
+    .and_then(|value| value.to_str().ok())
+    .and_then(parse_retry_after);
+  Err(DownloadError::HttpStatus { url: response.url().to_string(), status, retry_after })
+}
+
 pub struct DownloadableMonitor {
   current: Mutex<usize>,
   total: Mutex<usize>,
   reporter: Mutex<ProgressReporter>,
+  control: Mutex<DownloadControl>,
+  limiter: Mutex<NetworkLimiter>,
+  endpoints: Mutex<Endpoints>,
+  object_store: Mutex<Option<ObjectStore>>,
 }
 
 impl DownloadableMonitor {
@@ -127,6 +186,10 @@ impl DownloadableMonitor {
       current: Mutex::new(current),
       total: Mutex::new(total),
       reporter: Mutex::new(Arc::new(EmptyReporter)),
+      control: Mutex::new(DownloadControl::new()),
+      limiter: Mutex::new(NetworkLimiter::global()),
+      endpoints: Mutex::new(Endpoints::default()),
+      object_store: Mutex::new(None),
     }
   }
 
@@ -152,4 +215,83 @@ impl DownloadableMonitor {
     *self.reporter.lock().unwrap() = reporter;
     // TODO: fire update?
   }
+
+  pub fn set_control(&self, control: DownloadControl) {
+    *self.control.lock().unwrap() = control;
+  }
+
+  pub fn set_limiter(&self, limiter: NetworkLimiter) {
+    *self.limiter.lock().unwrap() = limiter;
+  }
+
+  pub fn set_endpoints(&self, endpoints: Endpoints) {
+    *self.endpoints.lock().unwrap() = endpoints;
+  }
+
+  pub fn set_object_store(&self, object_store: Option<ObjectStore>) {
+    *self.object_store.lock().unwrap() = object_store;
+  }
+
+  /// Places the file with the `expected` SHA-1 at `target` from the object store, if it has it.
+  /// Returns whether it did; store errors are logged and treated as a miss
+  pub async fn link_from_store(&self, expected: Option<&[u8]>, target: &Path) -> bool {
+    let Some((store, sha1)) = self.get_store_entry(expected) else {
+      return false;
+    };
+    match store.link_into(&sha1, target).await {
+      Ok(Some(kind)) => {
+        info!("Placed {} from the object store ({:?})", target.display(), kind);
+        true
+      }
+      Ok(None) => false,
+      Err(err) => {
+        warn!("Couldn't place {} from the object store: {}", target.display(), err);
+        false
+      }
+    }
+  }
+
+  /// Adds a verified file to the object store. The download already succeeded, so errors are only logged
+  pub async fn add_to_store(&self, expected: Option<&[u8]>, target: &Path) {
+    let Some((store, sha1)) = self.get_store_entry(expected) else {
+      return;
+    };
+    if let Err(err) = store.insert(target, &sha1).await {
+      warn!("Couldn't add {} to the object store: {}", target.display(), err);
+    }
+  }
+
+  /// Only files with a known SHA-1 can be stored
+  fn get_store_entry(&self, expected: Option<&[u8]>) -> Option<(ObjectStore, Sha1Sum)> {
+    let sha1 = <[u8; 20]>::try_from(expected?).ok()?;
+    let store = self.object_store.lock().unwrap().clone()?;
+    Some((store, Sha1Sum::new(sha1)))
+  }
+
+  /// Sends a GET request to `url`, trying the mirrors of `service` first
+  pub async fn fetch(&self, client: &Client, service: Option<Service>, url: &str) -> reqwest::Result<Response> {
+    let endpoints = self.endpoints.lock().unwrap().clone();
+    match service {
+      Some(service) => endpoints.get(client, service, url).await,
+      None => client.get(url).send().await,
+    }
+  }
+
+  /// Waits while the download is paused, and fails if it was cancelled
+  pub async fn checkpoint(&self) -> Result<(), DownloadError> {
+    let control = self.control.lock().unwrap().clone();
+    control.checkpoint().await
+  }
+
+  /// Waits until `bytes` more can be downloaded without going over the bandwidth limit
+  pub async fn throttle(&self, bytes: usize) {
+    let limiter = self.limiter.lock().unwrap().clone();
+    limiter.consume(bytes).await;
+  }
+
+  /// Waits until the per-host connection limit allows connecting to `url`
+  pub async fn acquire_connection(&self, url: &str) -> ConnectionPermit {
+    let limiter = self.limiter.lock().unwrap().clone();
+    limiter.acquire_connection(url).await
+  }
 }
@@ -1,13 +1,15 @@
 use thiserror::Error;
 
-use super::downloadables::DownloadError;
+use super::{ download_job::JobReport, downloadables::DownloadError };
 
 #[derive(Error, Debug)]
 pub enum Error {
   #[error(transparent)] DownloadError(#[from] DownloadError),
-  #[error("Job '{name}' finished with {failures} failure(s)! (took {total_time}s)")] JobFailed {
+  #[error("Job '{}' finished with {} failure(s)! (took {}s)", .0.name, .0.failures.len(), .0.total_time)] JobFailed(JobReport),
+  #[error("Job '{name}' was cancelled")] Cancelled {
     name: String,
-    failures: usize,
-    total_time: i64,
+  },
+  #[error("{} file(s) are missing and need the network, but offline mode is enabled: {}", missing.len(), missing.join(", "))] Offline {
+    missing: Vec<String>,
   },
 }
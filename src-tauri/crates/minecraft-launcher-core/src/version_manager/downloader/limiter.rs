@@ -0,0 +1,229 @@
+use std::{ collections::HashMap, pin::pin, sync::{ Arc, Mutex, OnceLock }, time::{ Duration, Instant } };
+
+use reqwest::Url;
+use tokio::{ sync::Notify, time::sleep };
+
+/// How long a throttled download sleeps before checking the limits again,
+/// so changing them takes effect quickly even with a very low rate
+const MAX_THROTTLE_WAIT: Duration = Duration::from_millis(100);
+
+/// Bandwidth and per-host connection limits shared by every download that uses them.
+///
+/// Clones share the same limits, and changing them affects the downloads that are
+/// already running. Both limits are disabled by default.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkLimiter {
+  inner: Arc<LimiterInner>,
+}
+
+#[derive(Debug, Default)]
+struct LimiterInner {
+  bucket: Mutex<TokenBucket>,
+  hosts: Mutex<HostConnections>,
+  /// Notified when a connection is released or the connection limit changes
+  released: Notify,
+}
+
+#[derive(Debug, Default)]
+struct HostConnections {
+  max_per_host: Option<usize>,
+  active: HashMap<String, usize>,
+}
+
+impl NetworkLimiter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The limiter used by every downloader unless another one is given, so the limits
+  /// apply to all downloads in the process
+  pub fn global() -> Self {
+    static GLOBAL: OnceLock<NetworkLimiter> = OnceLock::new();
+    GLOBAL.get_or_init(NetworkLimiter::new).clone()
+  }
+
+  pub fn with_bytes_per_second(self, bytes_per_second: Option<u64>) -> Self {
+    self.set_bytes_per_second(bytes_per_second);
+    self
+  }
+
+  pub fn with_max_connections_per_host(self, max_connections: Option<usize>) -> Self {
+    self.set_max_connections_per_host(max_connections);
+    self
+  }
+
+  pub fn get_bytes_per_second(&self) -> Option<u64> {
+    self.inner.bucket.lock().unwrap().rate
+  }
+
+  /// Sets the total download rate, or `None` to disable it
+  pub fn set_bytes_per_second(&self, bytes_per_second: Option<u64>) {
+    self.inner.bucket.lock().unwrap().set_rate(bytes_per_second.filter(|rate| *rate > 0));
+  }
+
+  pub fn get_max_connections_per_host(&self) -> Option<usize> {
+    self.inner.hosts.lock().unwrap().max_per_host
+  }
+
+  /// Sets how many downloads can run at once against the same host, or `None` to disable it
+  pub fn set_max_connections_per_host(&self, max_connections: Option<usize>) {
+    self.inner.hosts.lock().unwrap().max_per_host = max_connections.map(|max| max.max(1));
+    self.inner.released.notify_waiters();
+  }
+
+  /// Waits until a connection to the host of `url` is allowed. The connection is released
+  /// when the returned permit is dropped
+  pub async fn acquire_connection(&self, url: &str) -> ConnectionPermit {
+    let host = host_key(url);
+    loop {
+      // Register before checking, so a release between the check and the wait isn't missed
+      let mut released = pin!(self.inner.released.notified());
+      released.as_mut().enable();
+
+      {
+        let mut hosts = self.inner.hosts.lock().unwrap();
+        let max_per_host = hosts.max_per_host;
+        let active = hosts.active.entry(host.clone()).or_default();
+        if max_per_host.is_none_or(|max| *active < max) {
+          *active += 1;
+          return ConnectionPermit { limiter: self.clone(), host };
+        }
+      }
+
+      released.await;
+    }
+  }
+
+  /// Waits until `bytes` can be downloaded without going over the rate limit
+  pub async fn consume(&self, bytes: usize) {
+    {
+      let mut bucket = self.inner.bucket.lock().unwrap();
+      if bucket.rate.is_none() {
+        return;
+      }
+      bucket.refill();
+      bucket.tokens -= bytes as f64;
+    }
+
+    loop {
+      let wait = {
+        let mut bucket = self.inner.bucket.lock().unwrap();
+        bucket.refill();
+        match bucket.rate {
+          Some(rate) if bucket.tokens < 0.0 => Duration::from_secs_f64(-bucket.tokens / (rate as f64)),
+          _ => {
+            return;
+          }
+        }
+      };
+      sleep(wait.min(MAX_THROTTLE_WAIT)).await;
+    }
+  }
+
+  fn release(&self, host: &str) {
+    let mut hosts = self.inner.hosts.lock().unwrap();
+    if let Some(active) = hosts.active.get_mut(host) {
+      *active -= 1;
+      if *active == 0 {
+        hosts.active.remove(host);
+      }
+    }
+    drop(hosts);
+    self.inner.released.notify_waiters();
+  }
+}
+
+/// A connection slot taken with `NetworkLimiter::acquire_connection`
+#[derive(Debug)]
+pub struct ConnectionPermit {
+  limiter: NetworkLimiter,
+  host: String,
+}
+
+impl Drop for ConnectionPermit {
+  fn drop(&mut self) {
+    self.limiter.release(&self.host);
+  }
+}
+
+/// Token bucket holding up to one second worth of bytes. Tokens go negative when a
+/// download takes more than what's available, and it waits until the debt is paid back
+#[derive(Debug)]
+struct TokenBucket {
+  rate: Option<u64>,
+  tokens: f64,
+  last_refill: Instant,
+}
+
+impl Default for TokenBucket {
+  fn default() -> Self {
+    Self { rate: None, tokens: 0.0, last_refill: Instant::now() }
+  }
+}
+
+impl TokenBucket {
+  fn refill(&mut self) {
+    let now = Instant::now();
+    if let Some(rate) = self.rate {
+      let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+      self.tokens = (self.tokens + elapsed * (rate as f64)).min(rate as f64);
+    }
+    self.last_refill = now;
+  }
+
+  fn set_rate(&mut self, rate: Option<u64>) {
+    self.refill();
+    self.rate = rate;
+    if let Some(rate) = rate {
+      self.tokens = self.tokens.min(rate as f64);
+    } else {
+      self.tokens = 0.0;
+    }
+  }
+}
+
+fn host_key(url: &str) -> String {
+  match Url::parse(url) {
+    Ok(url) => format!("{}:{}", url.host_str().unwrap_or_default(), url.port_or_known_default().unwrap_or_default()),
+    Err(_) => url.to_string(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_connections_per_host() {
+    let limiter = NetworkLimiter::new().with_max_connections_per_host(Some(1));
+    let first = limiter.acquire_connection("https://example.com/a").await;
+    // Other hosts aren't affected
+    let _other = limiter.acquire_connection("https://example.org/a").await;
+
+    let waiting = tokio::spawn({
+      let limiter = limiter.clone();
+      async move { limiter.acquire_connection("https://example.com/b").await }
+    });
+    for _ in 0..10 {
+      tokio::task::yield_now().await;
+    }
+    assert!(!waiting.is_finished());
+
+    drop(first);
+    waiting.await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_rate_limit() {
+    let limiter = NetworkLimiter::new().with_bytes_per_second(Some(10_000));
+    let start = Instant::now();
+    // The bucket starts empty, so this has to wait about 200ms
+    limiter.consume(2_000).await;
+    assert!(start.elapsed() >= Duration::from_millis(150));
+
+    limiter.set_bytes_per_second(None);
+    let start = Instant::now();
+    limiter.consume(1_000_000).await;
+    assert!(start.elapsed() < Duration::from_millis(50));
+  }
+}
@@ -1,19 +1,26 @@
-use std::{ fs::{ self, File }, path::Path };
+use std::{ collections::HashSet, path::{ Path, PathBuf } };
 
+use control::DownloadControl;
 use download_job::DownloadJob;
-use downloadables::{ DownloadError, HashError };
+use limiter::NetworkLimiter;
+use downloadables::{ AtomicFile, DownloadError, HashError };
 use log::warn;
 use md5::Digest;
 use progress::ProgressReporter;
+use retry::RetryPolicy;
 use reqwest::Client;
 use sha1::Sha1;
+use tokio::fs;
 use utils::{ get_jar_downloadable, get_library_downloadables, get_asset_downloadables };
 
-use crate::json::{ manifest::{ assets::AssetIndex, VersionManifest }, Sha1Sum };
+use crate::{ json::{ manifest::{ assets::AssetIndex, rule::TargetPlatform, VersionManifest }, Sha1Sum }, version_manager::{ endpoints::{ Endpoints, Service }, object_store::ObjectStore } };
 
 use super::VersionManager;
 
 pub mod progress;
+pub mod control;
+pub mod retry;
+pub mod limiter;
 pub mod download_job;
 pub mod downloadables;
 pub mod utils;
@@ -23,7 +30,19 @@ pub struct ClientDownloader {
   pub client: Client,
   pub parallel_downloads: Option<usize>,
   pub retries: Option<usize>,
+  /// Overridden by `retries`, if set
+  pub retry_policy: RetryPolicy,
   pub reporter: ProgressReporter,
+  /// The platform the libraries are downloaded for
+  pub platform: TargetPlatform,
+  /// Shared by every job this downloader creates
+  pub control: DownloadControl,
+  /// Bandwidth and per-host connection limits. Defaults to `NetworkLimiter::global()`
+  pub limiter: NetworkLimiter,
+  /// Mirrors tried before the official servers
+  pub endpoints: Endpoints,
+  /// Shared store for libraries, jars and assets. Instances are materialized from it with hardlinks
+  pub object_store: Option<ObjectStore>,
 }
 
 impl ClientDownloader {
@@ -33,42 +52,78 @@ impl ClientDownloader {
       reporter,
       parallel_downloads,
       retries,
+      retry_policy: RetryPolicy::default(),
+      platform: TargetPlatform::current().clone(),
+      control: DownloadControl::new(),
+      limiter: NetworkLimiter::global(),
+      endpoints: Endpoints::default(),
+      object_store: None,
     }
   }
 
+  pub fn with_platform(mut self, platform: TargetPlatform) -> Self {
+    self.platform = platform;
+    self
+  }
+
+  pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+    self.retry_policy = retry_policy;
+    self
+  }
+
+  pub fn with_control(mut self, control: DownloadControl) -> Self {
+    self.control = control;
+    self
+  }
+
+  pub fn with_limiter(mut self, limiter: NetworkLimiter) -> Self {
+    self.limiter = limiter;
+    self
+  }
+
+  pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+    self.endpoints = endpoints;
+    self
+  }
+
+  pub fn with_object_store(mut self, object_store: Option<ObjectStore>) -> Self {
+    self.object_store = object_store;
+    self
+  }
+
   async fn get_asset_index(&self, local_version: &VersionManifest, game_dir: &Path) -> Result<AssetIndex, DownloadError> {
     let index_info = local_version.asset_index.as_ref().ok_or(DownloadError::Other("Asset index not found in version manifest!".into()))?;
 
-    let indexes_dir = game_dir.join("assets").join("indexes");
-    let index_file = indexes_dir.join(format!("{}.json", index_info.id));
+    let index_file = get_asset_index_path(game_dir, &index_info.id);
+    let indexes_dir = index_file.parent().unwrap_or(game_dir);
 
-    if let Ok(mut file) = File::open(&index_file) {
-      let sha1 = Sha1Sum::from_reader(&mut file).map_err(HashError::ChecksumFile)?;
-      if sha1 != index_info.sha1 {
-        warn!("Asset index file is invalid, redownloading");
-        fs::remove_file(&index_file).map_err(DownloadError::RemoveFile)?;
+    if let Ok(bytes) = fs::read(&index_file).await {
+      let sha1 = Sha1Sum::from_reader(&mut bytes.as_slice()).map_err(HashError::ChecksumFile)?;
+      if sha1 == index_info.sha1 {
+        return serde_json::from_slice(&bytes).map_err(|err| DownloadError::Other(Box::new(err)));
       }
+      // Kept until the replacement is verified, committing it overwrites the file
+      warn!("Asset index file is invalid, redownloading");
     }
 
-    if let Ok(file) = File::open(&index_file) {
-      Ok(serde_json::from_reader(file).map_err(|err| DownloadError::Other(Box::new(err)))?)
-    } else {
-      let response = self.client.get(&index_info.url).send().await?.error_for_status()?;
-      let bytes = response.bytes().await?;
-      fs::create_dir_all(&indexes_dir).map_err(DownloadError::WriteFile)?;
-      fs::write(&index_file, &bytes).map_err(DownloadError::WriteFile)?;
-
-      let mut sha1 = Sha1::new();
-      sha1.update(&bytes);
-      let actual = Sha1Sum::from(sha1);
-
-      if actual != index_info.sha1 {
-        let _ = fs::remove_file(&index_file);
-        return Err(DownloadError::ChecksumMismatch { expected: index_info.sha1.as_slice().to_vec(), actual: actual.into() });
-      } else {
-        Ok(serde_json::from_slice(&bytes).map_err(|err| DownloadError::Other(Box::new(err)))?)
-      }
+    let connection = self.limiter.acquire_connection(&index_info.url).await;
+    let response = self.endpoints.get(&self.client, Service::VersionMeta, &index_info.url).await?.error_for_status()?;
+    let bytes = response.bytes().await?;
+    drop(connection);
+    self.limiter.consume(bytes.len()).await;
+
+    let mut sha1 = Sha1::new();
+    sha1.update(&bytes);
+    let actual = Sha1Sum::from(sha1);
+    if actual != index_info.sha1 {
+      return Err(DownloadError::ChecksumMismatch { expected: index_info.sha1.as_slice().to_vec(), actual: actual.into() });
     }
+
+    fs::create_dir_all(&indexes_dir).await.map_err(DownloadError::WriteFile)?;
+    let mut file = AtomicFile::create(&index_file).await.map_err(DownloadError::WriteFile)?;
+    file.write_all(&bytes).await.map_err(DownloadError::WriteFile)?;
+    file.commit().await.map_err(DownloadError::WriteFile)?;
+    serde_json::from_slice(&bytes).map_err(|err| DownloadError::Other(Box::new(err)))
   }
 
   /// Downloads the specified version of the game along with its libraries and resources.
@@ -87,14 +142,47 @@ impl ClientDownloader {
   /// This function will return an error if any part of the download process fails.
 
   pub async fn download_version(&self, local_version: &VersionManifest, version_manager: &VersionManager) -> Result<(), error::Error> {
+    self.download_version_files(local_version, version_manager, None).await
+  }
+
+  /// Like `download_version`, but only downloads the libraries, jar and assets at the paths in `files`.
+  /// If the asset index is one of them, every asset is checked, since the old index can't be trusted
+  pub async fn download_files(
+    &self,
+    local_version: &VersionManifest,
+    version_manager: &VersionManager,
+    files: &HashSet<PathBuf>
+  ) -> Result<(), error::Error> {
+    self.download_version_files(local_version, version_manager, Some(files)).await
+  }
+
+  async fn download_version_files(
+    &self,
+    local_version: &VersionManifest,
+    version_manager: &VersionManager,
+    only: Option<&HashSet<PathBuf>>
+  ) -> Result<(), error::Error> {
     let VersionManager { game_dir, env_features, .. } = version_manager;
     let asset_index = self.get_asset_index(local_version, game_dir).await?;
 
-    let mut libs = get_library_downloadables(game_dir, local_version, env_features, None);
+    let mut libs = get_library_downloadables(game_dir, local_version, env_features, Some(&self.platform));
     libs.push(get_jar_downloadable(game_dir, local_version));
+    let mut assets = get_asset_downloadables(game_dir, &asset_index);
+    if let Some(only) = only {
+      libs.retain(|downloadable| only.contains(downloadable.get_target_file()));
+      let index_file = local_version.asset_index.as_ref().map(|index| get_asset_index_path(game_dir, &index.id));
+      if !index_file.is_some_and(|index_file| only.contains(&index_file)) {
+        assets.retain(|downloadable| only.contains(downloadable.get_target_file()));
+      }
+    }
 
-    let version_job = self.create_download_job("Version & Libraries").add_downloadables(libs);
-    let assets_job = self.create_download_job("Resources").add_downloadables(get_asset_downloadables(game_dir, &asset_index));
+    let mut version_job = self.create_download_job("Version & Libraries").add_downloadables(libs);
+    let mut assets_job = self.create_download_job("Resources").add_downloadables(assets);
+    if let Some(object_store) = &self.object_store {
+      let object_store = object_store.for_instance(game_dir);
+      version_job = version_job.with_object_store(&object_store);
+      assets_job = assets_job.with_object_store(&object_store);
+    }
 
     // Download one at a time
     version_job.start().await?;
@@ -103,7 +191,7 @@ impl ClientDownloader {
   }
 
   pub fn create_download_job(&self, name: &str) -> DownloadJob {
-    let mut job = DownloadJob::new(name).with_client(self.client.clone()).ignore_failures(false).with_progress_reporter(&self.reporter);
+    let mut job = DownloadJob::new(name).with_client(self.client.clone()).ignore_failures(false).with_progress_reporter(&self.reporter).with_control(&self.control).with_retry_policy(self.retry_policy.clone()).with_limiter(&self.limiter).with_endpoints(&self.endpoints);
     if let Some(parallel_downloads) = self.parallel_downloads {
       job = job.with_parallel_downloads(parallel_downloads);
     }
@@ -114,3 +202,7 @@ impl ClientDownloader {
     job
   }
 }
+
+pub fn get_asset_index_path(game_dir: &Path, id: &str) -> PathBuf {
+  game_dir.join("assets").join("indexes").join(format!("{}.json", id))
+}
@@ -0,0 +1,171 @@
+use std::{ collections::hash_map::RandomState, hash::{ BuildHasher, Hasher }, time::Duration };
+
+use chrono::{ DateTime, Utc };
+use reqwest::{ header::{ HeaderMap, RETRY_AFTER }, StatusCode };
+
+/// Why a download attempt failed, which decides if and when it's retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+  /// Timeouts, connection errors, 5xx responses...
+  Transient,
+  /// 429 and 503 responses, with the delay the server asked for in `Retry-After`
+  RateLimited(Option<Duration>),
+  /// Other 4xx responses, or local errors. Retrying won't help
+  Permanent,
+  /// The file was downloaded, but its checksum didn't match
+  Checksum,
+}
+
+impl FailureKind {
+  pub fn from_status(status: StatusCode, headers: Option<&HeaderMap>) -> Self {
+    match status {
+      StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+        let retry_after = headers
+          .and_then(|headers| headers.get(RETRY_AFTER))
+          .and_then(|value| value.to_str().ok())
+          .and_then(parse_retry_after);
+        Self::RateLimited(retry_after)
+      }
+      StatusCode::REQUEST_TIMEOUT => Self::Transient,
+      status if status.is_client_error() => Self::Permanent,
+      _ => Self::Transient,
+    }
+  }
+
+  pub fn from_reqwest(err: &reqwest::Error) -> Self {
+    match err.status() {
+      Some(status) => Self::from_status(status, None),
+      // Timeouts, connection resets, truncated bodies...
+      None if err.is_builder() => Self::Permanent,
+      None => Self::Transient,
+    }
+  }
+}
+
+/// Parses a `Retry-After` header, either in seconds or as an HTTP date
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+  let value = value.trim();
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+  let date = DateTime::parse_from_rfc2822(value).ok()?;
+  let delay = date.with_timezone(&Utc).signed_duration_since(Utc::now());
+  Some(delay.to_std().unwrap_or_default())
+}
+
+/// Decides how many times, and after how long, a failed download is retried.
+///
+/// Transient failures are retried with an exponential backoff (plus jitter), rate limited ones
+/// after the delay the server asked for, and permanent ones are never retried.
+/// Checksum failures have their own attempt count, since they usually come from a bad mirror or cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+  /// Total attempts, including the first one
+  pub max_attempts: usize,
+  /// Attempts allowed for checksum failures
+  pub checksum_attempts: usize,
+  /// Delay before the first retry, doubled on each attempt
+  pub base_delay: Duration,
+  pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_attempts: 5,
+      checksum_attempts: 2,
+      base_delay: Duration::from_millis(500),
+      max_delay: Duration::from_secs(30),
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// Never retries
+  pub fn none() -> Self {
+    Self { max_attempts: 1, checksum_attempts: 1, ..Self::default() }
+  }
+
+  pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+    self.max_attempts = max_attempts;
+    self
+  }
+
+  pub fn with_checksum_attempts(mut self, checksum_attempts: usize) -> Self {
+    self.checksum_attempts = checksum_attempts;
+    self
+  }
+
+  pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+    self.base_delay = base_delay;
+    self
+  }
+
+  pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+    self.max_delay = max_delay;
+    self
+  }
+
+  /// Returns how long to wait before retrying, after `attempt` (starting at 1) failed with `kind`.
+  /// Returns `None` if the download should be given up.
+  pub fn next_delay(&self, attempt: usize, kind: FailureKind) -> Option<Duration> {
+    match kind {
+      FailureKind::Permanent => None,
+      FailureKind::Checksum if attempt >= self.checksum_attempts.min(self.max_attempts) => None,
+      _ if attempt >= self.max_attempts => None,
+      FailureKind::RateLimited(Some(retry_after)) => Some(retry_after.min(self.max_delay)),
+      _ => Some(self.backoff(attempt)),
+    }
+  }
+
+  /// Exponential backoff, with a random delay between half and the full value
+  fn backoff(&self, attempt: usize) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16) as u32;
+    let delay = self.base_delay.saturating_mul(1 << exponent).min(self.max_delay);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(attempt);
+    let jitter = (hasher.finish() % 1000) as u32;
+    delay / 2 + (delay / 2) * jitter / 1000
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use reqwest::header::HeaderValue;
+
+  use crate::version_manager::downloader::downloadables::{ CompressedAssetError, DownloadError };
+
+  use super::*;
+
+  #[test]
+  fn test_classification() {
+    assert_eq!(FailureKind::from_status(StatusCode::NOT_FOUND, None), FailureKind::Permanent);
+    assert_eq!(FailureKind::from_status(StatusCode::BAD_GATEWAY, None), FailureKind::Transient);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+    assert_eq!(FailureKind::from_status(StatusCode::TOO_MANY_REQUESTS, Some(&headers)), FailureKind::RateLimited(Some(Duration::from_secs(7))));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+
+    // Corrupted compressed files get the checksum retries, not the transient backoff
+    let err = DownloadError::from(CompressedAssetError::ChecksumMismatch { expected: vec![1], actual: vec![2] });
+    assert_eq!(err.failure_kind(), FailureKind::Checksum);
+  }
+
+  #[test]
+  fn test_next_delay() {
+    let policy = RetryPolicy::default().with_max_attempts(4).with_base_delay(Duration::from_secs(1)).with_max_delay(Duration::from_secs(3));
+
+    assert_eq!(policy.next_delay(1, FailureKind::Permanent), None);
+    assert_eq!(policy.next_delay(4, FailureKind::Transient), None);
+    assert_eq!(policy.next_delay(2, FailureKind::Checksum), None);
+    assert!(policy.next_delay(1, FailureKind::Checksum).is_some());
+    assert_eq!(policy.next_delay(1, FailureKind::RateLimited(Some(Duration::from_secs(60)))), Some(Duration::from_secs(3)));
+
+    let first = policy.next_delay(1, FailureKind::Transient).unwrap();
+    assert!(first >= Duration::from_millis(500) && first <= Duration::from_secs(1));
+    let third = policy.next_delay(3, FailureKind::Transient).unwrap();
+    assert!(third >= Duration::from_millis(1500) && third <= Duration::from_secs(3));
+  }
+}
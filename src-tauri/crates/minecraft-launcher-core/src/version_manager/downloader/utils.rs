@@ -3,9 +3,12 @@ use std::{ boxed::Box, path::Path, str::FromStr, vec::Vec };
 use reqwest::Url;
 
 use crate::{
-  version_manager::downloader::downloadables::{ AssetDownloadable, ChecksummedDownloadable, Downloadable, EtagDownloadable, PreHashedDownloadable },
+  version_manager::{
+    downloader::downloadables::{ AssetDownloadable, ChecksummedDownloadable, Downloadable, EtagDownloadable, PreHashedDownloadable },
+    endpoints::{ LEGACY_JARS_URL, LIBRARIES_URL, RESOURCES_URL },
+  },
   json::{
-    manifest::{ assets::AssetIndex, download::{ DownloadInfo, DownloadType }, library::Library, rule::OperatingSystem, VersionManifest },
+    manifest::{ assets::AssetIndex, download::{ DownloadInfo, DownloadType }, library::Library, rule::TargetPlatform, VersionManifest },
     EnvironmentFeatures,
     VersionInfo,
   },
@@ -18,7 +21,7 @@ pub fn get_jar_downloadable(game_dir: &Path, local_version: &VersionManifest) ->
   if let Some(DownloadInfo { sha1, url, .. }) = local_version.get_download_url(DownloadType::Client) {
     Box::new(PreHashedDownloadable::new(url, &jar_path, sha1.clone()))
   } else {
-    let url = format!("https://s3.amazonaws.com/Minecraft.Download/versions/{}/{}.jar", &version_id, &version_id);
+    let url = format!("{}versions/{}/{}.jar", LEGACY_JARS_URL, &version_id, &version_id);
     Box::new(EtagDownloadable::new(&url, &jar_path, false))
   }
 }
@@ -27,13 +30,13 @@ pub fn get_library_downloadables(
   game_dir: &Path,
   local_version: &VersionManifest,
   env_features: &EnvironmentFeatures,
-  os: Option<OperatingSystem>
+  platform: Option<&TargetPlatform>
 ) -> Vec<Box<dyn Downloadable + Send + Sync>> {
-  let os = os.unwrap_or(OperatingSystem::get_current_platform());
+  let platform = platform.unwrap_or(TargetPlatform::current());
   local_version
-    .get_relevant_libraries(env_features)
+    .get_relevant_libraries_for_platform(platform, env_features)
     .into_iter()
-    .flat_map(|lib| create_lib_downloadable(lib, game_dir, &os))
+    .flat_map(|lib| create_lib_downloadable(lib, game_dir, platform))
     .collect()
 }
 
@@ -42,7 +45,7 @@ pub fn get_asset_downloadables(game_dir: &Path, asset_index: &AssetIndex) -> Vec
   let objects_dir = assets_dir.join("objects");
 
   // Turn each resource object into a downloadable
-  let url_base = Url::from_str("https://resources.download.minecraft.net/").unwrap();
+  let url_base = Url::from_str(RESOURCES_URL).unwrap();
   let mut downloadables: Vec<Box<dyn Downloadable + Send + Sync>> = vec![];
   for (asset_object, asset_name) in asset_index.get_unique_objects() {
     downloadables.push(Box::new(AssetDownloadable::new(asset_name, asset_object, &url_base, &objects_dir)));
@@ -50,9 +53,9 @@ pub fn get_asset_downloadables(game_dir: &Path, asset_index: &AssetIndex) -> Vec
   downloadables
 }
 
-pub fn create_lib_downloadable(lib: &Library, game_dir: &Path, os: &OperatingSystem) -> Option<Box<dyn Downloadable + Send + Sync>> {
+pub fn create_lib_downloadable(lib: &Library, game_dir: &Path, platform: &TargetPlatform) -> Option<Box<dyn Downloadable + Send + Sync>> {
   // If the lib has a natives field, but the os is not supported, return None immediately
-  let classifier = lib.get_artifact_classifier(os)?;
+  let classifier = lib.get_artifact_classifier(platform)?;
 
   let libraries_dir = game_dir.join("libraries");
   let artifact_path = lib.get_artifact_path(classifier);
@@ -60,21 +63,25 @@ pub fn create_lib_downloadable(lib: &Library, game_dir: &Path, os: &OperatingSys
 
   // If the lib has a single url
   if let Some(url) = &lib.url {
+    // Join instead of replacing the path, so repositories hosted under a sub path (like `/maven2/`) work
     let mut url = Url::parse(url).ok()?;
-    url.set_path(&artifact_path);
+    if !url.path().ends_with('/') {
+      url.set_path(&format!("{}/", url.path()));
+    }
+    let url = url.join(&artifact_path).ok()?;
     let downloadable = ChecksummedDownloadable::new(url.as_str(), &file_path);
     return Some(Box::new(downloadable));
   }
 
   // If the lib has no url, try the default download server
   if lib.downloads.is_none() {
-    let url = format!("https://libraries.minecraft.net/{}", &artifact_path);
+    let url = format!("{}{}", LIBRARIES_URL, &artifact_path);
     return Some(Box::new(ChecksummedDownloadable::new(&url, &file_path)));
   }
 
   // If the lib has multiple urls (like for each OS)
   // We obtain the download info for the OS
-  if let Some(DownloadInfo { url, sha1, .. }) = lib.get_download_info(os) {
+  if let Some(DownloadInfo { url, sha1, .. }) = lib.get_download_info(platform) {
     let downloadable = PreHashedDownloadable::new(&url, &file_path, sha1);
     Some(Box::new(downloadable))
   } else {
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use log::warn;
+use reqwest::{ Client, RequestBuilder, Response, StatusCode };
+
+pub const VERSION_MANIFEST_URL: &str = "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+pub const JRE_INDEX_URL: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+pub const LIBRARIES_URL: &str = "https://libraries.minecraft.net/";
+pub const RESOURCES_URL: &str = "https://resources.download.minecraft.net/";
+/// Where jars of versions without a `downloads` section are found
+pub const LEGACY_JARS_URL: &str = "https://s3.amazonaws.com/Minecraft.Download/";
+
+/// The official servers, and the service each of them belongs to.
+/// A mirror replaces the matching prefix and keeps the rest of the URL.
+const OFFICIAL_ORIGINS: &[(&str, Service)] = &[
+  ("https://piston-meta.mojang.com/", Service::VersionMeta),
+  ("https://piston-data.mojang.com/", Service::VersionMeta),
+  ("https://launchermeta.mojang.com/", Service::VersionMeta),
+  ("https://launcher.mojang.com/", Service::VersionMeta),
+  (LEGACY_JARS_URL, Service::VersionMeta),
+  (LIBRARIES_URL, Service::Libraries),
+  (RESOURCES_URL, Service::Assets),
+];
+
+/// A group of official servers that can be replaced by mirrors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Service {
+  /// Version manifests, version JSONs, client jars and asset indexes
+  VersionMeta,
+  Libraries,
+  /// Asset objects
+  Assets,
+  /// The Java runtime index, manifests and files. They are served from the same hosts as
+  /// `VersionMeta`, so the caller has to ask for this service explicitly
+  JavaRuntime,
+}
+
+impl Service {
+  /// The service an official URL belongs to, if any
+  pub fn from_url(url: &str) -> Option<Self> {
+    OFFICIAL_ORIGINS.iter().find(|(origin, _)| url.starts_with(origin)).map(|(_, service)| *service)
+  }
+}
+
+/// Mirrors to use instead of the official servers, in order of preference.
+///
+/// URLs that don't point to an official server (like a library with its own repository)
+/// are never rewritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+  mirrors: HashMap<Service, Vec<String>>,
+  fallback_to_official: bool,
+}
+
+impl Default for Endpoints {
+  fn default() -> Self {
+    Self { mirrors: HashMap::new(), fallback_to_official: true }
+  }
+}
+
+impl Endpoints {
+  /// Only uses the official servers
+  pub fn official() -> Self {
+    Self::default()
+  }
+
+  /// Adds a mirror for `service`, tried after the ones added before it.
+  /// `base_url` replaces the official server, e.g. `https://mirror.example/maven/` for the libraries.
+  pub fn with_mirror(mut self, service: Service, base_url: &str) -> Self {
+    let mut base_url = base_url.to_string();
+    if !base_url.ends_with('/') {
+      base_url.push('/');
+    }
+    self.mirrors.entry(service).or_default().push(base_url);
+    self
+  }
+
+  /// Uses `base_url` for every service
+  pub fn with_mirror_for_all(self, base_url: &str) -> Self {
+    [Service::VersionMeta, Service::Libraries, Service::Assets, Service::JavaRuntime]
+      .into_iter()
+      .fold(self, |endpoints, service| endpoints.with_mirror(service, base_url))
+  }
+
+  /// Whether the official servers are tried after every mirror failed. Enabled by default
+  pub fn with_official_fallback(mut self, fallback_to_official: bool) -> Self {
+    self.fallback_to_official = fallback_to_official;
+    self
+  }
+
+  pub fn get_mirrors(&self, service: Service) -> &[String] {
+    self.mirrors.get(&service).map(Vec::as_slice).unwrap_or_default()
+  }
+
+  /// The URLs to try for `url`, in order. Never empty
+  pub fn resolve(&self, service: Service, url: &str) -> Vec<String> {
+    let path = OFFICIAL_ORIGINS.iter().find_map(|(origin, _)| url.strip_prefix(origin));
+    let mirrors = self.get_mirrors(service);
+    let Some(path) = path.filter(|_| !mirrors.is_empty()) else {
+      return vec![url.to_string()];
+    };
+
+    let mut urls: Vec<_> = mirrors
+      .iter()
+      .map(|mirror| format!("{}{}", mirror, path))
+      .collect();
+    if self.fallback_to_official {
+      urls.push(url.to_string());
+    }
+    urls
+  }
+
+  /// Like `resolve`, guessing the service from the URL
+  pub fn resolve_url(&self, url: &str) -> Vec<String> {
+    match Service::from_url(url) {
+      Some(service) => self.resolve(service, url),
+      None => vec![url.to_string()],
+    }
+  }
+
+  /// Sends a GET request to each URL `resolve` returns until one responds successfully
+  /// (or with `304 Not Modified`, for conditional requests).
+  ///
+  /// If all of them fail, the last response or error is returned, so callers can check
+  /// the status like with a normal request.
+  pub async fn get(&self, client: &Client, service: Service, url: &str) -> reqwest::Result<Response> {
+    self.send_with(service, url, |url| client.get(url)).await
+  }
+
+  /// Like `get`, guessing the service from the URL
+  pub async fn get_url(&self, client: &Client, url: &str) -> reqwest::Result<Response> {
+    Self::send_all(self.resolve_url(url), |url| client.get(url)).await
+  }
+
+  /// Like `get`, with a request built by `request` for each URL
+  pub async fn send_with<F>(&self, service: Service, url: &str, request: F) -> reqwest::Result<Response>
+    where F: Fn(&str) -> RequestBuilder
+  {
+    Self::send_all(self.resolve(service, url), request).await
+  }
+
+  async fn send_all<F>(mut urls: Vec<String>, request: F) -> reqwest::Result<Response> where F: Fn(&str) -> RequestBuilder {
+    let last = urls.pop().unwrap_or_default();
+    for url in urls {
+      match request(&url).send().await {
+        Ok(response) if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED => {
+          return Ok(response);
+        }
+        Ok(response) => warn!("Mirror {} responded with {}, trying the next one", url, response.status()),
+        Err(err) => warn!("Couldn't reach mirror {}: {}, trying the next one", url, err),
+      }
+    }
+    request(&last).send().await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_resolve() {
+    let endpoints = Endpoints::official()
+      .with_mirror(Service::Libraries, "https://mirror.example/maven")
+      .with_mirror(Service::Libraries, "https://other.example/maven/");
+
+    assert_eq!(endpoints.resolve_url("https://libraries.minecraft.net/a/b.jar"), vec![
+      "https://mirror.example/maven/a/b.jar",
+      "https://other.example/maven/a/b.jar",
+      "https://libraries.minecraft.net/a/b.jar"
+    ]);
+    // Other services and third party repositories aren't affected
+    assert_eq!(endpoints.resolve_url("https://resources.download.minecraft.net/ab/abcd"), vec!["https://resources.download.minecraft.net/ab/abcd"]);
+    assert_eq!(endpoints.resolve(Service::Libraries, "https://maven.fabricmc.net/a/b.jar"), vec!["https://maven.fabricmc.net/a/b.jar"]);
+
+    let endpoints = Endpoints::official().with_mirror_for_all("http://127.0.0.1:8080/").with_official_fallback(false);
+    assert_eq!(endpoints.resolve(Service::JavaRuntime, "https://piston-data.mojang.com/v1/objects/abc/java"), vec![
+      "http://127.0.0.1:8080/v1/objects/abc/java"
+    ]);
+  }
+}
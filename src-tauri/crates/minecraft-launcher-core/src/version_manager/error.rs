@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
 use crate::json::{ MCVersion, Sha1Sum };
@@ -13,6 +15,7 @@ pub enum LoadVersionError {
   #[error("manifest not found")]
   ManifestNotFound,
   #[error("failed to parse manifest: {0}")] ManifestParseError(#[from] serde_json::Error),
+  #[error(transparent)] MetadataError(#[from] MetadataError),
   #[error(transparent)] IoError(#[from] std::io::Error),
 }
 
@@ -26,6 +29,46 @@ pub enum InstallVersionError {
   },
   #[error("failed to parse: {0}")] ParseError(#[from] serde_json::Error),
   #[error("failed to read checksum: {0}")] ChecksumError(#[source] std::io::Error),
+  #[error(transparent)] MetadataError(#[from] MetadataError),
+  #[error(transparent)] IoError(#[from] std::io::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum MetadataError {
+  #[error("{url} needs the network, it isn't cached and offline mode is enabled")] Offline {
+    url: String,
+  },
+  #[error("failed to fetch: {0}")] FetchError(#[from] reqwest::Error),
+  #[error("checksum mismatch for {url}, expected {expected}, got {actual}")] ChecksumMismatch {
+    url: String,
+    expected: Sha1Sum,
+    actual: Sha1Sum,
+  },
+  #[error("failed to parse: {0}")] ParseError(#[from] serde_json::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum UninstallVersionError {
+  #[error("version not found: {0}")] VersionNotFound(String),
+  #[error("{version} can't be removed, other versions inherit from it: {}", dependents.join(", "))] HasDependents {
+    version: String,
+    dependents: Vec<String>,
+  },
+  #[error(transparent)] LoadVersionError(#[from] LoadVersionError),
+  #[error(transparent)] IoError(#[from] std::io::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum AssetGcError {
+  #[error("failed to read asset index {id}, nothing was removed: {source}")] ReadIndex {
+    id: String,
+    #[source] source: std::io::Error,
+  },
+  #[error("failed to parse asset index {id}, nothing was removed: {source}")] ParseIndex {
+    id: String,
+    #[source] source: serde_json::Error,
+  },
+  #[error(transparent)] LoadVersionError(#[from] LoadVersionError),
   #[error(transparent)] IoError(#[from] std::io::Error),
 }
 
@@ -37,3 +80,18 @@ pub enum ResolveManifestError {
   },
   #[error(transparent)] InstallVersionError(#[from] InstallVersionError),
 }
+
+#[derive(Error, Debug)]
+pub enum CustomVersionError {
+  #[error("no version JSON found in {0:?}")] ManifestNotFound(PathBuf),
+  #[error("{0:?} has more than one version JSON, pass the files instead")] AmbiguousManifest(PathBuf),
+  #[error("invalid version JSON: {0}")] InvalidManifest(#[from] serde_json::Error),
+  #[error("invalid version id {0:?}")] InvalidId(String),
+  #[error("{0} doesn't inherit from another version, a client jar is required")] MissingJar(String),
+  #[error("{version} inherits from {inherits_from}, which isn't installed or in the version list")] ParentNotFound {
+    version: String,
+    inherits_from: String,
+  },
+  #[error("{0} is already installed and isn't a custom version")] AlreadyInstalled(String),
+  #[error(transparent)] IoError(#[from] std::io::Error),
+}
@@ -0,0 +1,235 @@
+use std::path::{ Path, PathBuf };
+
+use log::{ info, warn };
+use reqwest::{ header::{ ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED }, Client, StatusCode };
+use serde::{ de::DeserializeOwned, Deserialize, Serialize };
+use tokio::fs;
+
+use crate::json::Sha1Sum;
+
+use super::{ downloader::downloadables::AtomicFile, endpoints::{ Endpoints, Service }, error::MetadataError };
+
+/// Keeps a copy of the metadata fetched from Mojang (version list, version JSONs, the Java
+/// runtime index and runtime manifests), revalidated with `ETag`/`Last-Modified`.
+///
+/// The cached copy is used when the server can't be reached, and it's the only source in
+/// offline mode. Game files aren't stored here.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataCache {
+  dir: Option<PathBuf>,
+  offline: bool,
+}
+
+/// What's stored next to each cached file
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheEntry {
+  url: String,
+  etag: Option<String>,
+  last_modified: Option<String>,
+}
+
+impl MetadataCache {
+  pub fn new(dir: &Path) -> Self {
+    Self { dir: Some(dir.to_path_buf()), offline: false }
+  }
+
+  /// Nothing is stored, every request goes to the network
+  pub fn disabled() -> Self {
+    Self::default()
+  }
+
+  /// In offline mode nothing is requested, and metadata that isn't cached fails with `MetadataError::Offline`
+  pub fn with_offline(mut self, offline: bool) -> Self {
+    self.offline = offline;
+    self
+  }
+
+  pub fn set_offline(&mut self, offline: bool) {
+    self.offline = offline;
+  }
+
+  pub fn is_offline(&self) -> bool {
+    self.offline
+  }
+
+  pub fn get_dir(&self) -> Option<&Path> {
+    self.dir.as_deref()
+  }
+
+  /// Fetches `url`, trying the mirrors of `service` first.
+  ///
+  /// If `expected` is given, a cached copy with that hash is returned without asking the server,
+  /// and a download that doesn't match it is rejected and not stored.
+  pub async fn fetch(
+    &self,
+    client: &Client,
+    endpoints: &Endpoints,
+    service: Service,
+    url: &str,
+    expected: Option<&Sha1Sum>
+  ) -> Result<Vec<u8>, MetadataError> {
+    let cached = self.read(url).await;
+    if let (Some((bytes, _)), Some(expected)) = (&cached, expected) {
+      if &sha1(bytes) == expected {
+        return Ok(bytes.clone());
+      }
+    }
+
+    if self.offline {
+      return match cached {
+        Some((bytes, _)) if expected.is_none() => Ok(bytes),
+        _ => Err(MetadataError::Offline { url: url.to_string() }),
+      };
+    }
+
+    // Only revalidate copies that could still be valid
+    let validators = cached
+      .as_ref()
+      .filter(|_| expected.is_none())
+      .map(|(_, entry)| entry);
+    let response = endpoints.send_with(service, url, |url| {
+      let mut request = client.get(url);
+      if let Some(etag) = validators.and_then(|entry| entry.etag.as_deref()) {
+        request = request.header(IF_NONE_MATCH, etag);
+      }
+      if let Some(last_modified) = validators.and_then(|entry| entry.last_modified.as_deref()) {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+      }
+      request
+    }).await;
+
+    let response = match response.and_then(|response| response.error_for_status()) {
+      Ok(response) => response,
+      Err(err) => {
+        return match cached {
+          Some((bytes, _)) if expected.is_none() => {
+            warn!("Couldn't fetch {} ({}), using the cached copy", url, err);
+            Ok(bytes)
+          }
+          _ => Err(err.into()),
+        };
+      }
+    };
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+      if let Some((bytes, _)) = cached {
+        info!("{} wasn't modified, using the cached copy", url);
+        return Ok(bytes);
+      }
+    }
+
+    let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    let entry = CacheEntry { url: url.to_string(), etag: header(ETAG), last_modified: header(LAST_MODIFIED) };
+    let bytes = response.bytes().await?.to_vec();
+
+    if let Some(expected) = expected {
+      let actual = sha1(&bytes);
+      if &actual != expected {
+        return Err(MetadataError::ChecksumMismatch { url: url.to_string(), expected: expected.clone(), actual });
+      }
+    }
+
+    if let Err(err) = self.write(&entry, &bytes).await {
+      warn!("Couldn't cache {}: {}", url, err);
+    }
+    Ok(bytes)
+  }
+
+  /// Like `fetch`, parsing the response as JSON
+  pub async fn fetch_json<T: DeserializeOwned>(
+    &self,
+    client: &Client,
+    endpoints: &Endpoints,
+    service: Service,
+    url: &str,
+    expected: Option<&Sha1Sum>
+  ) -> Result<T, MetadataError> {
+    let bytes = self.fetch(client, endpoints, service, url, expected).await?;
+    Ok(serde_json::from_slice(&bytes)?)
+  }
+
+  /// `<dir>/<hash of the url>-<file name>`, so different URLs with the same file name don't clash
+  fn get_path(&self, url: &str) -> Option<PathBuf> {
+    let dir = self.dir.as_ref()?;
+    let name = url.rsplit('/').next().unwrap_or_default();
+    let hash = sha1(url.as_bytes()).to_string();
+    Some(dir.join(format!("{}-{}", &hash[..16], name)))
+  }
+
+  async fn read(&self, url: &str) -> Option<(Vec<u8>, CacheEntry)> {
+    let path = self.get_path(url)?;
+    let bytes = fs::read(&path).await.ok()?;
+    // A copy without validators is still usable, it's just always downloaded again when online
+    let entry = fs
+      ::read(entry_path(&path)).await
+      .ok()
+      .and_then(|entry| serde_json::from_slice(&entry).ok())
+      .unwrap_or_default();
+    Some((bytes, entry))
+  }
+
+  async fn write(&self, entry: &CacheEntry, bytes: &[u8]) -> std::io::Result<()> {
+    let Some(path) = self.get_path(&entry.url) else {
+      return Ok(());
+    };
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).await?;
+    }
+
+    let mut file = AtomicFile::create(&path).await?;
+    file.write_all(bytes).await?;
+    file.commit().await?;
+
+    let mut file = AtomicFile::create(&entry_path(&path)).await?;
+    file.write_all(&serde_json::to_vec(entry)?).await?;
+    file.commit().await
+  }
+}
+
+fn entry_path(path: &Path) -> PathBuf {
+  let mut name = path.file_name().unwrap_or_default().to_os_string();
+  name.push(".meta");
+  path.with_file_name(name)
+}
+
+fn sha1(bytes: &[u8]) -> Sha1Sum {
+  // Reading from a slice can't fail
+  Sha1Sum::from_reader(&mut &bytes[..]).unwrap_or_else(|_| Sha1Sum::null())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env::temp_dir;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_offline_uses_cache() {
+    let dir = temp_dir().join("minecraft-launcher-core-metadata-cache");
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = MetadataCache::new(&dir);
+    let url = "https://piston-meta.mojang.com/v1/packages/abc/1.20.json";
+
+    let entry = CacheEntry { url: url.to_string(), etag: Some("\"v1\"".to_string()), last_modified: None };
+    cache.write(&entry, b"{}").await.unwrap();
+    let (bytes, entry) = cache.read(url).await.unwrap();
+    assert_eq!(bytes, b"{}");
+    assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+
+    // Nothing is requested, the client has nowhere to connect to
+    let cache = cache.with_offline(true);
+    let client = Client::new();
+    let endpoints = Endpoints::official();
+    let bytes = cache.fetch(&client, &endpoints, Service::VersionMeta, url, None).await.unwrap();
+    assert_eq!(bytes, b"{}");
+    let bytes = cache.fetch(&client, &endpoints, Service::VersionMeta, url, Some(&sha1(b"{}"))).await.unwrap();
+    assert_eq!(bytes, b"{}");
+
+    let missing = "https://piston-meta.mojang.com/v1/packages/def/1.21.json";
+    let err = cache.fetch(&client, &endpoints, Service::VersionMeta, missing, None).await.unwrap_err();
+    assert!(matches!(err, MetadataError::Offline { url } if url == missing));
+    // A cached copy with another hash isn't used either
+    let err = cache.fetch(&client, &endpoints, Service::VersionMeta, url, Some(&Sha1Sum::null())).await.unwrap_err();
+    assert!(matches!(err, MetadataError::Offline { .. }));
+  }
+}
@@ -1,30 +1,82 @@
-use std::{ collections::{ HashMap, HashSet }, fs::{ self, create_dir_all, read_dir, File }, path::{ Path, PathBuf }, sync::Arc };
+use std::{
+  collections::{ HashMap, HashSet },
+  fs::{ self, create_dir_all, read_dir, File },
+  panic,
+  path::{ Path, PathBuf },
+  sync::{ Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard },
+};
 
-use downloader::{ download_job::DownloadJob, progress::ProgressReporter, ClientDownloader };
-use error::{ InstallVersionError, LoadVersionError, ResolveManifestError };
+use downloader::{ control::DownloadControl, downloadables::AtomicFile, progress::ProgressReporter, ClientDownloader };
+use endpoints::Endpoints;
+use asset_gc::{ AssetGcOptions, AssetGcReport };
+use catalog::{ CatalogEntry, VersionQuery, VersionRequirements };
+use custom::{ validate_id, write_custom_version, CustomFileMode, CustomVersion, CUSTOM_VERSION_MARKER };
+use error::{ AssetGcError, CustomVersionError, InstallVersionError, LoadVersionError, MetadataError, ResolveManifestError, UninstallVersionError };
 use log::{ error, info, warn };
+use metadata_cache::MetadataCache;
+use object_store::ObjectStore;
+use resolved_cache::ResolvedCache;
+use serde::Deserialize;
+use sha1::{ Digest, Sha1 };
+use uninstall::{ get_resolved_library_paths, remove_with_empty_parents, UninstallReport };
+use verify::{ get_expected_files, verify_files, VerifyReport };
 use remote::{ RawVersionList, RemoteVersionInfo };
 use reqwest::Client;
+use natives::NativeOverrides;
 use utils::resolve;
 
-use crate::json::{ manifest::{ rule::OperatingSystem, VersionManifest }, EnvironmentFeatures, MCVersion, VersionInfo };
+pub use utils::LibraryOverride;
+
+use crate::{
+  json::{ manifest::{ download::DownloadType, rule::TargetPlatform, VersionManifest }, Date, EnvironmentFeatures, MCVersion, ReleaseType, Sha1Sum, VersionInfo, VersionTimeline },
+  network::NetworkSettings,
+};
 
 pub mod downloader;
 pub mod remote;
 pub mod error;
+pub mod natives;
+pub mod endpoints;
+pub mod metadata_cache;
+pub mod object_store;
+pub mod verify;
+pub mod asset_gc;
+pub mod uninstall;
+pub mod catalog;
+pub mod custom;
+pub mod resolved_cache;
 
 mod utils;
 
+/// One async lock per version id
+type VersionLocks = Mutex<HashMap<MCVersion, Arc<tokio::sync::Mutex<()>>>>;
+
+/// Resolves, installs and downloads versions.
+///
+/// Everything takes `&self`, setters included, so it can be shared between tasks (e.g. in an `Arc`).
+/// Concurrent installs and resolves of the same version wait for each other and reuse the result
+/// instead of fetching and writing `versions/<id>/<id>.json` twice.
 #[derive(Debug)]
 pub struct VersionManager {
   pub game_dir: PathBuf,
   pub env_features: EnvironmentFeatures,
   pub client: Client,
 
-  local_cache: Vec<MCVersion>,
-  remote_cache: Option<RawVersionList>,
+  local_cache: RwLock<Vec<MCVersion>>,
+  remote_cache: RwLock<Option<RawVersionList>>,
 
-  resolved_versions_cache: HashMap<MCVersion, VersionManifest>,
+  resolved_versions_cache: RwLock<HashMap<MCVersion, VersionManifest>>,
+  library_overrides: RwLock<HashMap<MCVersion, Vec<LibraryOverride>>>,
+  install_locks: VersionLocks,
+  resolve_locks: VersionLocks,
+  native_overrides: RwLock<Option<NativeOverrides>>,
+  platform: RwLock<TargetPlatform>,
+  endpoints: RwLock<Endpoints>,
+  metadata_cache: RwLock<MetadataCache>,
+  resolved_cache: RwLock<ResolvedCache>,
+  object_store: RwLock<Option<ObjectStore>>,
+  download_control: RwLock<DownloadControl>,
+  timeline: RwLock<VersionTimeline>,
 }
 
 impl VersionManager {
@@ -34,18 +86,134 @@ impl VersionManager {
     Self {
       game_dir: game_dir.to_path_buf(),
       env_features: env_features.clone(),
-      client: client.unwrap_or(DownloadJob::create_http_client(None).unwrap_or_default()),
+      client: client.unwrap_or_else(|| NetworkSettings::default().create_client().unwrap_or_default()),
 
-      local_cache: vec![],
-      remote_cache: None,
-      resolved_versions_cache: HashMap::new(),
+      local_cache: RwLock::new(vec![]),
+      remote_cache: RwLock::new(None),
+      resolved_versions_cache: RwLock::new(HashMap::new()),
+      library_overrides: RwLock::new(HashMap::new()),
+      install_locks: Mutex::new(HashMap::new()),
+      resolve_locks: Mutex::new(HashMap::new()),
+      native_overrides: RwLock::new(Some(NativeOverrides::default())),
+      platform: RwLock::new(TargetPlatform::current().clone()),
+      endpoints: RwLock::new(Endpoints::default()),
+      metadata_cache: RwLock::new(MetadataCache::new(&game_dir.join("cache").join("metadata"))),
+      resolved_cache: RwLock::new(ResolvedCache::new(&game_dir.join("cache").join("resolved"))),
+      object_store: RwLock::new(None),
+      download_control: RwLock::new(DownloadControl::new()),
+      timeline: RwLock::new(VersionTimeline::new()),
     }
   }
 
+  /// Replaces the LWJGL native overrides applied to resolved versions, `None` disables them.
+  /// Only used on platforms Mojang doesn't ship natives for.
+  pub fn set_native_overrides(&self, native_overrides: Option<NativeOverrides>) {
+    *write(&self.native_overrides) = native_overrides;
+    write(&self.resolved_versions_cache).clear();
+  }
+
+  /// The platform versions are resolved, checked and downloaded for. Defaults to the current one
+  pub fn set_platform(&self, platform: TargetPlatform) {
+    *write(&self.platform) = platform;
+    write(&self.resolved_versions_cache).clear();
+  }
+
+  pub fn get_platform(&self) -> TargetPlatform {
+    read(&self.platform).clone()
+  }
+
+  /// Sets the mirrors used for the version list, version JSONs and every file downloaded by
+  /// `download_required_files`
+  pub fn set_endpoints(&self, endpoints: Endpoints) {
+    *write(&self.endpoints) = endpoints;
+  }
+
+  pub fn get_endpoints(&self) -> Endpoints {
+    read(&self.endpoints).clone()
+  }
+
+  /// Where the version list and version JSONs are cached. Defaults to `<game_dir>/cache/metadata`
+  pub fn set_metadata_cache(&self, metadata_cache: MetadataCache) {
+    *write(&self.metadata_cache) = metadata_cache;
+  }
+
+  pub fn get_metadata_cache(&self) -> MetadataCache {
+    read(&self.metadata_cache).clone()
+  }
+
+  /// Where resolved manifests are kept between runs. Defaults to `<game_dir>/cache/resolved`
+  pub fn set_resolved_cache(&self, resolved_cache: ResolvedCache) {
+    *write(&self.resolved_cache) = resolved_cache;
+  }
+
+  pub fn get_resolved_cache(&self) -> ResolvedCache {
+    read(&self.resolved_cache).clone()
+  }
+
+  /// Shares libraries, client jars and assets with other instances using the same store.
+  /// `download_required_files` links them into `game_dir` instead of downloading them again
+  pub fn set_object_store(&self, object_store: Option<ObjectStore>) {
+    *write(&self.object_store) = object_store;
+  }
+
+  pub fn get_object_store(&self) -> Option<ObjectStore> {
+    read(&self.object_store).clone()
+  }
+
+  /// Pauses, resumes or cancels the downloads started by `download_required_files`, `repair_version`
+  /// and the resolves that download files. A cancelled control can't be resumed, so set a new one
+  /// before downloading again
+  pub fn set_download_control(&self, download_control: DownloadControl) {
+    *write(&self.download_control) = download_control;
+  }
+
+  pub fn get_download_control(&self) -> DownloadControl {
+    read(&self.download_control).clone()
+  }
+
+  /// The release times of every remote and installed version seen by `refresh`,
+  /// `refresh_local_versions` and `install_custom_version`, to order snapshots and modded ids
+  /// against releases
+  pub fn get_timeline(&self) -> VersionTimeline {
+    read(&self.timeline).clone()
+  }
+
+  /// In offline mode nothing is requested and installed versions are launched as they are.
+  /// Only operations that need the network fail: refreshing without a cached version list
+  /// (the remote list is left empty), installing a version whose JSON isn't cached, and
+  /// downloading missing game files
+  pub fn set_offline(&self, offline: bool) {
+    write(&self.metadata_cache).set_offline(offline);
+  }
+
+  pub fn is_offline(&self) -> bool {
+    read(&self.metadata_cache).is_offline()
+  }
+
   /// Loads the version manager with the provided game directory and environment features.
   /// Creates the version manager and refreshes it
   pub async fn load(game_dir: &Path, env_features: &EnvironmentFeatures, client: Option<Client>) -> Result<Self, LoadVersionError> {
-    let mut version_manager = Self::new(game_dir, env_features, client);
+    let version_manager = Self::new(game_dir, env_features, client);
+    version_manager.refresh().await?;
+    Ok(version_manager)
+  }
+
+  /// Like `VersionManager::new`, with a client that follows `network_settings` (proxy, timeouts and certificates)
+  pub fn new_with_network_settings(
+    game_dir: &Path,
+    env_features: &EnvironmentFeatures,
+    network_settings: &NetworkSettings
+  ) -> reqwest::Result<Self> {
+    Ok(Self::new(game_dir, env_features, Some(network_settings.create_client()?)))
+  }
+
+  /// Like `VersionManager::load`, with a client that follows `network_settings`
+  pub async fn load_with_network_settings(
+    game_dir: &Path,
+    env_features: &EnvironmentFeatures,
+    network_settings: &NetworkSettings
+  ) -> Result<Self, LoadVersionError> {
+    let version_manager = Self::new_with_network_settings(game_dir, env_features, network_settings)?;
     version_manager.refresh().await?;
     Ok(version_manager)
   }
@@ -54,23 +222,99 @@ impl VersionManager {
     self.game_dir.join("versions")
   }
 
-  pub fn installed_versions(&self) -> Vec<&MCVersion> {
-    self.local_cache.iter().collect()
+  pub fn installed_versions(&self) -> Vec<MCVersion> {
+    read(&self.local_cache).clone()
+  }
+
+  pub fn is_installed(&self, version_id: &MCVersion) -> bool {
+    read(&self.local_cache).contains(version_id)
   }
 
-  pub fn remote_versions(&self) -> Vec<&MCVersion> {
-    self.remote_cache
+  pub fn remote_versions(&self) -> Vec<MCVersion> {
+    read(&self.remote_cache)
       .iter()
       .flat_map(|raw| &raw.versions)
-      .map(|v| v.get_id())
+      .map(|v| v.get_id().clone())
       .collect()
   }
 
-  pub fn get_remote_version(&self, version_id: &MCVersion) -> Option<&RemoteVersionInfo> {
-    self.remote_cache
+  pub fn get_remote_version(&self, version_id: &MCVersion) -> Option<RemoteVersionInfo> {
+    read(&self.remote_cache)
       .iter()
       .flat_map(|raw| &raw.versions)
       .find(|v| v.get_id() == version_id)
+      .cloned()
+  }
+
+  /// The remote and installed versions matching `query`, newest first
+  pub fn query_versions(&self, query: &VersionQuery) -> Vec<CatalogEntry> {
+    let installed = self.installed_versions();
+    let mut entries: Vec<_> = read(&self.remote_cache)
+      .iter()
+      .flat_map(|raw| &raw.versions)
+      .map(|version| CatalogEntry {
+        id: version.get_id().clone(),
+        release_type: version.get_type().clone(),
+        release_time: version.get_release_time().clone(),
+        installed: installed.contains(version.get_id()),
+        remote: true,
+      })
+      .collect();
+    // Custom and modded versions are only known from their JSON
+    for version_id in &installed {
+      if self.get_remote_version(version_id).is_some() {
+        continue;
+      }
+      match self.load_manifest(version_id) {
+        Ok(manifest) => {
+          entries.push(CatalogEntry {
+            id: manifest.id.clone(),
+            release_type: manifest.release_type.clone(),
+            release_time: manifest.release_time.clone(),
+            installed: true,
+            remote: false,
+          });
+        }
+        Err(err) => warn!("Failed to load installed version {}: {}", version_id, err),
+      }
+    }
+
+    let timeline = read(&self.timeline);
+    entries.retain(|entry| query.matches(entry, &timeline));
+    drop(timeline);
+    entries.sort_by(|a, b| b.release_time.inner().cmp(a.release_time.inner()).then_with(|| b.id.cmp(&a.id)));
+    entries
+  }
+
+  /// The latest version of `release_type` in the remote version list, or the newest installed
+  /// vanilla one if the list isn't available. Modded and custom versions are never returned
+  pub fn get_latest_version(&self, release_type: &ReleaseType) -> Option<MCVersion> {
+    if let Some(latest) = read(&self.remote_cache).as_ref().and_then(|raw| raw.latest.get(release_type)) {
+      return Some(latest.clone());
+    }
+    let query = VersionQuery::default().with_release_type(release_type.clone()).with_installed(true);
+    self
+      .query_versions(&query)
+      .into_iter()
+      .map(|entry| entry.id)
+      .find(|version_id| {
+        !self.is_custom_version(version_id) &&
+          self.get_installed_version(version_id).is_ok_and(|manifest| manifest.inherits_from.is_none())
+      })
+  }
+
+  /// The Java version and asset index `version_id` needs. Installed versions are resolved with their
+  /// parents, remote ones are fetched (through the metadata cache) without installing them
+  pub async fn get_version_requirements(&self, version_id: &MCVersion) -> Result<VersionRequirements, ResolveManifestError> {
+    if self.is_installed(version_id) {
+      let manifest = self.resolve_local_version(version_id, false, false).await?;
+      return Ok(VersionRequirements::from(&manifest));
+    }
+    let Some(remote_version) = self.get_remote_version(version_id) else {
+      return Err(InstallVersionError::VersionNotFound(version_id.to_string()).into());
+    };
+    let manifest = remote_version.fetch_with(&self.client, &self.get_endpoints(), &self.get_metadata_cache()).await?;
+    Ok(VersionRequirements::from(&manifest))
   }
 
   /// Retrieves the local version information based on the provided version identifier.
@@ -88,28 +332,44 @@ impl VersionManager {
   /// # Panics
   /// This function will panic if the lock on the cache cannot be acquired.
   pub fn get_installed_version(&self, version_id: &MCVersion) -> Result<VersionManifest, LoadVersionError> {
-    let installed_versions = self.installed_versions();
-    if !installed_versions.contains(&version_id) {
+    if !self.is_installed(version_id) {
       return Err(LoadVersionError::VersionNotFound(version_id.to_string()));
     }
     self.load_manifest(version_id)
   }
 
-  pub fn get_resolved_version_cache(&self, version_id: &MCVersion) -> Option<&VersionManifest> {
-    self.resolved_versions_cache.get(version_id)
+  pub fn get_resolved_version_cache(&self, version_id: &MCVersion) -> Option<VersionManifest> {
+    read(&self.resolved_versions_cache).get(version_id).cloned()
+  }
+
+  /// Returns the inherited libraries that were replaced by newer ones the last time the version was resolved
+  pub fn get_library_overrides(&self, version_id: &MCVersion) -> Vec<LibraryOverride> {
+    read(&self.library_overrides).get(version_id).cloned().unwrap_or_default()
   }
 }
 
 impl VersionManager {
-  pub async fn refresh(&mut self) -> Result<(), LoadVersionError> {
-    self.remote_cache.replace(RawVersionList::fetch(&self.client).await?);
+  pub async fn refresh(&self) -> Result<(), LoadVersionError> {
+    match RawVersionList::fetch_with(&self.client, &self.get_endpoints(), &self.get_metadata_cache()).await {
+      Ok(remote) => {
+        write(&self.timeline).register(&remote.versions);
+        write(&self.remote_cache).replace(remote);
+      }
+      Err(LoadVersionError::MetadataError(MetadataError::Offline { .. })) => {
+        warn!("The version list isn't cached, only installed versions are available while offline");
+        *write(&self.remote_cache) = None;
+      }
+      Err(err) => {
+        return Err(err);
+      }
+    }
     self.refresh_local_versions()?;
     Ok(())
   }
 
-  fn refresh_local_versions(&mut self) -> Result<(), LoadVersionError> {
-    self.local_cache.clear();
-    self.resolved_versions_cache.clear();
+  fn refresh_local_versions(&self) -> Result<(), LoadVersionError> {
+    write(&self.resolved_versions_cache).clear();
+    write(&self.library_overrides).clear();
 
     let versions_dir = &self.game_dir.join("versions");
     match read_dir(versions_dir) {
@@ -120,12 +380,41 @@ impl VersionManager {
           .flat_map(|entry| entry.file_name().into_string())
           .collect();
 
+        // Parsing is the slow part with many versions, so it's split between threads
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk_size = dir_names.len().div_ceil(threads).max(1);
+        let loaded: Vec<_> = std::thread::scope(|scope| {
+          let handles: Vec<_> = dir_names
+            .chunks(chunk_size)
+            .map(|chunk| {
+              scope.spawn(move || {
+                chunk
+                  .iter()
+                  .map(|version_id| {
+                    let version_id = MCVersion::new(version_id);
+                    let manifest = self.load_manifest(&version_id);
+                    (version_id, manifest)
+                  })
+                  .collect::<Vec<_>>()
+              })
+            })
+            .collect();
+          handles
+            .into_iter()
+            // A panic while parsing is a bug, don't hide the versions of that chunk
+            .flat_map(|handle| handle.join().unwrap_or_else(|panic| panic::resume_unwind(panic)))
+            .collect()
+        });
+
         let mut versions = vec![];
-        for version_id in dir_names {
+        let mut manifests = vec![];
+        for (version_id, manifest) in loaded {
           info!("Scanning local version versions/{}", &version_id);
-          let version_id = MCVersion::from(version_id);
-          match self.load_manifest(&version_id) {
-            Ok(_) => versions.push(version_id),
+          match manifest {
+            Ok(manifest) => {
+              manifests.push(manifest);
+              versions.push(version_id);
+            }
             Err(LoadVersionError::ManifestNotFound) => {
               warn!("Version file not found! Skipping. (versions/{}/{}.json)", &version_id, &version_id);
             }
@@ -134,9 +423,13 @@ impl VersionManager {
             }
           }
         }
-        self.local_cache.append(&mut versions);
+        write(&self.timeline).register(&manifests);
+        *write(&self.local_cache) = versions;
+      }
+      Err(err) => {
+        warn!("Failed to read version directory: {}", err);
+        write(&self.local_cache).clear();
       }
-      Err(err) => warn!("Failed to read version directory: {}", err),
     }
     Ok(())
   }
@@ -150,6 +443,43 @@ impl VersionManager {
     let manifest_file = File::open(&manifest_path)?;
     Ok(serde_json::from_reader(manifest_file)?)
   }
+
+  /// Hashes the id and JSON of every installed version in the inheritance chain of `version_id`,
+  /// so the key changes when any of them does.
+  ///
+  /// Returns `None` if a version of the chain isn't installed or the chain is circular, and, when
+  /// `check_updates` is set, if the version list has a newer release of one of them.
+  fn get_chain_key(&self, version_id: &MCVersion, check_updates: bool) -> Option<Sha1Sum> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ChainLink {
+      inherits_from: Option<MCVersion>,
+      time: Date,
+    }
+
+    let mut hasher = Sha1::new();
+    let mut seen = HashSet::new();
+    let mut current = Some(version_id.clone());
+    while let Some(id) = current {
+      if !seen.insert(id.clone()) {
+        return None;
+      }
+      let bytes = fs::read(self.versions_dir().join(id.to_string()).join(format!("{}.json", id))).ok()?;
+      let link: ChainLink = serde_json::from_slice(&bytes).ok()?;
+      if check_updates && !self.is_offline() && !self.is_custom_version(&id) {
+        if let Some(remote_version) = self.get_remote_version(&id) {
+          if remote_version.get_updated_time().inner() > link.time.inner() {
+            return None;
+          }
+        }
+      }
+      hasher.update(id.to_string().as_bytes());
+      hasher.update([0]);
+      hasher.update(&bytes);
+      current = link.inherits_from;
+    }
+    Some(hasher.into())
+  }
 }
 
 /* Version Download Functions */
@@ -181,14 +511,26 @@ impl VersionManager {
   /// let manifest = resolver.resolve(&version_id, true, true).await?;
   /// ```
   pub async fn resolve_local_version(
-    &mut self,
+    &self,
     version_id: &MCVersion,
     update_if_necessary: bool,
     ignore_cache: bool
   ) -> Result<VersionManifest, ResolveManifestError> {
     if !ignore_cache {
       if let Some(manifest) = self.get_resolved_version_cache(version_id) {
-        return Ok(manifest.clone());
+        return Ok(manifest);
+      }
+    }
+
+    // Concurrent resolves of the same version wait for the first one and take its result from the cache
+    let lock = get_version_lock(&self.resolve_locks, version_id);
+    let _guard = lock.lock().await;
+    if !ignore_cache {
+      if let Some(manifest) = self.get_resolved_version_cache(version_id) {
+        return Ok(manifest);
+      }
+      if let Some(manifest) = self.load_resolved_version(version_id, update_if_necessary).await {
+        return Ok(manifest);
       }
     }
 
@@ -202,51 +544,276 @@ impl VersionManager {
       manifest = self.install_version_by_id(version_id).await?;
     }
 
+    write(&self.library_overrides).remove(version_id);
     let resolved = self.resolve_inheritances(manifest).await?;
-    self.resolved_versions_cache.insert(resolved.id.clone(), resolved.clone());
-    Ok(resolved)
+    // Everything in the chain is installed now. Native overrides depend on the platform, so they aren't stored
+    if let Some(key) = self.get_chain_key(version_id, false) {
+      let library_overrides = self.get_library_overrides(version_id);
+      if let Err(err) = self.get_resolved_cache().store(version_id, &key, &resolved, &library_overrides).await {
+        warn!("Failed to cache the resolved manifest of {}: {}", version_id, err);
+      }
+    }
+    Ok(self.finish_resolve(resolved))
+  }
+
+  /// The resolved manifest stored on disk, if no version of its chain changed since
+  async fn load_resolved_version(&self, version_id: &MCVersion, update_if_necessary: bool) -> Option<VersionManifest> {
+    let key = self.get_chain_key(version_id, update_if_necessary)?;
+    let (resolved, library_overrides) = self.get_resolved_cache().load(version_id, &key).await?;
+    write(&self.library_overrides).insert(version_id.clone(), library_overrides);
+    Some(self.finish_resolve(resolved))
   }
 
-  pub async fn install_version_by_id(&mut self, version_id: &MCVersion) -> Result<VersionManifest, InstallVersionError> {
+  /// Applies the native overrides and keeps the result in memory
+  fn finish_resolve(&self, mut resolved: VersionManifest) -> VersionManifest {
+    if let Some(native_overrides) = &*read(&self.native_overrides) {
+      let overrides = native_overrides.apply(&mut resolved, &read(&self.platform));
+      if !overrides.is_empty() {
+        write(&self.library_overrides).entry(resolved.id.clone()).or_default().extend(overrides);
+      }
+    }
+    write(&self.resolved_versions_cache).insert(resolved.id.clone(), resolved.clone());
+    resolved
+  }
+
+  pub async fn install_version_by_id(&self, version_id: &MCVersion) -> Result<VersionManifest, InstallVersionError> {
     if let Some(remote_version) = self.get_remote_version(version_id) {
-      return self.install_version(&remote_version.clone()).await;
+      return self.install_version(&remote_version).await;
     }
     Err(InstallVersionError::VersionNotFound(version_id.to_string()))
   }
 
-  pub async fn install_version(&mut self, remote_version: &RemoteVersionInfo) -> Result<VersionManifest, InstallVersionError> {
+  /// Installs the version JSON. If the installed JSON is already the one in the version list
+  /// (e.g. a concurrent call just installed it), it's used as is
+  pub async fn install_version(&self, remote_version: &RemoteVersionInfo) -> Result<VersionManifest, InstallVersionError> {
     let version_id = remote_version.get_id().to_string();
     let target_dir = self.versions_dir().join(&version_id);
     let target_json = target_dir.join(format!("{}.json", &version_id));
 
-    let bytes = self.client.get(remote_version.get_url()).send().await?.error_for_status()?.bytes().await?;
-    create_dir_all(&target_dir)?;
-    fs::write(target_json, &bytes)?;
-    let version_manifest: VersionManifest = serde_json::from_slice(&bytes)?;
+    let lock = get_version_lock(&self.install_locks, remote_version.get_id());
+    let _guard = lock.lock().await;
+    let installed = match tokio::fs::read(&target_json).await {
+      Ok(bytes) if &Sha1Sum::from_reader(&mut &bytes[..])? == remote_version.get_sha1() => Some(bytes),
+      _ => None,
+    };
+    let version_manifest: VersionManifest = if let Some(bytes) = installed {
+      serde_json::from_slice(&bytes)?
+    } else {
+      // Nothing is written until the JSON is verified and parsed, so a failed update keeps the installed version
+      let bytes = remote_version.fetch_bytes_with(&self.client, &self.get_endpoints(), &self.get_metadata_cache()).await?;
+      let version_manifest = serde_json::from_slice(&bytes)?;
+      create_dir_all(&target_dir)?;
+      let mut file = AtomicFile::create(&target_json).await?;
+      file.write_all(&bytes).await?;
+      file.commit().await?;
+      version_manifest
+    };
 
-    self.local_cache.push(version_manifest.get_id().clone());
+    let mut local_cache = write(&self.local_cache);
+    if !local_cache.contains(version_manifest.get_id()) {
+      local_cache.push(version_manifest.get_id().clone());
+    }
     Ok(version_manifest)
   }
 }
 
+// Uninstall Version
 impl VersionManager {
-  pub async fn resolve_inheritances(&mut self, version_manifest: VersionManifest) -> Result<VersionManifest, ResolveManifestError> {
+  /// Deletes `versions/<id>` and the libraries no other installed version needs anymore.
+  ///
+  /// If other installed versions inherit from it, they are removed too when `cascade` is true.
+  /// Otherwise nothing is deleted and `UninstallVersionError::HasDependents` lists them.
+  ///
+  /// Installed versions whose JSON can't be read are skipped, and since the libraries they use are
+  /// unknown, no library is removed. With an object store, the removed files are released from it.
+  pub fn uninstall_version(&self, version_id: &MCVersion, cascade: bool) -> Result<UninstallReport, UninstallVersionError> {
+    if !self.is_installed(version_id) {
+      return Err(UninstallVersionError::VersionNotFound(version_id.to_string()));
+    }
+
+    let mut installed = vec![];
+    let mut unreadable = vec![];
+    for id in self.installed_versions() {
+      match self.load_manifest(&id) {
+        Ok(manifest) => installed.push((id, manifest)),
+        Err(err) => {
+          warn!("Couldn't read version {}, keeping the libraries it may use: {}", id, err);
+          unreadable.push(id);
+        }
+      }
+    }
+    installed.sort_by_key(|(id, _)| id.to_string());
+
+    // The version and, recursively, every version inheriting from it
+    let mut removed = vec![version_id.clone()];
+    let mut i = 0;
+    while i < removed.len() {
+      for (id, manifest) in &installed {
+        if manifest.inherits_from.as_ref() == Some(&removed[i]) && !removed.contains(id) {
+          removed.push(id.clone());
+        }
+      }
+      i += 1;
+    }
+    if removed.len() > 1 && !cascade {
+      return Err(UninstallVersionError::HasDependents {
+        version: version_id.to_string(),
+        dependents: removed[1..].iter().map(MCVersion::to_string).collect(),
+      });
+    }
+
+    let (removed_manifests, remaining): (Vec<_>, Vec<_>) = installed.iter().partition(|(id, _)| removed.contains(id));
+    let native_overrides = read(&self.native_overrides).clone();
+    let platform = self.get_platform();
+    let library_paths = |manifest: &VersionManifest| get_resolved_library_paths(manifest, native_overrides.as_ref(), &platform);
+    let needed: HashSet<_> = remaining
+      .iter()
+      .flat_map(|(_, manifest)| library_paths(manifest))
+      .collect();
+    let mut orphaned: Vec<_> = removed_manifests
+      .iter()
+      .flat_map(|(_, manifest)| library_paths(manifest))
+      .filter(|path| !needed.contains(path))
+      .collect::<HashSet<_>>()
+      .into_iter()
+      .collect();
+    orphaned.sort();
+    if unreadable.iter().any(|id| id != version_id) {
+      orphaned.clear();
+    }
+
+    // The hashes of the removed files, to release them from the object store
+    let object_store = self.get_object_store();
+    let mut released = vec![];
+    let mut hash_for_store = |file: &Path| {
+      if object_store.is_some() {
+        released.extend(File::open(file).and_then(|mut file| Sha1Sum::from_reader(&mut file)).ok());
+      }
+    };
+
+    for id in &removed {
+      info!("Uninstalling version {}", id);
+      let dir = self.versions_dir().join(id.to_string());
+      hash_for_store(&dir.join(format!("{}.jar", id)));
+      if dir.is_dir() {
+        fs::remove_dir_all(dir)?;
+      }
+      write(&self.local_cache).retain(|installed| installed != id);
+      write(&self.resolved_versions_cache).remove(id);
+      write(&self.library_overrides).remove(id);
+      if let Err(err) = self.get_resolved_cache().remove(id) {
+        warn!("Failed to remove the cached manifest of {}: {}", id, err);
+      }
+    }
+
+    let libraries_dir = self.game_dir.join("libraries");
+    let mut libraries = vec![];
+    for path in orphaned {
+      let file = libraries_dir.join(&path);
+      if file.is_file() {
+        hash_for_store(&file);
+        remove_with_empty_parents(&file, &libraries_dir)?;
+        libraries.push(PathBuf::from(path));
+      }
+    }
+    if let Some(object_store) = &object_store {
+      // The files are already gone, so this only leaves stale references behind
+      if let Err(err) = object_store.release_objects(&released) {
+        warn!("Couldn't release the removed files from the object store: {}", err);
+      }
+    }
+    info!("Removed {} version(s) and {} unused libraries", removed.len(), libraries.len());
+    Ok(UninstallReport { versions: removed, libraries })
+  }
+}
+
+// Custom Versions
+impl VersionManager {
+  /// Installs a version that isn't in the version list, like an internal build, into `versions/<id>/`.
+  ///
+  /// The JSON is validated, and its `inheritsFrom` must be installed (custom or not) or in the version list.
+  /// The installed JSON gets a `downloads.client` entry for the client jar, so it isn't replaced by the
+  /// jar of the inherited version. Custom versions are never updated from the version list, installing
+  /// the same id again replaces them. If installing fails, a new `versions/<id>/` is removed.
+  pub async fn install_custom_version(&self, custom_version: &CustomVersion, mode: CustomFileMode) -> Result<VersionManifest, CustomVersionError> {
+    let mut value: serde_json::Value = serde_json::from_slice(&fs::read(&custom_version.json)?)?;
+    let manifest: VersionManifest = serde_json::from_value(value.clone())?;
+    let version_id = manifest.get_id().clone();
+    validate_id(&version_id)?;
+
+    let lock = get_version_lock(&self.install_locks, &version_id);
+    let _guard = lock.lock().await;
+    if self.is_installed(&version_id) && !self.is_custom_version(&version_id) {
+      return Err(CustomVersionError::AlreadyInstalled(version_id.to_string()));
+    }
+    if let Some(inherits_from) = &manifest.inherits_from {
+      if !self.is_installed(inherits_from) && self.get_remote_version(inherits_from).is_none() {
+        return Err(CustomVersionError::ParentNotFound { version: version_id.to_string(), inherits_from: inherits_from.to_string() });
+      }
+    } else if custom_version.jar.is_none() && manifest.jar.is_none() && manifest.get_download_url(DownloadType::Client).is_none() {
+      return Err(CustomVersionError::MissingJar(version_id.to_string()));
+    }
+
+    info!("Installing custom version {}", version_id);
+    let target_dir = self.versions_dir().join(version_id.to_string());
+    let is_new = !target_dir.exists();
+    if let Err(err) = write_custom_version(&target_dir, &version_id, custom_version, mode, &mut value).await {
+      // A reinstall keeps the files of the installed version that weren't replaced yet
+      if is_new {
+        if let Err(err) = fs::remove_dir_all(&target_dir) {
+          warn!("Failed to remove {}: {}", target_dir.display(), err);
+        }
+      }
+      return Err(err);
+    }
+
+    let mut local_cache = write(&self.local_cache);
+    if !local_cache.contains(&version_id) {
+      local_cache.push(version_id.clone());
+    }
+    drop(local_cache);
+    // Versions inheriting from it have to be resolved again
+    write(&self.resolved_versions_cache).clear();
+    write(&self.library_overrides).clear();
+    write(&self.timeline).register([&manifest]);
+    Ok(serde_json::from_value(value)?)
+  }
+
+  /// Whether the version was installed with `install_custom_version`
+  pub fn is_custom_version(&self, version_id: &MCVersion) -> bool {
+    self.versions_dir().join(version_id.to_string()).join(CUSTOM_VERSION_MARKER).is_file()
+  }
+}
+
+impl VersionManager {
+  pub async fn resolve_inheritances(&self, version_manifest: VersionManifest) -> Result<VersionManifest, ResolveManifestError> {
     resolve(version_manifest, self, &mut HashSet::new()).await
   }
 
-  pub async fn is_up_to_date(&mut self, version_manifest: &VersionManifest) -> bool {
+  pub async fn is_up_to_date(&self, version_manifest: &VersionManifest) -> bool {
+    // Nothing can be updated while offline, and custom versions have nowhere to be updated from
+    if self.is_offline() || self.is_custom_version(version_manifest.get_id()) {
+      return true;
+    }
+
     if let Some(remote_version) = self.get_remote_version(version_manifest.get_id()) {
       if remote_version.get_updated_time().inner() > version_manifest.get_updated_time().inner() {
         return false;
       }
 
-      match self.resolve_inheritances(version_manifest.clone()).await {
-        Ok(resolved) => { self.has_all_files(&resolved, &OperatingSystem::get_current_platform()) }
+      let mut resolved = match self.resolve_inheritances(version_manifest.clone()).await {
+        Ok(resolved) => resolved,
         Err(_) => {
           error!("Failed to resolve version {}", version_manifest.get_id().to_string());
-          self.has_all_files(version_manifest, &OperatingSystem::get_current_platform())
+          version_manifest.clone()
         }
+      };
+      // The files that are downloaded, not the ones the overrides replaced
+      let platform = self.get_platform();
+      if let Some(native_overrides) = &*read(&self.native_overrides) {
+        native_overrides.apply(&mut resolved, &platform);
       }
+      self.has_all_files(&resolved, &platform)
     } else {
       true
     }
@@ -259,15 +826,286 @@ impl VersionManager {
     parallel_downloads: Option<usize>,
     retries: Option<usize>
   ) -> Result<(), downloader::error::Error> {
-    let downloader = ClientDownloader::new(Some(self.client.clone()), Arc::clone(progress_reporter), parallel_downloads, retries);
+    if self.is_offline() {
+      let mut missing: Vec<_> = version_manifest
+        .get_required_files(&self.get_platform(), &self.env_features)
+        .into_iter()
+        .filter(|file| !self.game_dir.join(file).is_file())
+        .collect();
+      if !missing.is_empty() {
+        missing.sort();
+        return Err(downloader::error::Error::Offline { missing });
+      }
+      return Ok(());
+    }
+
+    let downloader = self.create_downloader(progress_reporter, parallel_downloads, retries);
     downloader.download_version(version_manifest, self).await
   }
+
+  /// Checks the size and SHA-1 of every file the resolved `version_manifest` needs: the client jar,
+  /// the libraries (natives included), the asset index and every asset, `parallel` files at a time.
+  ///
+  /// Unlike `is_up_to_date`, which only checks that the libraries exist, this reads every file.
+  pub async fn verify_version(&self, version_manifest: &VersionManifest, parallel: Option<usize>) -> VerifyReport {
+    let asset_index = match &version_manifest.asset_index {
+      Some(index) => {
+        let path = downloader::get_asset_index_path(&self.game_dir, &index.id);
+        fs::read(path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok())
+      }
+      None => None,
+    };
+    let files = get_expected_files(&self.game_dir, version_manifest, asset_index.as_ref(), &self.env_features, &self.get_platform());
+    let parallel = parallel.unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |n| n.get()));
+    verify_files(files, parallel).await
+  }
+
+  /// Verifies `version_manifest` and downloads again only the files that are missing or corrupted.
+  ///
+  /// Returns the report of a second verification, so anything that couldn't be repaired is still listed.
+  pub async fn repair_version(
+    &self,
+    version_manifest: &VersionManifest,
+    progress_reporter: &ProgressReporter,
+    parallel_downloads: Option<usize>,
+    retries: Option<usize>
+  ) -> Result<VerifyReport, downloader::error::Error> {
+    let report = self.verify_version(version_manifest, parallel_downloads).await;
+    let broken = report.get_broken();
+    if broken.is_empty() {
+      return Ok(report);
+    }
+    info!("Repairing {} file(s) of {}", broken.len(), version_manifest.get_id());
+
+    if self.is_offline() {
+      let mut missing: Vec<_> = broken
+        .iter()
+        .map(|file| file.path.strip_prefix(&self.game_dir).unwrap_or(&file.path).to_string_lossy().to_string())
+        .collect();
+      missing.sort();
+      return Err(downloader::error::Error::Offline { missing });
+    }
+
+    if let Some(object_store) = self.get_object_store() {
+      let hashes: HashSet<_> = broken.iter().filter_map(|file| file.sha1.as_ref()).collect();
+      for sha1 in hashes {
+        if let Err(err) = object_store.remove_if_corrupted(sha1).await {
+          warn!("Couldn't check object {}: {}", sha1, err);
+        }
+      }
+    }
+
+    let files: HashSet<_> = broken.into_iter().map(|file| file.path.clone()).collect();
+    let downloader = self.create_downloader(progress_reporter, parallel_downloads, retries);
+    downloader.download_files(version_manifest, self, &files).await?;
+    Ok(self.verify_version(version_manifest, parallel_downloads).await)
+  }
+
+  /// Removes the asset objects that no installed version uses anymore, and the virtual asset
+  /// folders that are unused or weren't launched for a while. See `AssetGcOptions::dry_run`
+  /// to only get the report.
+  ///
+  /// This goes through every asset object, so it blocks for a while on large game folders.
+  /// With an object store, the removed objects are released from it.
+  ///
+  /// If the JSON of an installed version can't be read, the assets it uses are unknown, so nothing is removed.
+  pub fn collect_asset_garbage(&self, options: &AssetGcOptions) -> Result<AssetGcReport, AssetGcError> {
+    let mut live_indexes = HashSet::new();
+    for version_id in self.installed_versions() {
+      let manifest = match self.load_manifest(&version_id) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+          warn!("Couldn't read version {}, keeping every asset: {}", version_id, err);
+          return Ok(AssetGcReport { dry_run: options.dry_run, ..Default::default() });
+        }
+      };
+      live_indexes.extend(manifest.asset_index.map(|index| index.id));
+      live_indexes.extend(manifest.assets);
+    }
+    asset_gc::collect_asset_garbage(&self.game_dir, &live_indexes, self.get_object_store().as_ref(), options)
+  }
+
+  fn create_downloader(&self, progress_reporter: &ProgressReporter, parallel_downloads: Option<usize>, retries: Option<usize>) -> ClientDownloader {
+    ClientDownloader::new(Some(self.client.clone()), Arc::clone(progress_reporter), parallel_downloads, retries)
+      .with_platform(self.get_platform())
+      .with_endpoints(self.get_endpoints())
+      .with_object_store(self.get_object_store())
+      .with_control(self.get_download_control())
+  }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+  lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+  lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The lock for `version_id`, created on first use
+fn get_version_lock(locks: &VersionLocks, version_id: &MCVersion) -> Arc<tokio::sync::Mutex<()>> {
+  let mut locks = locks.lock().unwrap_or_else(PoisonError::into_inner);
+  Arc::clone(locks.entry(version_id.clone()).or_default())
 }
 
 // Assets and Libraries
 impl VersionManager {
-  fn has_all_files(&self, local: &VersionManifest, os: &OperatingSystem) -> bool {
-    let required_files = local.get_required_files(os, &self.env_features);
+  fn has_all_files(&self, local: &VersionManifest, platform: &TargetPlatform) -> bool {
+    let required_files = local.get_required_files(platform, &self.env_features);
     required_files.iter().all(|file| self.game_dir.join(file).is_file())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::env::temp_dir;
+
+  use tokio::{ io::{ AsyncReadExt, AsyncWriteExt }, net::TcpListener };
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_install_rejects_checksum_mismatch() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/1.20.json", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut buffer = [0u8; 1024];
+      let _ = socket.read(&mut buffer).await;
+      let body = "{ \"tampered\": true }";
+      let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+      let _ = socket.write_all(response.as_bytes()).await;
+    });
+
+    let game_dir = temp_dir().join("minecraft-launcher-core-install-checksum");
+    let _ = fs::remove_dir_all(&game_dir);
+    let json = game_dir.join("versions").join("1.20").join("1.20.json");
+    create_dir_all(json.parent().unwrap()).unwrap();
+    fs::write(&json, b"installed").unwrap();
+
+    let remote_version: RemoteVersionInfo = serde_json
+      ::from_value(
+        serde_json::json!({
+          "id": "1.20",
+          "type": "release",
+          "url": url,
+          "time": "2023-06-07T09:35:25+00:00",
+          "releaseTime": "2023-06-02T08:36:17+00:00",
+          "sha1": "e575a48efda46cf88111ba05b624ef90c520eef1",
+          "complianceLevel": 1
+        })
+      )
+      .unwrap();
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    version_manager.set_metadata_cache(MetadataCache::disabled());
+
+    let err = version_manager.install_version(&remote_version).await.unwrap_err();
+    assert!(matches!(err, InstallVersionError::ChecksumMismatch { .. }));
+    assert_eq!(fs::read(&json).unwrap(), b"installed");
+    assert!(version_manager.installed_versions().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_concurrent_resolves_install_once() {
+    let body = serde_json::json!({
+      "id": "1.20",
+      "time": "2023-06-07T09:35:25+00:00",
+      "releaseTime": "2023-06-02T08:36:17+00:00",
+      "type": "release",
+    }).to_string();
+    let sha1 = Sha1Sum::from_reader(&mut body.as_bytes()).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/1.20.json", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(0));
+    let served = Arc::clone(&requests);
+    tokio::spawn(async move {
+      loop {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buffer = [0u8; 1024];
+        let _ = socket.read(&mut buffer).await;
+        *served.lock().unwrap() += 1;
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        let _ = socket.write_all(response.as_bytes()).await;
+      }
+    });
+
+    let game_dir = temp_dir().join("minecraft-launcher-core-concurrent-resolve");
+    let _ = fs::remove_dir_all(&game_dir);
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    version_manager.set_metadata_cache(MetadataCache::disabled());
+    let list = serde_json
+      ::from_value(
+        serde_json::json!({
+          "latest": {},
+          "versions": [{
+            "id": "1.20",
+            "type": "release",
+            "url": url,
+            "time": "2023-06-07T09:35:25+00:00",
+            "releaseTime": "2023-06-02T08:36:17+00:00",
+            "sha1": sha1.to_string(),
+            "complianceLevel": 1
+          }]
+        })
+      )
+      .unwrap();
+    write(&version_manager.remote_cache).replace(list);
+
+    let version_manager = Arc::new(version_manager);
+    let tasks: Vec<_> = (0..4)
+      .map(|_| {
+        let version_manager = Arc::clone(&version_manager);
+        tokio::spawn(async move { version_manager.resolve_local_version(&MCVersion::new("1.20"), false, false).await.map(|manifest| manifest.id) })
+      })
+      .collect();
+    for task in tasks {
+      assert_eq!(task.await.unwrap().unwrap(), MCVersion::new("1.20"));
+    }
+    assert_eq!(*requests.lock().unwrap(), 1);
+    assert_eq!(version_manager.installed_versions(), vec![MCVersion::new("1.20")]);
+
+    // Installing again doesn't fetch the JSON it already has
+    let remote_version = version_manager.get_remote_version(&MCVersion::new("1.20")).unwrap();
+    version_manager.install_version(&remote_version).await.unwrap();
+    assert_eq!(*requests.lock().unwrap(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_cancel_download_required_files() {
+    let game_dir = temp_dir().join("minecraft-launcher-core-cancel-download");
+    let _ = fs::remove_dir_all(&game_dir);
+    let index = b"{ \"objects\": {} }";
+    let index_path = downloader::get_asset_index_path(&game_dir, "17");
+    create_dir_all(index_path.parent().unwrap()).unwrap();
+    fs::write(&index_path, index).unwrap();
+    let manifest: VersionManifest = serde_json
+      ::from_value(
+        serde_json::json!({
+          "id": "1.20",
+          "time": "2023-06-07T09:35:25+00:00",
+          "releaseTime": "2023-06-02T08:36:17+00:00",
+          "type": "release",
+          "assetIndex": {
+            "id": "17",
+            "sha1": Sha1Sum::from_reader(&mut &index[..]).unwrap(),
+            "size": index.len(),
+            "totalSize": 0,
+            "url": "http://127.0.0.1:1/17.json"
+          },
+          "downloads": {
+            "client": { "sha1": Sha1Sum::null(), "size": 6, "url": "http://127.0.0.1:1/client.jar" }
+          }
+        })
+      )
+      .unwrap();
+
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    let control = DownloadControl::new();
+    version_manager.set_download_control(control.clone());
+    control.cancel();
+    let reporter: ProgressReporter = Arc::new(downloader::progress::EmptyReporter);
+    let err = version_manager.download_required_files(&manifest, &reporter, None, Some(0)).await.unwrap_err();
+    assert!(matches!(err, downloader::error::Error::Cancelled { .. }));
+    assert!(!game_dir.join("versions").join("1.20").join("1.20.jar").exists());
+  }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use log::{ info, warn };
+
+use crate::json::manifest::{ artifact::Artifact, library::Library, rule::{ OperatingSystem, TargetPlatform }, VersionManifest };
+
+use super::LibraryOverride;
+
+/// Maven repository LWJGL publishes every native build to
+pub const LWJGL_MAVEN_URL: &str = "https://repo1.maven.org/maven2/";
+
+/// Replacement for the LWJGL libraries of one LWJGL version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LwjglOverride {
+  /// The LWJGL version the libraries are moved to
+  pub version: String,
+  /// Native classifier to use for each `(os, arch)` pair, e.g. `(Linux, "arm64") -> "natives-linux-arm64"`
+  pub classifiers: HashMap<(OperatingSystem, String), String>,
+  /// Platforms whose natives only exist from a later LWJGL version, which replaces `version` there
+  pub versions: HashMap<(OperatingSystem, String), String>,
+}
+
+impl LwjglOverride {
+  pub fn new(version: &str) -> Self {
+    Self { version: version.to_string(), classifiers: HashMap::new(), versions: HashMap::new() }
+  }
+
+  pub fn with_classifier(mut self, os: OperatingSystem, arch: &str, classifier: &str) -> Self {
+    self.classifiers.insert((os, arch.to_string()), classifier.to_string());
+    self
+  }
+
+  /// Like `with_classifier`, moving the libraries to `version` on that platform
+  pub fn with_versioned_classifier(mut self, os: OperatingSystem, arch: &str, classifier: &str, version: &str) -> Self {
+    self.versions.insert((os.clone(), arch.to_string()), version.to_string());
+    self.with_classifier(os, arch, classifier)
+  }
+
+  fn get_classifier(&self, platform: &TargetPlatform) -> Option<&String> {
+    self.classifiers.get(&(platform.os.clone(), platform.arch.clone()))
+  }
+
+  fn get_version(&self, platform: &TargetPlatform) -> &String {
+    self.versions.get(&(platform.os.clone(), platform.arch.clone())).unwrap_or(&self.version)
+  }
+}
+
+/// Rewrites the LWJGL libraries of a version so it can run on platforms Mojang doesn't ship natives for
+/// (Linux on ARM or RISC-V, Windows on ARM, and macOS on Apple silicon for versions older than 1.19).
+///
+/// Only LWJGL 3 (1.13 and later) is supported. LWJGL 2 (`org.lwjgl.lwjgl`, up to 1.12.2) has no
+/// builds for these platforms, so older versions are left as they are and won't start there.
+///
+/// The affected libraries are pointed to a maven repository instead of Mojang's servers,
+/// since their hashes are not part of the version manifest.
+#[derive(Debug, Clone)]
+pub struct NativeOverrides {
+  pub maven_url: String,
+  /// Overrides, keyed by the LWJGL version found in the version manifest
+  pub lwjgl: HashMap<String, LwjglOverride>,
+}
+
+impl Default for NativeOverrides {
+  /// Moves LWJGL 3 versions without ARM natives to 3.3.1, and adds the ARM classifiers to the others.
+  /// RISC-V natives start at LWJGL 3.3.4, so every version is moved there on riscv64.
+  /// There are no entries for LWJGL 2, see `NativeOverrides`
+  fn default() -> Self {
+    let mut overrides = Self::new(LWJGL_MAVEN_URL);
+    for (version, target) in [
+      ("3.1.2", "3.3.1"),
+      ("3.1.6", "3.3.1"),
+      ("3.2.1", "3.3.1"),
+      ("3.2.2", "3.3.1"),
+      ("3.3.1", "3.3.1"),
+      ("3.3.2", "3.3.2"),
+      ("3.3.3", "3.3.3"),
+    ] {
+      let lwjgl_override = LwjglOverride::new(target)
+        .with_classifier(OperatingSystem::Linux, "arm64", "natives-linux-arm64")
+        .with_classifier(OperatingSystem::Linux, "arm32", "natives-linux-arm32")
+        .with_classifier(OperatingSystem::Windows, "arm64", "natives-windows-arm64")
+        .with_classifier(OperatingSystem::Osx, "arm64", "natives-macos-arm64")
+        .with_versioned_classifier(OperatingSystem::Linux, "riscv64", "natives-linux-riscv64", "3.3.4");
+      overrides = overrides.with_lwjgl_override(version, lwjgl_override);
+    }
+    overrides
+  }
+}
+
+impl NativeOverrides {
+  /// Creates an empty set of overrides, downloading the replacements from `maven_url`
+  pub fn new(maven_url: &str) -> Self {
+    let mut maven_url = maven_url.to_string();
+    if !maven_url.ends_with('/') {
+      maven_url.push('/');
+    }
+    Self { maven_url, lwjgl: HashMap::new() }
+  }
+
+  pub fn with_lwjgl_override(mut self, lwjgl_version: &str, lwjgl_override: LwjglOverride) -> Self {
+    self.lwjgl.insert(lwjgl_version.to_string(), lwjgl_override);
+    self
+  }
+
+  /// Mojang ships natives for every x86 platform
+  pub fn is_needed(platform: &TargetPlatform) -> bool {
+    !matches!(platform.arch.as_str(), "x64" | "x86")
+  }
+
+  /// Rewrites the LWJGL libraries of `manifest` for `platform`, and returns the replaced artifacts.
+  /// Does nothing if the platform has official natives, or if the LWJGL version has no override.
+  pub fn apply(&self, manifest: &mut VersionManifest, platform: &TargetPlatform) -> Vec<LibraryOverride> {
+    if !Self::is_needed(platform) {
+      return vec![];
+    }
+
+    let mut overrides = vec![];
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let libraries = std::mem::take(&mut manifest.libraries);
+    for mut library in libraries {
+      if let Some(replaced) = self.apply_to_library(&mut library, platform) {
+        overrides.push(LibraryOverride { replaced, replacement: library.name.clone() });
+      }
+      // Several natives (e.g. `natives-windows` and `natives-windows-x86`) can end up pointing to the same artifact.
+      // Mojang's own build of it has checksums, so it's kept over a rewritten one
+      match seen.get(&library.name.get_descriptor()) {
+        Some(&index) => {
+          let kept = &mut manifest.libraries[index];
+          if kept.downloads.is_none() && library.downloads.is_some() {
+            *kept = library;
+          }
+        }
+        None => {
+          seen.insert(library.name.get_descriptor(), manifest.libraries.len());
+          manifest.libraries.push(library);
+        }
+      }
+    }
+
+    if manifest.libraries.iter().any(is_lwjgl2) {
+      warn!("{} uses LWJGL 2, which has no natives for {}-{}", manifest.id, platform.os.get_name(), platform.arch);
+    }
+    if !overrides.is_empty() {
+      info!("Replaced {} LWJGL libraries of {} for {}-{}", overrides.len(), manifest.id, platform.os.get_name(), platform.arch);
+    }
+    overrides
+  }
+
+  fn apply_to_library(&self, library: &mut Library, platform: &TargetPlatform) -> Option<Artifact> {
+    if library.name.group_id.first().map(String::as_str) != Some("org") || library.name.group_id.get(1).map(String::as_str) != Some("lwjgl") {
+      return None;
+    }
+    let lwjgl_override = self.lwjgl.get(&library.name.version)?;
+    let target_classifier = lwjgl_override.get_classifier(platform)?;
+    let version = lwjgl_override.get_version(platform);
+
+    let replaced = library.name.clone();
+    let classifier = match &library.name.classifier {
+      // Newer manifests list each native as its own library
+      Some(classifier) if classifier.starts_with("natives-") => {
+        if natives_os(classifier) != Some(platform.os.clone()) {
+          return None;
+        }
+        // Mojang ships it for this platform already (e.g. macOS on Apple silicon since 1.19)
+        if classifier == target_classifier && *version == library.name.version && library.downloads.is_some() {
+          return None;
+        }
+        Some(target_classifier.clone())
+      }
+      classifier => {
+        // Older ones use the `natives` field
+        if let Some(native) = library.natives.get_mut(&platform.os) {
+          *native = target_classifier.clone();
+        } else if *version == library.name.version {
+          // Platform independent jar which is already at the right version
+          return None;
+        }
+        classifier.clone()
+      }
+    };
+
+    library.name = library.name.with_coordinates(version, classifier);
+    library.url = Some(self.maven_url.clone());
+    library.downloads = None;
+    Some(replaced)
+  }
+}
+
+/// LWJGL 2 is published under `org.lwjgl.lwjgl`, LWJGL 3 under `org.lwjgl`
+fn is_lwjgl2(library: &Library) -> bool {
+  library.name.group_id == ["org", "lwjgl", "lwjgl"]
+}
+
+/// Returns the operating system of an LWJGL native classifier (`natives-linux`, `natives-macos-arm64`, ...)
+fn natives_os(classifier: &str) -> Option<OperatingSystem> {
+  let os = classifier.strip_prefix("natives-")?;
+  let os = os.split('-').next()?;
+  match os {
+    "linux" => Some(OperatingSystem::Linux),
+    "windows" => Some(OperatingSystem::Windows),
+    "macos" | "osx" => Some(OperatingSystem::Osx),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn library(json: &str) -> Library {
+    serde_json::from_str(json).unwrap()
+  }
+
+  fn manifest(libraries: Vec<Library>) -> VersionManifest {
+    let mut manifest: VersionManifest = serde_json
+      ::from_str(
+        r#"{
+          "id": "1.18.2",
+          "time": "2022-02-28T10:42:45+00:00",
+          "releaseTime": "2022-02-28T10:42:45+00:00",
+          "type": "release",
+          "mainClass": "net.minecraft.client.main.Main"
+        }"#
+      )
+      .unwrap();
+    manifest.libraries = libraries;
+    manifest
+  }
+
+  #[test]
+  fn test_linux_arm64_natives() {
+    let platform = TargetPlatform::new(OperatingSystem::Linux, "arm64", "");
+    let mut manifest = manifest(
+      vec![
+        library(
+          r#"{ "name": "org.lwjgl:lwjgl:3.2.2", "downloads": { "artifact": { "url": "https://libraries.minecraft.net/lwjgl.jar", "sha1": "8ad6294407e15780b43e84929c40e4c5e997972e", "size": 1 } } }"#
+        ),
+        library(r#"{ "name": "org.lwjgl:lwjgl:3.2.2:natives-linux", "rules": [{ "action": "allow", "os": { "name": "linux" } }] }"#),
+        library(r#"{ "name": "org.lwjgl:lwjgl:3.2.2:natives-windows", "rules": [{ "action": "allow", "os": { "name": "windows" } }] }"#),
+        library(r#"{ "name": "com.mojang:brigadier:1.0.18" }"#)
+      ]
+    );
+
+    let overrides = NativeOverrides::default().apply(&mut manifest, &platform);
+    assert_eq!(overrides.len(), 2);
+
+    let descriptors: Vec<String> = manifest.libraries
+      .iter()
+      .map(|lib| lib.name.get_descriptor())
+      .collect();
+    assert_eq!(descriptors, vec!["org.lwjgl:lwjgl:3.3.1", "org.lwjgl:lwjgl:3.3.1:natives-linux-arm64", "org.lwjgl:lwjgl:3.2.2:natives-windows", "com.mojang:brigadier:1.0.18"]);
+    assert!(manifest.libraries[0].downloads.is_none());
+    assert_eq!(manifest.libraries[1].url.as_deref(), Some(LWJGL_MAVEN_URL));
+  }
+
+  #[test]
+  fn test_legacy_natives_field() {
+    let platform = TargetPlatform::new(OperatingSystem::Osx, "arm64", "");
+    let mut manifest = manifest(vec![library(r#"{ "name": "org.lwjgl:lwjgl:3.3.3", "natives": { "osx": "natives-macos", "linux": "natives-linux" } }"#)]);
+
+    let overrides = NativeOverrides::default().apply(&mut manifest, &platform);
+    assert_eq!(overrides.len(), 1);
+    let library = &manifest.libraries[0];
+    assert_eq!(library.get_artifact_classifier(&platform), Some(Some("natives-macos-arm64".to_string())));
+    assert_eq!(library.natives.get(&OperatingSystem::Linux).map(String::as_str), Some("natives-linux"));
+  }
+
+  #[test]
+  fn test_official_natives_kept() {
+    let platform = TargetPlatform::new(OperatingSystem::Osx, "arm64", "");
+    let natives = |classifier: &str| {
+      library(
+        &format!(
+          r#"{{ "name": "org.lwjgl:lwjgl:3.3.1:{0}", "downloads": {{ "artifact": {{ "url": "https://libraries.minecraft.net/lwjgl-3.3.1-{0}.jar", "sha1": "8ad6294407e15780b43e84929c40e4c5e997972e", "size": 1 }} }} }}"#,
+          classifier
+        )
+      )
+    };
+    let mut manifest = manifest(vec![natives("natives-macos"), natives("natives-macos-arm64")]);
+
+    let overrides = NativeOverrides::default().apply(&mut manifest, &platform);
+    assert_eq!(overrides.len(), 1);
+    assert_eq!(manifest.libraries.len(), 1);
+    let library = &manifest.libraries[0];
+    assert_eq!(library.name.get_descriptor(), "org.lwjgl:lwjgl:3.3.1:natives-macos-arm64");
+    assert!(library.downloads.is_some() && library.url.is_none());
+  }
+
+  #[test]
+  fn test_riscv64_natives() {
+    let platform = TargetPlatform::new(OperatingSystem::Linux, "riscv64", "");
+    let mut manifest = manifest(
+      vec![library(r#"{ "name": "org.lwjgl:lwjgl:3.3.3" }"#), library(r#"{ "name": "org.lwjgl:lwjgl:3.3.3:natives-linux" }"#)]
+    );
+    assert_eq!(NativeOverrides::default().apply(&mut manifest, &platform).len(), 2);
+    let descriptors: Vec<String> = manifest.libraries
+      .iter()
+      .map(|lib| lib.name.get_descriptor())
+      .collect();
+    assert_eq!(descriptors, vec!["org.lwjgl:lwjgl:3.3.4", "org.lwjgl:lwjgl:3.3.4:natives-linux-riscv64"]);
+  }
+
+  #[test]
+  fn test_lwjgl2_untouched() {
+    let platform = TargetPlatform::new(OperatingSystem::Linux, "arm64", "");
+    let mut manifest = manifest(vec![library(r#"{ "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209", "natives": { "linux": "natives-linux" } }"#)]);
+    assert!(NativeOverrides::default().apply(&mut manifest, &platform).is_empty());
+    assert_eq!(manifest.libraries[0].name.get_descriptor(), "org.lwjgl.lwjgl:lwjgl-platform:2.9.4-nightly-20150209");
+  }
+
+  #[test]
+  fn test_x86_untouched() {
+    let platform = TargetPlatform::new(OperatingSystem::Linux, "x64", "");
+    let mut manifest = manifest(vec![library(r#"{ "name": "org.lwjgl:lwjgl:3.2.2:natives-linux" }"#)]);
+    assert!(NativeOverrides::default().apply(&mut manifest, &platform).is_empty());
+    assert_eq!(manifest.libraries[0].name.get_descriptor(), "org.lwjgl:lwjgl:3.2.2:natives-linux");
+  }
+}
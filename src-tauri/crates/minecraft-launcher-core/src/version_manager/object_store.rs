@@ -0,0 +1,312 @@
+use std::{ collections::HashSet, fs, io, path::{ Path, PathBuf } };
+
+use log::{ info, warn };
+
+use crate::json::Sha1Sum;
+
+use super::downloader::downloadables::{ hash_file, temp_path };
+
+/// The file inside an instance's reference directory with the path of the instance
+const INSTANCE_PATH_FILE: &str = "path";
+
+/// How an object was placed at its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+  Hardlink,
+  /// Copy-on-write clone, used when hardlinks aren't possible (e.g. across volumes)
+  Reflink,
+  Copy,
+}
+
+/// A store of libraries, client jars and assets addressed by their SHA-1, shared between instances.
+///
+/// Objects live in `<root>/objects/<first two characters>/<sha1>` and are placed in each instance
+/// as hardlinks, falling back to reflinks and then to copies. Every instance using an object keeps
+/// a reference to it in `<root>/instances/<instance key>/<sha1>`, and an object is only removed by
+/// `collect_garbage` once no instance references it.
+#[derive(Debug, Clone)]
+pub struct ObjectStore {
+  root: PathBuf,
+  /// The instance references are recorded for
+  instance: Option<PathBuf>,
+}
+
+impl ObjectStore {
+  pub fn new(root: &Path) -> Self {
+    Self { root: root.to_path_buf(), instance: None }
+  }
+
+  /// The same store, recording references for the instance at `instance_dir`
+  pub fn for_instance(&self, instance_dir: &Path) -> Self {
+    let instance = std::path::absolute(instance_dir).unwrap_or_else(|_| instance_dir.to_path_buf());
+    Self { root: self.root.clone(), instance: Some(instance) }
+  }
+
+  pub fn get_root(&self) -> &Path {
+    &self.root
+  }
+
+  pub fn get_instance(&self) -> Option<&Path> {
+    self.instance.as_deref()
+  }
+
+  pub fn get_object_path(&self, sha1: &Sha1Sum) -> PathBuf {
+    let sha1 = sha1.to_string();
+    self.root.join("objects").join(&sha1[..2]).join(sha1)
+  }
+
+  pub fn contains(&self, sha1: &Sha1Sum) -> bool {
+    self.get_object_path(sha1).is_file()
+  }
+
+  /// Places the object with `sha1` at `target`, replacing it. Returns `None` if the store doesn't have it
+  pub async fn link_into(&self, sha1: &Sha1Sum, target: &Path) -> io::Result<Option<LinkKind>> {
+    let object = self.get_object_path(sha1);
+    if !object.is_file() {
+      return Ok(None);
+    }
+
+    let target = target.to_path_buf();
+    let kind = blocking(move || {
+      if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+      }
+      place(&object, &target)
+    }).await?;
+    self.add_reference(sha1).await?;
+    Ok(Some(kind))
+  }
+
+  /// Adds `source`, which must already be verified to hash to `sha1`, to the store.
+  ///
+  /// If the store already had the object, `source` is replaced by a link to it, so instances
+  /// that were populated before using the store stop taking extra space.
+  pub async fn insert(&self, source: &Path, sha1: &Sha1Sum) -> io::Result<LinkKind> {
+    let object = self.get_object_path(sha1);
+    let source = source.to_path_buf();
+    let kind = blocking(move || {
+      if object.is_file() {
+        if is_same_file(&source, &object) {
+          return Ok(LinkKind::Hardlink);
+        }
+        return place(&object, &source);
+      }
+      if let Some(parent) = object.parent() {
+        fs::create_dir_all(parent)?;
+      }
+      place(&source, &object)
+    }).await?;
+    self.add_reference(sha1).await?;
+    Ok(kind)
+  }
+
+  /// Removes the object if its contents don't match its hash. A corrupted instance file that was
+  /// hardlinked corrupts the object too, and it would be linked again instead of downloaded
+  pub async fn remove_if_corrupted(&self, sha1: &Sha1Sum) -> io::Result<bool> {
+    let object = self.get_object_path(sha1);
+    match hash_file(&object).await {
+      Ok(actual) if &actual == sha1 => Ok(false),
+      Ok(_) => {
+        warn!("Object {} is corrupted, removing it", sha1);
+        tokio::fs::remove_file(&object).await?;
+        Ok(true)
+      }
+      Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+      Err(err) => Err(err),
+    }
+  }
+
+  /// How many instances reference the object
+  pub async fn ref_count(&self, sha1: &Sha1Sum) -> io::Result<usize> {
+    let instances_dir = self.root.join("instances");
+    let sha1 = sha1.to_string();
+    blocking(move || Ok(list_dir(&instances_dir)?.filter(|dir| dir.join(&sha1).is_file()).count())).await
+  }
+
+  /// Drops every reference of this store's instance. The objects stay until `collect_garbage` runs
+  pub async fn release(&self) -> io::Result<()> {
+    let Some(dir) = self.get_references_dir() else {
+      return Ok(());
+    };
+    match tokio::fs::remove_dir_all(&dir).await {
+      Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+      _ => Ok(()),
+    }
+  }
+
+  /// Drops this store's instance references to `hashes`, for files removed from the instance.
+  /// The objects stay until `collect_garbage` runs
+  pub fn release_objects<'a>(&self, hashes: impl IntoIterator<Item = &'a Sha1Sum>) -> io::Result<()> {
+    let Some(dir) = self.get_references_dir() else {
+      return Ok(());
+    };
+    for sha1 in hashes {
+      match fs::remove_file(dir.join(sha1.to_string())) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+          return Err(err);
+        }
+        _ => {}
+      }
+    }
+    Ok(())
+  }
+
+  /// Removes the objects no instance references anymore, and returns their hashes.
+  ///
+  /// Instances whose directory was deleted are released first.
+  pub async fn collect_garbage(&self) -> io::Result<Vec<Sha1Sum>> {
+    let root = self.root.clone();
+    blocking(move || {
+      let mut referenced = HashSet::new();
+      for dir in list_dir(&root.join("instances"))? {
+        let instance = fs::read_to_string(dir.join(INSTANCE_PATH_FILE)).map(PathBuf::from);
+        if !instance.is_ok_and(|instance| instance.is_dir()) {
+          info!("Releasing the references of a removed instance ({})", dir.display());
+          fs::remove_dir_all(&dir)?;
+          continue;
+        }
+        referenced.extend(list_dir(&dir)?.filter_map(|file| file.file_name()?.to_str().map(str::to_string)));
+      }
+
+      let mut removed = vec![];
+      for prefix in list_dir(&root.join("objects"))? {
+        for object in list_dir(&prefix)? {
+          let Some(name) = object.file_name().and_then(|name| name.to_str()) else {
+            continue;
+          };
+          if referenced.contains(name) {
+            continue;
+          }
+          match Sha1Sum::try_from(name.to_string()) {
+            Ok(sha1) => {
+              fs::remove_file(&object)?;
+              removed.push(sha1);
+            }
+            Err(_) => warn!("Unexpected file in the object store: {}", object.display()),
+          }
+        }
+      }
+      info!("Removed {} unreferenced objects", removed.len());
+      Ok(removed)
+    }).await
+  }
+
+  fn get_references_dir(&self) -> Option<PathBuf> {
+    let instance = self.instance.as_ref()?;
+    let key = Sha1Sum::from_reader(&mut instance.to_string_lossy().as_bytes()).ok()?;
+    Some(self.root.join("instances").join(key.to_string()))
+  }
+
+  async fn add_reference(&self, sha1: &Sha1Sum) -> io::Result<()> {
+    let (Some(dir), Some(instance)) = (self.get_references_dir(), self.instance.clone()) else {
+      return Ok(());
+    };
+    let sha1 = sha1.to_string();
+    blocking(move || {
+      let path_file = dir.join(INSTANCE_PATH_FILE);
+      if !path_file.is_file() {
+        fs::create_dir_all(&dir)?;
+        fs::write(path_file, instance.to_string_lossy().as_bytes())?;
+      }
+      let reference = dir.join(sha1);
+      if !reference.is_file() {
+        fs::File::create(reference)?;
+      }
+      Ok(())
+    }).await
+  }
+}
+
+/// Replaces `target` with a link to (or a copy of) `source`
+pub(super) fn place(source: &Path, target: &Path) -> io::Result<LinkKind> {
+  let temp = temp_path(target);
+  let kind = if fs::hard_link(source, &temp).is_ok() {
+    LinkKind::Hardlink
+  } else {
+    match reflink_copy::reflink_or_copy(source, &temp)? {
+      None => LinkKind::Reflink,
+      Some(_) => LinkKind::Copy,
+    }
+  };
+  let result = fs::rename(&temp, target);
+  // Renaming over another link to the same file succeeds without removing the temporary one
+  let _ = fs::remove_file(&temp);
+  result.map(|_| kind)
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+  use std::os::unix::fs::MetadataExt;
+
+  match (fs::metadata(a), fs::metadata(b)) {
+    (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+    _ => false,
+  }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_: &Path, _: &Path) -> bool {
+  false
+}
+
+/// The entries of `dir`, or nothing if it doesn't exist
+fn list_dir(dir: &Path) -> io::Result<impl Iterator<Item = PathBuf>> {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => Some(entries),
+    Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+    Err(err) => return Err(err),
+  };
+  Ok(entries.into_iter().flatten().filter_map(|entry| entry.ok().map(|entry| entry.path())))
+}
+
+async fn blocking<T, F>(f: F) -> io::Result<T> where T: Send + 'static, F: FnOnce() -> io::Result<T> + Send + 'static {
+  tokio::task::spawn_blocking(f).await.map_err(io::Error::other)?
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env::temp_dir;
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_link_and_collect_garbage() {
+    let dir = temp_dir().join("minecraft-launcher-core-object-store");
+    let _ = fs::remove_dir_all(&dir);
+    let (first, second) = (dir.join("first"), dir.join("second"));
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+
+    let store = ObjectStore::new(&dir.join("store"));
+    let sha1 = Sha1Sum::from_reader(&mut &b"library"[..]).unwrap();
+    let library = first.join("libraries").join("a.jar");
+    fs::create_dir_all(library.parent().unwrap()).unwrap();
+    fs::write(&library, b"library").unwrap();
+
+    let first_store = store.for_instance(&first);
+    let second_store = store.for_instance(&second);
+    assert_eq!(first_store.insert(&library, &sha1).await.unwrap(), LinkKind::Hardlink);
+    assert!(store.contains(&sha1));
+    let copy = second.join("libraries").join("a.jar");
+    assert!(second_store.link_into(&sha1, &copy).await.unwrap().is_some());
+    assert_eq!(fs::read(&copy).unwrap(), b"library");
+    assert_eq!(store.ref_count(&sha1).await.unwrap(), 2);
+    assert!(store.link_into(&Sha1Sum::null(), &copy).await.unwrap().is_none());
+
+    // Removing the file from an instance releases the object
+    second_store.release_objects([&sha1]).unwrap();
+    assert_eq!(store.ref_count(&sha1).await.unwrap(), 1);
+    second_store.link_into(&sha1, &copy).await.unwrap();
+
+    // Still used by the second instance
+    first_store.release().await.unwrap();
+    assert!(store.collect_garbage().await.unwrap().is_empty());
+    assert_eq!(store.ref_count(&sha1).await.unwrap(), 1);
+
+    // Deleting the instance releases it
+    fs::remove_dir_all(&second).unwrap();
+    assert_eq!(store.collect_garbage().await.unwrap(), vec![sha1.clone()]);
+    assert!(!store.contains(&sha1));
+    assert_eq!(fs::read(&library).unwrap(), b"library");
+  }
+}
@@ -1,5 +1,3 @@
-use std::io::Cursor;
-
 use reqwest::Client;
 use serde::{ Deserialize, Serialize };
 
@@ -9,7 +7,7 @@ mod raw_version_list;
 
 pub use raw_version_list::RawVersionList;
 
-use super::error::InstallVersionError;
+use super::{ endpoints::{ Endpoints, Service }, error::{ InstallVersionError, MetadataError }, metadata_cache::MetadataCache };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -39,13 +37,23 @@ impl RemoteVersionInfo {
   }
 
   pub async fn fetch(&self, client: &Client) -> Result<VersionManifest, InstallVersionError> {
-    let bytes = client.get(&self.url).send().await?.error_for_status()?.bytes().await?;
-    let sha1 = Sha1Sum::from_reader(&mut Cursor::new(&bytes)).map_err(InstallVersionError::ChecksumError)?;
-    if sha1 != self.sha1 {
-      return Err(InstallVersionError::ChecksumMismatch { expected: self.sha1.clone(), actual: sha1 });
-    }
+    self.fetch_with(client, &Endpoints::default(), &MetadataCache::disabled()).await
+  }
+
+  pub async fn fetch_with(&self, client: &Client, endpoints: &Endpoints, cache: &MetadataCache) -> Result<VersionManifest, InstallVersionError> {
+    let bytes = self.fetch_bytes_with(client, endpoints, cache).await?;
     Ok(serde_json::from_slice(&bytes[..])?)
   }
+
+  /// The version JSON as it is served, checked against the SHA-1 in the version list
+  pub async fn fetch_bytes_with(&self, client: &Client, endpoints: &Endpoints, cache: &MetadataCache) -> Result<Vec<u8>, InstallVersionError> {
+    cache.fetch(client, endpoints, Service::VersionMeta, &self.url, Some(&self.sha1)).await.map_err(|err| {
+      match err {
+        MetadataError::ChecksumMismatch { expected, actual, .. } => InstallVersionError::ChecksumMismatch { expected, actual },
+        err => err.into(),
+      }
+    })
+  }
 }
 
 impl VersionInfo for RemoteVersionInfo {
@@ -3,12 +3,17 @@ use std::collections::HashMap;
 use reqwest::Client;
 use serde::{ Deserialize, Serialize };
 
-use crate::{ json::{ MCVersion, ReleaseType }, version_manager::error::LoadVersionError };
+use crate::{
+  json::{ MCVersion, ReleaseType },
+  version_manager::{
+    endpoints::{ Endpoints, Service, VERSION_MANIFEST_URL },
+    error::LoadVersionError,
+    metadata_cache::MetadataCache,
+  },
+};
 
 use super::RemoteVersionInfo;
 
-const VERSION_MANIFEST_URL: &str = "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RawVersionList {
   pub latest: HashMap<ReleaseType, MCVersion>,
@@ -18,6 +23,12 @@ pub struct RawVersionList {
 impl RawVersionList {
   /// Fetches the version manifest from Mojang's servers.
   pub async fn fetch(client: &Client) -> Result<RawVersionList, LoadVersionError> {
-    Ok(client.get(VERSION_MANIFEST_URL).send().await?.json::<RawVersionList>().await?)
+    Self::fetch_with(client, &Endpoints::default(), &MetadataCache::disabled()).await
+  }
+
+  /// Fetches the version manifest, trying the version metadata mirrors first.
+  /// Falls back to the copy in `cache` if the servers can't be reached
+  pub async fn fetch_with(client: &Client, endpoints: &Endpoints, cache: &MetadataCache) -> Result<RawVersionList, LoadVersionError> {
+    Ok(cache.fetch_json(client, endpoints, Service::VersionMeta, VERSION_MANIFEST_URL, None).await?)
   }
 }
@@ -0,0 +1,182 @@
+use std::path::{ Path, PathBuf };
+
+use log::warn;
+use serde::{ Deserialize, Serialize };
+use tokio::fs;
+
+use crate::json::{ manifest::{ artifact::Artifact, VersionManifest }, MCVersion, Sha1Sum };
+
+use super::{ downloader::downloadables::AtomicFile, LibraryOverride };
+
+/// Bumped when the way manifests are resolved changes, so older entries are ignored
+const FORMAT_VERSION: u32 = 1;
+
+/// Keeps the manifests resolved by `VersionManager::resolve_local_version` on disk, so versions
+/// inheriting from others aren't resolved again on every start.
+///
+/// Entries are keyed by a hash of every JSON in the inheritance chain (see
+/// `VersionManager::get_chain_key`), so changing any of them invalidates the entry.
+/// LWJGL native overrides aren't stored, they are applied again after loading.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedCache {
+  dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+  format_version: u32,
+  key: Sha1Sum,
+  manifest: VersionManifest,
+  library_overrides: Vec<(Artifact, Artifact)>,
+}
+
+impl ResolvedCache {
+  pub fn new(dir: &Path) -> Self {
+    Self { dir: Some(dir.to_path_buf()) }
+  }
+
+  /// Nothing is stored, versions are resolved every time
+  pub fn disabled() -> Self {
+    Self::default()
+  }
+
+  pub fn get_dir(&self) -> Option<&Path> {
+    self.dir.as_deref()
+  }
+
+  fn get_path(&self, version_id: &MCVersion) -> Option<PathBuf> {
+    Some(self.dir.as_ref()?.join(format!("{}.json", version_id)))
+  }
+
+  /// The resolved manifest and its library overrides, if they were stored with the same `key`
+  pub async fn load(&self, version_id: &MCVersion, key: &Sha1Sum) -> Option<(VersionManifest, Vec<LibraryOverride>)> {
+    let bytes = fs::read(self.get_path(version_id)?).await.ok()?;
+    let entry: CacheEntry = match serde_json::from_slice(&bytes) {
+      Ok(entry) => entry,
+      Err(err) => {
+        warn!("Ignoring the unreadable resolved manifest of {}: {}", version_id, err);
+        return None;
+      }
+    };
+    if entry.format_version != FORMAT_VERSION || &entry.key != key {
+      return None;
+    }
+    let library_overrides = entry.library_overrides
+      .into_iter()
+      .map(|(replaced, replacement)| LibraryOverride { replaced, replacement })
+      .collect();
+    Some((entry.manifest, library_overrides))
+  }
+
+  pub async fn store(
+    &self,
+    version_id: &MCVersion,
+    key: &Sha1Sum,
+    manifest: &VersionManifest,
+    library_overrides: &[LibraryOverride]
+  ) -> Result<(), std::io::Error> {
+    let Some(path) = self.get_path(version_id) else {
+      return Ok(());
+    };
+    let entry = CacheEntry {
+      format_version: FORMAT_VERSION,
+      key: key.clone(),
+      manifest: manifest.clone(),
+      library_overrides: library_overrides
+        .iter()
+        .map(|library_override| (library_override.replaced.clone(), library_override.replacement.clone()))
+        .collect(),
+    };
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).await?;
+    }
+    let mut file = AtomicFile::create(&path).await?;
+    file.write_all(&serde_json::to_vec(&entry)?).await?;
+    file.commit().await
+  }
+
+  pub fn remove(&self, version_id: &MCVersion) -> Result<(), std::io::Error> {
+    let Some(path) = self.get_path(version_id) else {
+      return Ok(());
+    };
+    match std::fs::remove_file(path) {
+      Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{ env::temp_dir, fs };
+
+  use serde_json::json;
+
+  use crate::{ json::EnvironmentFeatures, version_manager::VersionManager };
+
+  use super::*;
+
+  fn write_version(game_dir: &Path, id: &str, inherits_from: Option<&str>, libraries: &[&str]) {
+    let libraries: Vec<_> = libraries
+      .iter()
+      .map(|name| json!({ "name": name }))
+      .collect();
+    let mut manifest = json!({
+      "id": id,
+      "time": "2023-06-12T13:25:51+00:00",
+      "releaseTime": "2023-06-12T13:25:51+00:00",
+      "type": "release",
+      "mainClass": "net.minecraft.client.main.Main",
+      "libraries": libraries,
+    });
+    if let Some(inherits_from) = inherits_from {
+      manifest["inheritsFrom"] = inherits_from.into();
+    }
+    let dir = game_dir.join("versions").join(id);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(format!("{}.json", id)), manifest.to_string()).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_resolved_cache() {
+    let game_dir = temp_dir().join("minecraft-launcher-core-resolved-cache");
+    let _ = fs::remove_dir_all(&game_dir);
+    write_version(&game_dir, "1.20.1", None, &["org.ow2.asm:asm:9.3", "com.mojang:brigadier:1.0.18"]);
+    write_version(&game_dir, "fabric-1.20.1", Some("1.20.1"), &["org.ow2.asm:asm:9.6"]);
+    let version = MCVersion::new("fabric-1.20.1");
+    let load = || async {
+      let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+      version_manager.refresh_local_versions().unwrap();
+      version_manager
+    };
+
+    let version_manager = load().await;
+    let resolved = version_manager.resolve_local_version(&version, false, false).await.unwrap();
+    assert_eq!(resolved.libraries.len(), 2);
+    let key = version_manager.get_chain_key(&version, false).unwrap();
+    let cache = version_manager.get_resolved_cache();
+    let (mut stored, library_overrides) = cache.load(&version, &key).await.unwrap();
+    assert_eq!(library_overrides, version_manager.get_library_overrides(&version));
+    assert_eq!(library_overrides.len(), 1);
+
+    // A new version manager takes the stored manifest instead of resolving it again
+    stored.main_class = Some("cached.Main".to_string());
+    cache.store(&version, &key, &stored, &library_overrides).await.unwrap();
+    let version_manager = load().await;
+    let resolved = version_manager.resolve_local_version(&version, false, false).await.unwrap();
+    assert_eq!(resolved.main_class.as_deref(), Some("cached.Main"));
+    assert_eq!(version_manager.get_library_overrides(&version), library_overrides);
+
+    // Changing the parent invalidates it
+    write_version(&game_dir, "1.20.1", None, &["org.ow2.asm:asm:9.3", "com.mojang:brigadier:1.0.18", "org.joml:joml:1.10.5"]);
+    let version_manager = load().await;
+    assert_ne!(version_manager.get_chain_key(&version, false), Some(key));
+    let resolved = version_manager.resolve_local_version(&version, false, false).await.unwrap();
+    assert_eq!(resolved.main_class.as_deref(), Some("net.minecraft.client.main.Main"));
+    assert_eq!(resolved.libraries.len(), 3);
+
+    version_manager.uninstall_version(&version, false).unwrap();
+    assert!(!game_dir.join("cache").join("resolved").join("fabric-1.20.1.json").exists());
+  }
+}
@@ -0,0 +1,182 @@
+use std::{ collections::HashSet, fs, io, path::{ Path, PathBuf } };
+
+use crate::json::{ manifest::{ rule::TargetPlatform, VersionManifest }, MCVersion };
+
+use super::natives::NativeOverrides;
+
+#[derive(Debug, Clone, Default)]
+pub struct UninstallReport {
+  /// The removed versions, the requested one first
+  pub versions: Vec<MCVersion>,
+  /// The removed libraries, relative to `libraries/`
+  pub libraries: Vec<PathBuf>,
+}
+
+/// Every path under `libraries/` the manifest could use, on any platform.
+/// Rules aren't evaluated, so a library is kept as long as some version might need it.
+pub fn get_library_paths(manifest: &VersionManifest) -> HashSet<String> {
+  let mut paths = HashSet::new();
+  for library in &manifest.libraries {
+    paths.insert(library.get_artifact_path(None));
+    for classifier in library.natives.values() {
+      for arch in ["32", "64"] {
+        paths.insert(library.get_artifact_path(Some(classifier.replace("${arch}", arch))));
+      }
+    }
+    if let Some(downloads) = &library.downloads {
+      paths.extend(downloads.classifiers.keys().map(|classifier| library.get_artifact_path(Some(classifier.clone()))));
+    }
+  }
+  paths
+}
+
+/// Like `get_library_paths`, adding the libraries resolving the version downloads instead on `platform`.
+/// Inherited libraries come from the parent's own JSON, so only the native overrides are missing
+pub fn get_resolved_library_paths(
+  manifest: &VersionManifest,
+  native_overrides: Option<&NativeOverrides>,
+  platform: &TargetPlatform
+) -> HashSet<String> {
+  let mut paths = get_library_paths(manifest);
+  if let Some(native_overrides) = native_overrides {
+    let mut resolved = manifest.clone();
+    if !native_overrides.apply(&mut resolved, platform).is_empty() {
+      paths.extend(get_library_paths(&resolved));
+    }
+  }
+  paths
+}
+
+/// Removes `file`, and then its parent folders up to `root` while they are empty
+pub fn remove_with_empty_parents(file: &Path, root: &Path) -> io::Result<()> {
+  fs::remove_file(file)?;
+  let mut dir = file.parent();
+  while let Some(current) = dir.filter(|dir| *dir != root && dir.starts_with(root)) {
+    if fs::remove_dir(current).is_err() {
+      break;
+    }
+    dir = current.parent();
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::env::temp_dir;
+
+  use crate::{
+    json::{ manifest::rule::OperatingSystem, EnvironmentFeatures, Sha1Sum },
+    version_manager::{ error::UninstallVersionError, object_store::ObjectStore, VersionManager },
+  };
+
+  use super::*;
+
+  fn write_version(game_dir: &Path, id: &str, inherits_from: Option<&str>, libraries: &[&str]) {
+    let libraries: Vec<_> = libraries
+      .iter()
+      .map(|name| serde_json::json!({ "name": name }))
+      .collect();
+    let mut manifest = serde_json::json!({
+      "id": id,
+      "time": "2022-02-28T10:42:45+00:00",
+      "releaseTime": "2022-02-28T10:42:45+00:00",
+      "type": "release",
+      "libraries": libraries,
+    });
+    if let Some(inherits_from) = inherits_from {
+      manifest["inheritsFrom"] = inherits_from.into();
+    }
+    let dir = game_dir.join("versions").join(id);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(format!("{}.json", id)), manifest.to_string()).unwrap();
+  }
+
+  fn write_library(game_dir: &Path, path: &str) -> PathBuf {
+    let path = game_dir.join("libraries").join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, b"jar").unwrap();
+    path
+  }
+
+  #[test]
+  fn test_uninstall_version() {
+    let game_dir = temp_dir().join("minecraft-launcher-core-uninstall");
+    let _ = fs::remove_dir_all(&game_dir);
+    write_version(&game_dir, "1.20.1", None, &["com.mojang:brigadier:1.0.18", "org.lwjgl:lwjgl:3.3.1"]);
+    write_version(&game_dir, "fabric-1.20.1", Some("1.20.1"), &["net.fabricmc:fabric-loader:0.15.0"]);
+    write_version(&game_dir, "1.19.4", None, &["com.mojang:brigadier:1.0.18"]);
+    let brigadier = write_library(&game_dir, "com/mojang/brigadier/1.0.18/brigadier-1.0.18.jar");
+    let lwjgl = write_library(&game_dir, "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar");
+    let loader = write_library(&game_dir, "net/fabricmc/fabric-loader/0.15.0/fabric-loader-0.15.0.jar");
+
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    version_manager.refresh_local_versions().unwrap();
+    let version = MCVersion::new("1.20.1");
+
+    let err = version_manager.uninstall_version(&version, false).unwrap_err();
+    assert!(matches!(err, UninstallVersionError::HasDependents { dependents, .. } if dependents == ["fabric-1.20.1"]));
+    assert!(game_dir.join("versions").join("1.20.1").is_dir());
+
+    let report = version_manager.uninstall_version(&version, true).unwrap();
+    assert_eq!(report.versions, vec![version.clone(), MCVersion::new("fabric-1.20.1")]);
+    assert_eq!(version_manager.installed_versions(), vec![MCVersion::new("1.19.4")]);
+    assert!(!game_dir.join("versions").join("fabric-1.20.1").exists());
+    // Still used by 1.19.4
+    assert!(brigadier.is_file());
+    assert!(!lwjgl.exists() && !loader.exists());
+    assert!(!game_dir.join("libraries").join("net").exists());
+    assert_eq!(report.libraries.len(), 2);
+  }
+
+  #[test]
+  fn test_uninstall_overridden_natives() {
+    let game_dir = temp_dir().join("minecraft-launcher-core-uninstall-natives");
+    let _ = fs::remove_dir_all(&game_dir);
+    write_version(&game_dir, "1.18.2", None, &["org.lwjgl:lwjgl:3.2.2", "org.lwjgl:lwjgl:3.2.2:natives-linux"]);
+    write_version(&game_dir, "1.16.5", None, &["org.lwjgl:lwjgl:3.2.1", "org.lwjgl:lwjgl:3.2.1:natives-linux"]);
+    let lwjgl = write_library(&game_dir, "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar");
+    let natives = write_library(&game_dir, "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1-natives-linux-arm64.jar");
+
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    version_manager.set_platform(TargetPlatform::new(OperatingSystem::Linux, "arm64", ""));
+    version_manager.refresh_local_versions().unwrap();
+
+    // 1.16.5 is moved to the same LWJGL
+    version_manager.uninstall_version(&MCVersion::new("1.18.2"), false).unwrap();
+    assert!(lwjgl.is_file() && natives.is_file());
+    let report = version_manager.uninstall_version(&MCVersion::new("1.16.5"), false).unwrap();
+    assert_eq!(report.libraries.len(), 2);
+    assert!(!lwjgl.exists() && !natives.exists());
+  }
+
+  #[tokio::test]
+  async fn test_uninstall_unreadable_and_object_store() {
+    let root = temp_dir().join("minecraft-launcher-core-uninstall-store");
+    let _ = fs::remove_dir_all(&root);
+    let game_dir = root.join("game");
+    write_version(&game_dir, "1.20.1", None, &["org.lwjgl:lwjgl:3.3.1"]);
+    write_version(&game_dir, "broken", None, &[]);
+    let broken = game_dir.join("versions").join("broken");
+    let lwjgl = write_library(&game_dir, "org/lwjgl/lwjgl/3.3.1/lwjgl-3.3.1.jar");
+
+    let store = ObjectStore::new(&root.join("store")).for_instance(&game_dir);
+    let sha1 = Sha1Sum::from_reader(&mut &b"jar"[..]).unwrap();
+    store.insert(&lwjgl, &sha1).await.unwrap();
+    let version_manager = VersionManager::new(&game_dir, &EnvironmentFeatures::default(), None);
+    version_manager.set_object_store(Some(store.clone()));
+    version_manager.refresh_local_versions().unwrap();
+    fs::write(broken.join("broken.json"), "{").unwrap();
+
+    // The broken version might use it
+    let report = version_manager.uninstall_version(&MCVersion::new("1.20.1"), false).unwrap();
+    assert!(report.libraries.is_empty() && lwjgl.is_file());
+    version_manager.uninstall_version(&MCVersion::new("broken"), false).unwrap();
+    assert!(!broken.exists());
+
+    write_version(&game_dir, "1.20.2", None, &["org.lwjgl:lwjgl:3.3.1"]);
+    version_manager.refresh_local_versions().unwrap();
+    let report = version_manager.uninstall_version(&MCVersion::new("1.20.2"), false).unwrap();
+    assert_eq!(report.libraries.len(), 1);
+    assert_eq!(store.ref_count(&sha1).await.unwrap(), 0);
+  }
+}
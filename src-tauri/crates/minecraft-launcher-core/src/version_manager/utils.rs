@@ -1,15 +1,16 @@
 use std::collections::HashSet;
 
 use async_recursion::async_recursion;
+use log::info;
 
-use crate::json::{ manifest::VersionManifest, MCVersion };
+use crate::json::{ manifest::{ artifact::Artifact, library::Library, VersionManifest }, MCVersion };
 
-use super::{ error::ResolveManifestError, VersionManager };
+use super::{ error::ResolveManifestError, write, VersionManager };
 
 #[async_recursion]
 pub async fn resolve(
   mut version_manifest: VersionManifest,
-  version_manager: &mut VersionManager,
+  version_manager: &VersionManager,
   inheritance_trace: &mut HashSet<MCVersion>
 ) -> Result<VersionManifest, ResolveManifestError> {
   if let Some(inherits_from) = version_manifest.inherits_from {
@@ -55,11 +56,20 @@ pub async fn resolve(
       resolved_manifest.asset_index.replace(asset_index);
     }
 
+    // Like the client jar of a custom version, which must not be replaced by the inherited one
+    resolved_manifest.downloads.extend(version_manifest.downloads);
+
+    let mut overrides = version_manager.get_library_overrides(&inherits_from);
     if !version_manifest.libraries.is_empty() {
-      let mut new_libraries = version_manifest.libraries;
-      new_libraries.append(&mut resolved_manifest.libraries);
-      resolved_manifest.libraries = new_libraries;
+      let parent_libraries = std::mem::take(&mut resolved_manifest.libraries);
+      let (libraries, mut new_overrides) = merge_libraries(version_manifest.libraries, parent_libraries);
+      for library_override in &new_overrides {
+        info!("{} replaces inherited library {} with {}", resolved_manifest.id, library_override.replaced, library_override.replacement);
+      }
+      resolved_manifest.libraries = libraries;
+      overrides.append(&mut new_overrides);
     }
+    write(&version_manager.library_overrides).insert(resolved_manifest.id.clone(), overrides);
 
     if !version_manifest.arguments.is_empty() {
       for (arg_type, mut args) in version_manifest.arguments {
@@ -84,3 +94,83 @@ pub async fn resolve(
     Ok(version_manifest)
   }
 }
+
+/// A library of an inherited version that was dropped in favour of one declared by the inheriting version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryOverride {
+  /// The library declared by the inherited (parent) version
+  pub replaced: Artifact,
+  /// The library declared by the inheriting (child) version
+  pub replacement: Artifact,
+}
+
+/// Merges the libraries of a child version with the ones of its parent.
+///
+/// Libraries are matched by `group:artifact(:classifier)`. When both versions declare the same library,
+/// the child's entries win and the parent's are dropped, so only one version of it ends up on the classpath.
+/// Duplicates within a single version are kept, as vanilla manifests rely on them for per-OS entries.
+pub fn merge_libraries(child: Vec<Library>, parent: Vec<Library>) -> (Vec<Library>, Vec<LibraryOverride>) {
+  let child_keys: HashSet<String> = child
+    .iter()
+    .map(|lib| lib.name.get_versionless_descriptor())
+    .collect();
+
+  let mut overrides = vec![];
+  let mut libraries = child;
+  for parent_lib in parent {
+    let key = parent_lib.name.get_versionless_descriptor();
+    if !child_keys.contains(&key) {
+      libraries.push(parent_lib);
+      continue;
+    }
+
+    if let Some(child_lib) = libraries.iter().find(|lib| lib.name.get_versionless_descriptor() == key) {
+      overrides.push(LibraryOverride { replaced: parent_lib.name, replacement: child_lib.name.clone() });
+    }
+  }
+  (libraries, overrides)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn library(name: &str) -> Library {
+    serde_json::from_value(json!({ "name": name })).unwrap()
+  }
+
+  #[test]
+  fn test_merge_libraries_child_wins() {
+    let child = vec![library("org.ow2.asm:asm:9.6"), library("net.fabricmc:fabric-loader:0.15.0")];
+    let parent = vec![
+      library("org.ow2.asm:asm:9.3"),
+      library("com.google.guava:guava:31.1-jre"),
+      library("org.lwjgl:lwjgl:3.3.1"),
+      library("org.lwjgl:lwjgl:3.3.1:natives-linux")
+    ];
+
+    let (libraries, overrides) = merge_libraries(child, parent);
+    let names: Vec<String> = libraries
+      .iter()
+      .map(|lib| lib.name.to_string())
+      .collect();
+    assert_eq!(names, vec![
+      "org.ow2.asm:asm:9.6",
+      "net.fabricmc:fabric-loader:0.15.0",
+      "com.google.guava:guava:31.1-jre",
+      "org.lwjgl:lwjgl:3.3.1",
+      "org.lwjgl:lwjgl:3.3.1:natives-linux"
+    ]);
+    assert_eq!(overrides, vec![LibraryOverride { replaced: library("org.ow2.asm:asm:9.3").name, replacement: library("org.ow2.asm:asm:9.6").name }]);
+  }
+
+  #[test]
+  fn test_merge_libraries_keeps_parent_duplicates() {
+    let parent = vec![library("org.lwjgl.lwjgl:lwjgl:2.9.2-nightly-20140822"), library("org.lwjgl.lwjgl:lwjgl:2.9.4-nightly-20150209")];
+    let (libraries, overrides) = merge_libraries(vec![library("com.example:mod:1.0")], parent);
+    assert_eq!(libraries.len(), 3);
+    assert!(overrides.is_empty());
+  }
+}
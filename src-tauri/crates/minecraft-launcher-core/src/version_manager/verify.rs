@@ -0,0 +1,236 @@
+use std::{ io, path::{ Path, PathBuf } };
+
+use futures::{ stream, StreamExt };
+use log::warn;
+
+use crate::json::{
+  manifest::{ assets::{ AssetIndex, AssetObject }, download::DownloadType, rule::TargetPlatform, VersionManifest },
+  EnvironmentFeatures,
+  Sha1Sum,
+  VersionInfo,
+};
+
+use super::downloader::{ downloadables::hash_file, get_asset_index_path };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileKind {
+  ClientJar,
+  /// Includes the jars with natives
+  Library,
+  AssetIndex,
+  Asset,
+}
+
+/// A file an installed version needs, with whatever the manifest says about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedFile {
+  pub kind: FileKind,
+  pub path: PathBuf,
+  pub size: Option<u64>,
+  pub sha1: Option<Sha1Sum>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+  Valid,
+  /// The file exists, but the manifest has no size or hash to check it against
+  Unverified,
+  Missing,
+  SizeMismatch {
+    expected: u64,
+    actual: u64,
+  },
+  ChecksumMismatch {
+    expected: Sha1Sum,
+    actual: Sha1Sum,
+  },
+  /// The file couldn't be read
+  Unreadable {
+    error: String,
+  },
+}
+
+impl FileStatus {
+  /// Whether the file has to be downloaded again
+  pub fn is_broken(&self) -> bool {
+    !matches!(self, Self::Valid | Self::Unverified)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+  pub kind: FileKind,
+  pub path: PathBuf,
+  /// The hash the file should have, if known
+  pub sha1: Option<Sha1Sum>,
+  pub status: FileStatus,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+  pub files: Vec<FileReport>,
+}
+
+impl VerifyReport {
+  pub fn is_valid(&self) -> bool {
+    self.files.iter().all(|file| !file.status.is_broken())
+  }
+
+  /// The missing or corrupted files
+  pub fn get_broken(&self) -> Vec<&FileReport> {
+    self.files
+      .iter()
+      .filter(|file| file.status.is_broken())
+      .collect()
+  }
+}
+
+/// Every file `manifest` needs in `game_dir`: the client jar, the libraries for `platform`, the asset
+/// index and, if `asset_index` is given, every asset object
+pub fn get_expected_files(
+  game_dir: &Path,
+  manifest: &VersionManifest,
+  asset_index: Option<&AssetIndex>,
+  env_features: &EnvironmentFeatures,
+  platform: &TargetPlatform
+) -> Vec<ExpectedFile> {
+  let mut files = vec![];
+
+  let version_id = manifest.get_id().to_string();
+  let jar = manifest.get_download_url(DownloadType::Client);
+  files.push(ExpectedFile {
+    kind: FileKind::ClientJar,
+    path: game_dir.join("versions").join(&version_id).join(format!("{}.jar", version_id)),
+    size: jar.map(|jar| jar.size as u64),
+    sha1: jar.map(|jar| jar.sha1.clone()),
+  });
+
+  let libraries_dir = game_dir.join("libraries");
+  for library in manifest.get_relevant_libraries_for_platform(platform, env_features) {
+    // Libraries with natives for other operating systems only are skipped
+    let Some(classifier) = library.get_artifact_classifier(platform) else {
+      continue;
+    };
+    let download = library.get_download_info(platform);
+    files.push(ExpectedFile {
+      kind: FileKind::Library,
+      path: libraries_dir.join(library.get_artifact_path(classifier)),
+      size: download.as_ref().map(|download| download.size as u64),
+      sha1: download.map(|download| download.sha1),
+    });
+  }
+
+  if let Some(index) = &manifest.asset_index {
+    files.push(ExpectedFile {
+      kind: FileKind::AssetIndex,
+      path: get_asset_index_path(game_dir, &index.id),
+      size: Some(index.size as u64),
+      sha1: Some(index.sha1.clone()),
+    });
+  }
+  if let Some(asset_index) = asset_index {
+    let objects_dir = game_dir.join("assets").join("objects");
+    for object in asset_index.get_unique_objects().into_keys() {
+      files.push(ExpectedFile {
+        kind: FileKind::Asset,
+        path: objects_dir.join(AssetObject::create_path_from_hash(&object.hash)),
+        size: Some(object.size as u64),
+        sha1: Some(object.hash.clone()),
+      });
+    }
+  }
+  files
+}
+
+/// Checks the size and SHA-1 of every file, up to `parallel` at a time
+pub async fn verify_files(files: Vec<ExpectedFile>, parallel: usize) -> VerifyReport {
+  let files = stream
+    ::iter(files)
+    .map(|file| async move {
+      let status = verify_file(&file).await;
+      FileReport { kind: file.kind, path: file.path, sha1: file.sha1, status }
+    })
+    .buffer_unordered(parallel.max(1))
+    .collect().await;
+  VerifyReport { files }
+}
+
+pub async fn verify_file(file: &ExpectedFile) -> FileStatus {
+  let metadata = match tokio::fs::metadata(&file.path).await {
+    Ok(metadata) if metadata.is_file() => metadata,
+    Ok(_) => {
+      return FileStatus::Missing;
+    }
+    Err(err) if err.kind() == io::ErrorKind::NotFound => {
+      return FileStatus::Missing;
+    }
+    Err(err) => {
+      return FileStatus::Unreadable { error: err.to_string() };
+    }
+  };
+
+  // Checking the size first avoids hashing files that are obviously wrong
+  if let Some(expected) = file.size {
+    if metadata.len() != expected {
+      return FileStatus::SizeMismatch { expected, actual: metadata.len() };
+    }
+  }
+
+  let Some(expected) = &file.sha1 else {
+    return if file.size.is_some() { FileStatus::Valid } else { FileStatus::Unverified };
+  };
+  match hash_file(&file.path).await {
+    Ok(actual) if &actual == expected => FileStatus::Valid,
+    Ok(actual) => FileStatus::ChecksumMismatch { expected: expected.clone(), actual },
+    Err(err) => {
+      warn!("Couldn't hash {}: {}", file.path.display(), err);
+      FileStatus::Unreadable { error: err.to_string() }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{ env::temp_dir, fs };
+
+  use super::*;
+
+  #[tokio::test]
+  async fn test_verify_files() {
+    let dir = temp_dir().join("minecraft-launcher-core-verify");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let sha1 = |bytes: &[u8]| Sha1Sum::from_reader(&mut &bytes[..]).unwrap();
+    let file = |name: &str, contents: &[u8]| ExpectedFile {
+      kind: FileKind::Asset,
+      path: dir.join(name),
+      size: Some(contents.len() as u64),
+      sha1: Some(sha1(contents)),
+    };
+
+    fs::write(dir.join("valid"), b"valid").unwrap();
+    fs::write(dir.join("truncated"), b"trunc").unwrap();
+    fs::write(dir.join("corrupted"), b"c0rrupted").unwrap();
+    fs::write(dir.join("unknown"), b"unknown").unwrap();
+    let unknown = ExpectedFile { kind: FileKind::Library, path: dir.join("unknown"), size: None, sha1: None };
+
+    let report = verify_files(
+      vec![
+        file("valid", b"valid"),
+        file("missing", b"missing"),
+        file("truncated", b"truncated"),
+        file("corrupted", b"corrupted"),
+        unknown
+      ],
+      2
+    ).await;
+    let status = |name: &str| report.files.iter().find(|file| file.path == dir.join(name)).map(|file| file.status.clone()).unwrap();
+    assert_eq!(status("valid"), FileStatus::Valid);
+    assert_eq!(status("missing"), FileStatus::Missing);
+    assert_eq!(status("truncated"), FileStatus::SizeMismatch { expected: 9, actual: 5 });
+    assert_eq!(status("corrupted"), FileStatus::ChecksumMismatch { expected: sha1(b"corrupted"), actual: sha1(b"c0rrupted") });
+    assert_eq!(status("unknown"), FileStatus::Unverified);
+    assert!(!report.is_valid());
+    assert_eq!(report.get_broken().len(), 3);
+  }
+}
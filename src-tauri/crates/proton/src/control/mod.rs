@@ -0,0 +1,5 @@
+//! Es el mismo control que usa `minecraft-launcher-core`, así un solo `DownloadControl`
+//! pausa o cancela tanto las descargas de proton como las del `VersionManager`
+pub use minecraft_launcher_core::version_manager::downloader::control::{
+    DownloadControl, DownloadState,
+};
@@ -1,16 +1,25 @@
+use crate::control::DownloadControl;
 use crate::errors::ProtonError;
-use crate::manifest::resolve_asset_index;
+use crate::manifest::resolve_asset_index_with_options;
 use crate::types::{
-    DownloadProgress, DownloadProgressInfo, DownloadProgressType, NormalizedVersion,
-    RESOURCES_BASE_URL,
+    DownloadOutcome, DownloadProgress, DownloadProgressInfo, DownloadProgressType, FailedDownload,
+    NormalizedVersion, RESOURCES_BASE_URL, StageSummary, VersionAssets,
 };
-use crate::utilities::{download_file, extract_native};
+use crate::utilities::{DownloadOptions, download_file_with_options, extract_native};
 use futures::stream::{FuturesUnordered, StreamExt};
+use log::warn;
+use minecraft_launcher_core::version_manager::{
+    downloader::{limiter::NetworkLimiter, retry::RetryPolicy},
+    endpoints::Endpoints,
+    object_store::ObjectStore,
+};
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::Semaphore;
 use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
 
 const MAX_CONCURRENT_DOWNLOADS: usize = 24;
 
@@ -20,6 +29,8 @@ pub struct MinecraftDownloader {
     natives_dir: PathBuf,
     objects_dir: PathBuf,
     libraries_dir: PathBuf,
+    options: DownloadOptions,
+    keep_going: bool,
 }
 
 impl MinecraftDownloader {
@@ -33,144 +44,251 @@ impl MinecraftDownloader {
             natives_dir,
             objects_dir,
             libraries_dir,
+            options: DownloadOptions::default(),
+            keep_going: false,
         }
     }
 
+    /// Si es true, los archivos que fallan se listan en `StageSummary::failures`
+    /// en vez de abortar la etapa
+    pub fn with_keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Permite cancelar, pausar o reanudar las descargas desde fuera
+    pub fn with_control(mut self, control: DownloadControl) -> Self {
+        self.options.control = control;
+        self
+    }
+
+    /// Cuántas veces y con qué espera se reintenta cada archivo
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.options.retry_policy = retry_policy;
+        self
+    }
+
+    /// Límites de red, compartidos con cualquier otra descarga que use el mismo limitador
+    pub fn with_limiter(mut self, limiter: NetworkLimiter) -> Self {
+        self.options.limiter = limiter;
+        self
+    }
+
+    /// Mirrors que se prueban antes que los servidores de Mojang
+    pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.options.endpoints = endpoints;
+        self
+    }
+
+    /// Almacén compartido con otras instancias. Las librerías, el jar y los assets se enlazan
+    /// desde él, y las referencias se registran a nombre de `game_path`
+    pub fn with_object_store(mut self, object_store: &ObjectStore) -> Self {
+        self.options.object_store = Some(object_store.for_instance(&self.game_path));
+        self
+    }
+
+    pub fn with_options(mut self, options: DownloadOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn control(&self) -> &DownloadControl {
+        &self.options.control
+    }
+
+    /// Descarga el jar del cliente en `versions/<id>/<id>.jar`
+    pub async fn download_client_jar(&self) -> Result<DownloadOutcome, ProtonError> {
+        let id = &self.game_version.id;
+        let client_jar = &self.game_version.client_jar;
+        let path = self
+            .game_path
+            .join("versions")
+            .join(id)
+            .join(format!("{}.jar", id));
+        download_file_with_options(
+            client_jar.url.clone(),
+            &path,
+            client_jar.sha1.clone(),
+            &self.options,
+        )
+        .await
+    }
+
+    /// Descarga el índice de assets en `assets/indexes/<id>.json` y lo devuelve
+    pub async fn download_asset_index(
+        &self,
+    ) -> Result<(DownloadOutcome, VersionAssets), ProtonError> {
+        let asset_index = &self.game_version.asset_index;
+        let path = self
+            .game_path
+            .join("assets")
+            .join("indexes")
+            .join(format!("{}.json", asset_index.id));
+        let outcome = download_file_with_options(
+            asset_index.url.clone(),
+            &path,
+            asset_index.sha1.clone(),
+            &self.options,
+        )
+        .await?;
+        let asset_index = serde_json::from_slice(&tokio::fs::read(&path).await?)?;
+        Ok((outcome, asset_index))
+    }
+
+    /// Descarga la configuración de logging en `assets/log_configs/<id>`, si la versión tiene una
+    pub async fn download_logging_config(&self) -> Result<Option<DownloadOutcome>, ProtonError> {
+        let Some(logging) = &self.game_version.logging else {
+            return Ok(None);
+        };
+        let path = self
+            .game_path
+            .join("assets")
+            .join("log_configs")
+            .join(&logging.id);
+        let outcome = download_file_with_options(
+            logging.url.clone(),
+            &path,
+            logging.sha1.clone(),
+            &self.options,
+        )
+        .await?;
+        Ok(Some(outcome))
+    }
+
     pub async fn download_natives(
         &mut self,
         progress_tx: Option<Sender<DownloadProgress>>,
-    ) -> Result<(), ProtonError> {
-        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS));
-        let total = self.game_version.natives.len();
+    ) -> Result<StageSummary, ProtonError> {
         // Como no se usa mas prefiero tomarlo
         // att: santiagolxx
         let natives = std::mem::take(&mut self.game_version.natives);
-        let completed = Arc::new(AtomicUsize::new(0));
-        let mut tasks = FuturesUnordered::new();
-        let natives_dir = Arc::new(self.natives_dir.clone());
-        let game_version = Arc::new(self.game_version.id.clone());
-        let temp_dir = &self
-            .game_path
-            .join("temp")
-            .join(uuid::Uuid::new_v4().to_string());
-
-        tokio::fs::create_dir_all(temp_dir).await?;
-
-        for native in natives {
-            let temp_native_path = temp_dir.join(native.path);
-            let completed = Arc::clone(&completed);
-            let semaphore = Arc::clone(&semaphore);
-            let natives_dir = Arc::clone(&natives_dir);
-            let tx = progress_tx.clone();
-            let info = DownloadProgressInfo {
+        let files = natives
+            .into_iter()
+            .map(|native| FileDownload {
                 name: native.name,
-                version: Arc::clone(&game_version),
-            };
-            tasks.push(tokio::spawn(async move {
-                let permit = semaphore.acquire_owned().await;
-                let result = download_file(native.url, &temp_native_path, native.sha1).await;
-                extract_native(&temp_native_path, natives_dir.as_ref()).await?;
-                let count = completed.fetch_add(1, Ordering::Relaxed) + 1;
-
-                if let Some(tx) = tx {
-                    let _ = tx
-                        .send(DownloadProgress {
-                            current: count,
-                            total,
-                            info: info,
-                            download_type: DownloadProgressType::Native,
-                        })
-                        .await;
-                }
-                drop(permit);
-                result
-            }));
-        }
-        while let Some(res) = tasks.next().await {
-            res??;
-        }
-        tokio::fs::remove_dir_all(temp_dir).await?;
-        Ok(())
+                url: native.url,
+                // Los jars se quedan en `libraries`, como los deja el launcher oficial
+                path: self.libraries_dir.join(native.path),
+                sha1: native.sha1,
+                download_type: DownloadProgressType::Native,
+            })
+            .collect();
+        self.run_downloads(files, progress_tx).await
     }
 
     pub async fn download_libraries(
         &mut self,
         progress_tx: Option<Sender<DownloadProgress>>,
-    ) -> Result<(), ProtonError> {
-        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS));
-        let total = self.game_version.libraries.len();
-        // Esto la verdad es lo mismo que el de natives
-        // solo cambia que no se extraen los jars
-        // ni tampoco se necesita el dir temporal.
-        // att: santiagolxx
+    ) -> Result<StageSummary, ProtonError> {
         let libraries = std::mem::take(&mut self.game_version.libraries);
-        let completed = Arc::new(AtomicUsize::new(0));
-        let mut tasks = FuturesUnordered::new();
-        let game_version = Arc::new(self.game_version.id.clone());
-
-        for library in libraries {
-            let library_path = self.libraries_dir.join(library.path);
-            let completed = Arc::clone(&completed);
-            let semaphore = Arc::clone(&semaphore);
-            let tx = progress_tx.clone();
-            let info = DownloadProgressInfo {
+        let files = libraries
+            .into_iter()
+            .map(|library| FileDownload {
                 name: library.name,
-                version: Arc::clone(&game_version),
-            };
+                url: library.url,
+                path: self.libraries_dir.join(library.path),
+                sha1: library.sha1,
+                download_type: DownloadProgressType::Library,
+            })
+            .collect();
+        self.run_downloads(files, progress_tx).await
+    }
 
-            tasks.push(tokio::spawn(async move {
-                let permit = semaphore.acquire_owned().await;
-                let result = download_file(library.url, &library_path, library.sha1).await;
-                let count = completed.fetch_add(1, Ordering::Relaxed) + 1;
+    pub async fn download_assets(
+        &self,
+        progress_tx: Option<Sender<DownloadProgress>>,
+    ) -> Result<StageSummary, ProtonError> {
+        let asset_index =
+            resolve_asset_index_with_options(&self.game_version, &self.options).await?;
+        self.download_assets_from_index(asset_index, progress_tx)
+            .await
+    }
 
-                if let Some(tx) = tx {
-                    let _ = tx
-                        .send(DownloadProgress {
-                            current: count,
-                            total,
-                            info: info,
-                            download_type: DownloadProgressType::Library,
-                        })
-                        .await;
+    /// Descarga los objetos de un índice de assets ya obtenido
+    pub async fn download_assets_from_index(
+        &self,
+        asset_index: VersionAssets,
+        progress_tx: Option<Sender<DownloadProgress>>,
+    ) -> Result<StageSummary, ProtonError> {
+        // Varios assets pueden compartir el mismo objeto, y cada objeto se descarga a una ruta fija
+        let mut seen = HashSet::new();
+        let files = asset_index
+            .as_vec()
+            .into_iter()
+            .filter(|(_, asset)| seen.insert(asset.hash.clone()))
+            .map(|(name, asset)| {
+                let subhash: String = asset.hash.chars().take(2).collect();
+                FileDownload {
+                    name,
+                    url: format!("{}{}/{}", RESOURCES_BASE_URL, subhash, asset.hash),
+                    path: self.objects_dir.join(&subhash).join(&asset.hash),
+                    sha1: asset.hash,
+                    download_type: DownloadProgressType::Asset,
                 }
-                drop(permit);
-                result
-            }));
-        }
-        while let Some(res) = tasks.next().await {
-            res??;
-        }
-        Ok(())
+            })
+            .collect();
+        self.run_downloads(files, progress_tx).await
     }
 
-    pub async fn download_assets(
+    /// Vuelve a intentar solo los archivos que fallaron en una etapa anterior
+    pub async fn retry_failed(
         &self,
+        failures: Vec<FailedDownload>,
         progress_tx: Option<Sender<DownloadProgress>>,
-    ) -> Result<(), ProtonError> {
+    ) -> Result<StageSummary, ProtonError> {
+        let files = failures
+            .into_iter()
+            .map(|failure| FileDownload {
+                name: failure.name,
+                url: failure.url,
+                path: failure.path,
+                sha1: failure.sha1,
+                download_type: failure.download_type,
+            })
+            .collect();
+        self.run_downloads(files, progress_tx).await
+    }
+
+    async fn run_downloads(
+        &self,
+        files: Vec<FileDownload>,
+        progress_tx: Option<Sender<DownloadProgress>>,
+    ) -> Result<StageSummary, ProtonError> {
         let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS));
-        let asset_index = resolve_asset_index(&self.game_version).await?;
-        let mut tasks = FuturesUnordered::new();
+        let total = files.len();
         let completed = Arc::new(AtomicUsize::new(0));
-        let total = asset_index.len();
+        let mut tasks = FuturesUnordered::new();
+        let natives_dir = Arc::new(self.natives_dir.clone());
         let game_version = Arc::new(self.game_version.id.clone());
 
-        for (name, asset) in asset_index.as_vec() {
-            let hash = &asset.hash;
+        for file in files {
+            let completed = Arc::clone(&completed);
             let semaphore = Arc::clone(&semaphore);
-            let subhash: String = hash.chars().take(2).collect();
-            let url = format!("{}/{}/{}", RESOURCES_BASE_URL, subhash, hash);
-            let path = self.objects_dir.join(&subhash).join(hash);
-            let hash = hash.to_string();
+            let options = self.options.clone();
+            let natives_dir = Arc::clone(&natives_dir);
             let tx = progress_tx.clone();
-            let completed = Arc::clone(&completed);
             let info = DownloadProgressInfo {
-                name: name,
+                name: file.name.clone(),
                 version: Arc::clone(&game_version),
             };
 
             tasks.push(tokio::spawn(async move {
                 let permit = semaphore.acquire_owned().await;
-                let result = download_file(url, &path, hash).await;
+                let result = async {
+                    let outcome = download_file_with_options(
+                        file.url.clone(),
+                        &file.path,
+                        file.sha1.clone(),
+                        &options,
+                    )
+                    .await?;
+                    if matches!(file.download_type, DownloadProgressType::Native) {
+                        options.control.checkpoint().await?;
+                        extract_native(&file.path, natives_dir.as_ref()).await?;
+                    }
+                    Ok(outcome)
+                }
+                .await;
                 let count = completed.fetch_add(1, Ordering::Relaxed) + 1;
 
                 if let Some(tx) = tx {
@@ -178,20 +296,61 @@ impl MinecraftDownloader {
                         .send(DownloadProgress {
                             current: count,
                             total,
-                            info: info,
-                            download_type: DownloadProgressType::Asset,
+                            info,
+                            download_type: file.download_type.clone(),
                         })
                         .await;
                 }
                 drop(permit);
-                result
+                result.map_err(|error| file.into_failure(error))
             }));
         }
 
+        let mut summary = StageSummary::default();
         while let Some(res) = tasks.next().await {
-            res??;
+            match res? {
+                Ok(outcome) => summary.record(outcome),
+                Err(failure)
+                    if self.keep_going && !matches!(failure.error, ProtonError::Cancelled) =>
+                {
+                    warn!("Failed to download {}: {}", failure.url, failure.error);
+                    summary.failures.push(failure);
+                }
+                Err(failure) => {
+                    // No dejar descargas corriendo en segundo plano
+                    tasks.iter().for_each(JoinHandle::abort);
+                    return Err(failure.error);
+                }
+            }
         }
 
-        Ok(())
+        Ok(summary)
+    }
+}
+
+/// Un archivo a descargar por `run_downloads`
+struct FileDownload {
+    name: String,
+    url: String,
+    path: PathBuf,
+    sha1: String,
+    download_type: DownloadProgressType,
+}
+
+impl FileDownload {
+    fn into_failure(self, error: ProtonError) -> FailedDownload {
+        let attempts = match &error {
+            ProtonError::DownloadFailed { attempts, .. } => *attempts,
+            _ => 1,
+        };
+        FailedDownload {
+            name: self.name,
+            url: self.url,
+            path: self.path,
+            sha1: self.sha1,
+            download_type: self.download_type,
+            attempts,
+            error,
+        }
     }
 }
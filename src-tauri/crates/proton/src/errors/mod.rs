@@ -1,17 +1,45 @@
+use minecraft_launcher_core::version_manager::{
+    downloader::{downloadables::DownloadError, retry::FailureKind},
+    error::MetadataError,
+};
+use reqwest::StatusCode;
 use std::io;
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ProtonError {
     #[error("Request failed: {0}")]
     RequestError(#[from] reqwest::Error),
+    #[error("Server responded {status} for {url}")]
+    HttpStatus {
+        url: String,
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
     #[error("Version {0} not found.")]
     VersionNotFound(String),
     #[error("Filesystem error {0}")]
     IoError(#[from] io::Error),
-    #[error("Hash mismatch")]
-    HashMismatch,
+    #[error("Invalid JSON: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Failed to download {url} after {attempts} attempt(s): {source}")]
+    DownloadFailed {
+        url: String,
+        attempts: usize,
+        source: Box<ProtonError>,
+    },
+    #[error("Download was cancelled")]
+    Cancelled,
+    #[error("{url} needs the network, but offline mode is enabled")]
+    Offline { url: String },
+    #[error("Hash mismatch for {url}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
     #[error("Concurrency Error")]
     JoinError(#[from] tokio::task::JoinError),
     #[error("Invalid library name: '{0}'")]
@@ -24,12 +52,76 @@ pub enum ProtonError {
     Other(String),
 }
 
+impl ProtonError {
+    /// Clasifica el error para decidir si vale la pena reintentar la descarga
+    pub fn failure_kind(&self) -> FailureKind {
+        match self {
+            ProtonError::RequestError(err) => FailureKind::from_reqwest(err),
+            ProtonError::HttpStatus {
+                status,
+                retry_after,
+                ..
+            } => match FailureKind::from_status(*status, None) {
+                FailureKind::RateLimited(_) => FailureKind::RateLimited(*retry_after),
+                kind => kind,
+            },
+            ProtonError::ChecksumMismatch { .. } => FailureKind::Checksum,
+            ProtonError::DownloadFailed { source, .. } => source.failure_kind(),
+            // Un error de red al leer el cuerpo o un Content-Range inesperado
+            ProtonError::Other(_) => FailureKind::Transient,
+            _ => FailureKind::Permanent,
+        }
+    }
+}
+
 impl From<Box<dyn std::error::Error + Send + Sync>> for ProtonError {
     fn from(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
         ProtonError::Other(err.to_string())
     }
 }
 
+impl From<MetadataError> for ProtonError {
+    fn from(err: MetadataError) -> Self {
+        match err {
+            MetadataError::Offline { url } => ProtonError::Offline { url },
+            MetadataError::FetchError(err) => ProtonError::RequestError(err),
+            MetadataError::ParseError(err) => ProtonError::JsonError(err),
+            MetadataError::ChecksumMismatch {
+                url,
+                expected,
+                actual,
+            } => ProtonError::ChecksumMismatch {
+                url,
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            },
+        }
+    }
+}
+
+/// Sobre todo para `DownloadControl::checkpoint`, que solo falla con `DownloadError::Cancelled`
+impl From<DownloadError> for ProtonError {
+    fn from(err: DownloadError) -> Self {
+        match err {
+            DownloadError::Cancelled => ProtonError::Cancelled,
+            DownloadError::Download(err) => ProtonError::RequestError(err),
+            DownloadError::HttpStatus {
+                url,
+                status,
+                retry_after,
+            } => ProtonError::HttpStatus {
+                url,
+                status,
+                retry_after,
+            },
+            DownloadError::WriteFile(err)
+            | DownloadError::RemoveFile(err)
+            | DownloadError::PrepareDestination(err) => ProtonError::IoError(err),
+            err => ProtonError::Other(err.to_string()),
+        }
+    }
+}
+
 impl From<async_zip::error::ZipError> for ProtonError {
     fn from(err: async_zip::error::ZipError) -> Self {
         ProtonError::Other(format!("Zip extraction error: {}", err))
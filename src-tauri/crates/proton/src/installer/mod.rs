@@ -0,0 +1,307 @@
+use std::path::{Path, PathBuf};
+
+use log::info;
+use minecraft_launcher_core::network::NetworkSettings;
+use minecraft_launcher_core::version_manager::{
+    downloader::{limiter::NetworkLimiter, retry::RetryPolicy},
+    endpoints::Endpoints,
+    metadata_cache::MetadataCache,
+    object_store::ObjectStore,
+};
+use reqwest::Client;
+use tokio::sync::mpsc::Sender;
+
+use crate::control::DownloadControl;
+use crate::downloaders::MinecraftDownloader;
+use crate::errors::ProtonError;
+use crate::manifest::resolve_version_in_manifest_with_options;
+use crate::rules::RuleContext;
+use crate::types::{
+    DownloadOutcome, DownloadProgress, FailedDownload, MojangVersionDetails, NormalizedVersion,
+    StageSummary,
+};
+use crate::utilities::{
+    DownloadOptions, HTTP_CLIENT, create_client, default_retry_policy, download_file_with_options,
+};
+
+/// Etapas de una instalación, en el orden en que se ejecutan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InstallStage {
+    /// `versions/<id>/<id>.json`, tal cual lo sirve Mojang
+    VersionJson,
+    /// `versions/<id>/<id>.jar`
+    ClientJar,
+    /// `libraries/`
+    Libraries,
+    /// Jars nativos en `libraries/`, extraídos en `natives/<id>`
+    Natives,
+    /// `assets/indexes/<id>.json`
+    AssetIndex,
+    /// `assets/objects/`
+    Assets,
+    /// `assets/log_configs/<id>`
+    LoggingConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct InstallOptions {
+    /// Contexto contra el que se evalúan las reglas de librerías y argumentos
+    pub rule_context: RuleContext,
+    pub progress_tx: Option<Sender<DownloadProgress>>,
+    /// Permite cancelar, pausar o reanudar la instalación
+    pub control: DownloadControl,
+    /// Cuántas veces y con qué espera se reintenta cada archivo
+    pub retry_policy: RetryPolicy,
+    /// Límites de red. Por defecto, `NetworkLimiter::global()`
+    pub limiter: NetworkLimiter,
+    /// Mirrors para el manifiesto, las librerías, los assets, etc.
+    pub endpoints: Endpoints,
+    /// Sin red: el manifiesto y los JSON salen de la caché en `<game_path>/cache/metadata`
+    /// (la misma que usa `minecraft-launcher-core`) y solo se aceptan archivos ya descargados.
+    /// Cualquier archivo que falte falla con `ProtonError::Offline`
+    pub offline: bool,
+    /// Cliente HTTP de la instalación. Por defecto, `HTTP_CLIENT`
+    pub client: Client,
+    /// Almacén de librerías, jars y assets compartido entre instancias.
+    /// Los archivos se enlazan en `game_path` con hardlinks (o reflinks, o copias)
+    pub object_store: Option<ObjectStore>,
+    /// Si es true, las librerías, nativos y assets que fallan se listan en el reporte
+    /// en vez de abortar la instalación
+    pub keep_going: bool,
+}
+
+impl Default for InstallOptions {
+    fn default() -> Self {
+        Self {
+            rule_context: RuleContext::current(),
+            progress_tx: None,
+            control: DownloadControl::new(),
+            retry_policy: default_retry_policy(),
+            limiter: NetworkLimiter::global(),
+            endpoints: Endpoints::default(),
+            offline: false,
+            client: HTTP_CLIENT.clone(),
+            object_store: None,
+            keep_going: false,
+        }
+    }
+}
+
+impl InstallOptions {
+    pub fn with_rule_context(mut self, rule_context: RuleContext) -> Self {
+        self.rule_context = rule_context;
+        self
+    }
+
+    pub fn with_control(mut self, control: DownloadControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_limiter(mut self, limiter: NetworkLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Usa un cliente creado con `settings` para el manifiesto y todas las descargas
+    pub fn with_network_settings(self, settings: &NetworkSettings) -> Result<Self, ProtonError> {
+        Ok(self.with_client(create_client(settings)?))
+    }
+
+    pub fn with_object_store(mut self, object_store: ObjectStore) -> Self {
+        self.object_store = Some(object_store);
+        self
+    }
+
+    pub fn with_keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    pub fn with_progress(mut self, progress_tx: Sender<DownloadProgress>) -> Self {
+        self.progress_tx = Some(progress_tx);
+        self
+    }
+
+    fn download_options(&self, game_path: &Path) -> DownloadOptions {
+        let metadata_cache = MetadataCache::new(&game_path.join("cache").join("metadata"))
+            .with_offline(self.offline);
+        DownloadOptions {
+            control: self.control.clone(),
+            retry_policy: self.retry_policy.clone(),
+            limiter: self.limiter.clone(),
+            endpoints: self.endpoints.clone(),
+            metadata_cache,
+            client: self.client.clone(),
+            object_store: self
+                .object_store
+                .as_ref()
+                .map(|store| store.for_instance(game_path)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StageReport {
+    pub stage: InstallStage,
+    pub summary: StageSummary,
+}
+
+/// Qué etapas se ejecutaron y qué cambió cada una
+#[derive(Debug)]
+pub struct InstallReport {
+    pub version: NormalizedVersion,
+    pub stages: Vec<StageReport>,
+}
+
+impl InstallReport {
+    pub fn get_stage(&self, stage: InstallStage) -> Option<&StageSummary> {
+        self.stages
+            .iter()
+            .find(|report| report.stage == stage)
+            .map(|report| &report.summary)
+    }
+
+    /// Si la instalación descargó algo
+    pub fn changed(&self) -> bool {
+        self.stages.iter().any(|report| report.summary.changed())
+    }
+
+    /// Archivos que fallaron en cualquier etapa
+    pub fn failures(&self) -> impl Iterator<Item = &FailedDownload> {
+        self.stages
+            .iter()
+            .flat_map(|report| &report.summary.failures)
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    fn push(&mut self, stage: InstallStage, summary: StageSummary) {
+        info!("{:?}: {:?}", stage, summary);
+        self.stages.push(StageReport { stage, summary });
+    }
+}
+
+/// Instala una versión vanilla completa en `game_path`, con la misma estructura que el launcher oficial.
+/// Los archivos que ya existen con el hash correcto no se vuelven a descargar.
+pub async fn install(
+    game_path: PathBuf,
+    version_id: &str,
+    options: InstallOptions,
+) -> Result<InstallReport, ProtonError> {
+    let download_options = options.download_options(&game_path);
+
+    // El JSON se guarda sin modificar, para que otros launchers lo puedan leer
+    let json_path = game_path
+        .join("versions")
+        .join(version_id)
+        .join(format!("{}.json", version_id));
+    let json_outcome =
+        match resolve_version_in_manifest_with_options(version_id.to_string(), &download_options)
+            .await
+        {
+            Ok(info) => {
+                download_file_with_options(info.url, &json_path, info.sha1, &download_options)
+                    .await?
+            }
+            // Sin red y sin manifiesto en caché, una versión ya instalada se usa tal cual
+            Err(ProtonError::Offline { .. }) if json_path.is_file() => {
+                DownloadOutcome::AlreadyPresent
+            }
+            Err(err) => return Err(err),
+        };
+    let details: MojangVersionDetails =
+        serde_json::from_slice(&tokio::fs::read(&json_path).await?)?;
+    let version = NormalizedVersion::from_details(details, &options.rule_context)?;
+
+    let mut report = InstallReport {
+        version: version.clone(),
+        stages: Vec::new(),
+    };
+    report.push(InstallStage::VersionJson, json_outcome.into());
+
+    let mut downloader = MinecraftDownloader::new(game_path, version)
+        .with_options(download_options)
+        .with_keep_going(options.keep_going);
+    report.push(
+        InstallStage::ClientJar,
+        downloader.download_client_jar().await?.into(),
+    );
+    report.push(
+        InstallStage::Libraries,
+        downloader
+            .download_libraries(options.progress_tx.clone())
+            .await?,
+    );
+    report.push(
+        InstallStage::Natives,
+        downloader
+            .download_natives(options.progress_tx.clone())
+            .await?,
+    );
+
+    let (index_outcome, asset_index) = downloader.download_asset_index().await?;
+    report.push(InstallStage::AssetIndex, index_outcome.into());
+    report.push(
+        InstallStage::Assets,
+        downloader
+            .download_assets_from_index(asset_index, options.progress_tx)
+            .await?,
+    );
+
+    if let Some(outcome) = downloader.download_logging_config().await? {
+        report.push(InstallStage::LoggingConfig, outcome.into());
+    }
+
+    Ok(report)
+}
+
+/// Reintenta solo los archivos que fallaron en `report`. Devuelve un reporte con una etapa
+/// por cada etapa que tenía fallos.
+pub async fn retry_failed(
+    game_path: PathBuf,
+    report: InstallReport,
+    options: InstallOptions,
+) -> Result<InstallReport, ProtonError> {
+    let download_options = options.download_options(&game_path);
+    let downloader = MinecraftDownloader::new(game_path, report.version.clone())
+        .with_options(download_options)
+        .with_keep_going(options.keep_going);
+
+    let mut retry_report = InstallReport {
+        version: report.version,
+        stages: Vec::new(),
+    };
+    for stage in report.stages {
+        if stage.summary.is_success() {
+            continue;
+        }
+        let summary = downloader
+            .retry_failed(stage.summary.failures, options.progress_tx.clone())
+            .await?;
+        retry_report.push(stage.stage, summary);
+    }
+    Ok(retry_report)
+}
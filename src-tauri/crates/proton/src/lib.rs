@@ -1,6 +1,9 @@
+pub mod control;
 pub mod downloaders;
 pub mod errors;
+pub mod installer;
 pub mod manifest;
+pub mod rules;
 pub mod types;
 pub mod utilities;
 
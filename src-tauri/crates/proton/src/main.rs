@@ -1,21 +1,26 @@
 use proton::{
-    downloaders::MinecraftDownloader, errors::ProtonError, manifest::resolve_version_data,
+    errors::ProtonError,
+    installer::{InstallOptions, install},
     types::DownloadProgress,
 };
 use tokio::{sync::mpsc, task};
 #[tokio::main]
 async fn main() -> Result<(), ProtonError> {
     let (tx, mut rx) = mpsc::channel::<DownloadProgress>(100);
-    let mut downloader = MinecraftDownloader::new(
-        std::env::current_dir()?.join("minecraft"),
-        resolve_version_data("1.16.5".to_string()).await?,
-    );
 
     task::spawn(async move {
         while let Some(msg) = rx.recv().await {
             println!("{:?}", msg)
         }
     });
-    downloader.download_natives(Some(tx)).await?;
+    let report = install(
+        std::env::current_dir()?.join("minecraft"),
+        "1.16.5",
+        InstallOptions::default().with_progress(tx),
+    )
+    .await?;
+    for stage in report.stages {
+        println!("{:?}: {:?}", stage.stage, stage.summary);
+    }
     Ok(())
 }
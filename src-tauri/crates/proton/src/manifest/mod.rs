@@ -1,26 +1,37 @@
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::errors::ProtonError;
+use crate::rules::RuleContext;
 use crate::types::{
     MOJANG_MANIFEST_URL, MojangVersionDetails, MojangVersionInfo, MojangVersionManifest,
     NormalizedVersion, VersionAssets,
 };
-use crate::utilities::HTTP_CLIENT;
+use crate::utilities::{DownloadOptions, fetch_metadata};
 
 pub async fn get_manifest() -> Result<MojangVersionManifest, ProtonError> {
-    let res = HTTP_CLIENT
-        .get(MOJANG_MANIFEST_URL)
-        .send()
-        .await?
-        .json::<MojangVersionManifest>()
-        .await?;
-    Ok(res)
+    get_manifest_with_options(&DownloadOptions::default()).await
+}
+
+/// Igual que `get_manifest`, usando los mirrors y la caché de `options`.
+/// En modo offline solo funciona si el manifiesto está en la caché
+pub async fn get_manifest_with_options(
+    options: &DownloadOptions,
+) -> Result<MojangVersionManifest, ProtonError> {
+    // Nada referencia al manifiesto, así que no hay hash con el que comprobarlo
+    fetch_metadata(MOJANG_MANIFEST_URL, None, options).await
 }
 
 pub async fn resolve_version_in_manifest(
     version_id: String,
 ) -> Result<MojangVersionInfo, ProtonError> {
-    let manifest = get_manifest().await?;
+    resolve_version_in_manifest_with_options(version_id, &DownloadOptions::default()).await
+}
+
+pub async fn resolve_version_in_manifest_with_options(
+    version_id: String,
+    options: &DownloadOptions,
+) -> Result<MojangVersionInfo, ProtonError> {
+    let manifest = get_manifest_with_options(options).await?;
 
     manifest
         .versions
@@ -30,12 +41,26 @@ pub async fn resolve_version_in_manifest(
 }
 
 pub async fn resolve_version_data(version_id: String) -> Result<NormalizedVersion, ProtonError> {
-    let version_manifest = HTTP_CLIENT
-        .get(MOJANG_MANIFEST_URL)
-        .send()
-        .await?
-        .json::<MojangVersionManifest>()
-        .await?;
+    resolve_version_data_with_context(version_id, &RuleContext::current()).await
+}
+
+/// Igual que `resolve_version_data`, pero evaluando las reglas contra `ctx`
+/// (otro sistema operativo, features como `is_demo_user`, ...)
+pub async fn resolve_version_data_with_context(
+    version_id: String,
+    ctx: &RuleContext,
+) -> Result<NormalizedVersion, ProtonError> {
+    resolve_version_data_with_options(version_id, ctx, &DownloadOptions::default()).await
+}
+
+/// Igual que `resolve_version_data_with_context`, descargando el manifiesto y el JSON
+/// de la versión con los mirrors y la caché de `options`
+pub async fn resolve_version_data_with_options(
+    version_id: String,
+    ctx: &RuleContext,
+    options: &DownloadOptions,
+) -> Result<NormalizedVersion, ProtonError> {
+    let version_manifest = get_manifest_with_options(options).await?;
 
     let version = version_manifest
         .versions
@@ -44,23 +69,25 @@ pub async fn resolve_version_data(version_id: String) -> Result<NormalizedVersio
         .cloned()
         .ok_or(ProtonError::VersionNotFound(version_id))?;
 
-    let version = HTTP_CLIENT
-        .get(version.url)
-        .send()
-        .await?
-        .json::<MojangVersionDetails>()
-        .await?;
-    NormalizedVersion::try_from(version)
+    let version: MojangVersionDetails =
+        fetch_metadata(&version.url, Some(&version.sha1), options).await?;
+    NormalizedVersion::from_details(version, ctx)
 }
 
 pub async fn resolve_asset_index(
     version: &NormalizedVersion,
 ) -> Result<VersionAssets, ProtonError> {
-    let res = HTTP_CLIENT
-        .get(&version.asset_index.url)
-        .send()
-        .await?
-        .json::<VersionAssets>()
-        .await?;
-    Ok(res)
+    resolve_asset_index_with_options(version, &DownloadOptions::default()).await
+}
+
+pub async fn resolve_asset_index_with_options(
+    version: &NormalizedVersion,
+    options: &DownloadOptions,
+) -> Result<VersionAssets, ProtonError> {
+    fetch_metadata(
+        &version.asset_index.url,
+        Some(&version.asset_index.sha1),
+        options,
+    )
+    .await
 }
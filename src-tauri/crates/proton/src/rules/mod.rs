@@ -0,0 +1,162 @@
+// Evaluación de reglas de los manifiestos de Mojang.
+// Sigue la misma semántica que el launcher oficial: sin reglas todo se permite,
+// con reglas se parte de "disallow" y la última regla que coincide decide.
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::types::{MojangOSRule, MojangRule};
+use crate::utilities::{get_arch_runtime, get_os_name_runtime, get_os_version_runtime};
+
+/// Entorno contra el que se evalúan las reglas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleContext {
+    /// Nombre del sistema tal y como aparece en los manifiestos ("windows", "osx", "linux")
+    pub os_name: String,
+    /// Arquitectura tal y como aparece en los manifiestos ("x86", "x86_64", "arm64", ...)
+    pub arch: String,
+    /// Versión del sistema, comparada contra la regex `os.version`
+    pub os_version: String,
+    /// Features activadas por quien lanza el juego (`is_demo_user`, `has_custom_resolution`, ...)
+    pub features: HashMap<String, bool>,
+}
+
+impl RuleContext {
+    pub fn new(os_name: &str, arch: &str, os_version: &str) -> Self {
+        Self {
+            os_name: os_name.to_string(),
+            arch: arch.to_string(),
+            os_version: os_version.to_string(),
+            features: HashMap::new(),
+        }
+    }
+
+    /// Contexto del sistema actual, sin features activadas
+    pub fn current() -> Self {
+        Self::new(
+            get_os_name_runtime(),
+            get_arch_runtime(),
+            &get_os_version_runtime(),
+        )
+    }
+
+    pub fn with_feature(mut self, feature: &str, enabled: bool) -> Self {
+        self.features.insert(feature.to_string(), enabled);
+        self
+    }
+
+    /// Valor de `${arch}` en los clasificadores de nativos antiguos ("natives-windows-${arch}")
+    pub fn arch_bits(&self) -> &'static str {
+        if self.arch == "x86" { "32" } else { "64" }
+    }
+}
+
+/// Indica si un conjunto de reglas permite la librería o el argumento
+pub fn rules_allow(rules: &[MojangRule], ctx: &RuleContext) -> bool {
+    if rules.is_empty() {
+        return true;
+    }
+
+    let mut allow = false;
+    for rule in rules {
+        if !rule_matches(rule, ctx) {
+            continue;
+        }
+        match rule.action.as_str() {
+            "allow" => allow = true,
+            "disallow" => allow = false,
+            _ => {}
+        }
+    }
+    allow
+}
+
+fn rule_matches(rule: &MojangRule, ctx: &RuleContext) -> bool {
+    if let Some(os) = &rule.os
+        && !os_matches(os, ctx)
+    {
+        return false;
+    }
+
+    // Las features que no fueron indicadas se consideran desactivadas
+    if let Some(features) = &rule.features {
+        return features.iter().all(|(feature, expected)| {
+            ctx.features.get(feature).copied().unwrap_or(false) == *expected
+        });
+    }
+    true
+}
+
+fn os_matches(os: &MojangOSRule, ctx: &RuleContext) -> bool {
+    if os.name.as_ref().is_some_and(|name| name != &ctx.os_name) {
+        return false;
+    }
+    if os.arch.as_ref().is_some_and(|arch| arch != &ctx.arch) {
+        return false;
+    }
+    if let Some(version) = &os.version {
+        // Una regex inválida no descarta la regla, igual que en el launcher oficial
+        if let Ok(regex) = Regex::new(version)
+            && !regex.is_match(&ctx.os_version)
+        {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(json: &str) -> MojangRule {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_osx_natives_match() {
+        let rules = vec![
+            rule(r#"{"action": "allow"}"#),
+            rule(r#"{"action": "disallow", "os": {"name": "osx"}}"#),
+        ];
+        let mac = RuleContext::new("osx", "x86_64", "14.1");
+        let linux = RuleContext::new("linux", "x86_64", "6.1");
+        assert!(!rules_allow(&rules, &mac));
+        assert!(rules_allow(&rules, &linux));
+    }
+
+    #[test]
+    fn test_last_matching_rule_wins() {
+        let rules = vec![
+            rule(r#"{"action": "disallow", "os": {"name": "osx"}}"#),
+            rule(r#"{"action": "allow", "os": {"name": "osx", "version": "^10\\.5\\.\\d$"}}"#),
+        ];
+        assert!(rules_allow(
+            &rules,
+            &RuleContext::new("osx", "x86_64", "10.5.8")
+        ));
+        assert!(!rules_allow(
+            &rules,
+            &RuleContext::new("osx", "x86_64", "10.9.0")
+        ));
+    }
+
+    #[test]
+    fn test_arch_and_features() {
+        let x86_only = vec![rule(r#"{"action": "allow", "os": {"arch": "x86"}}"#)];
+        assert!(rules_allow(
+            &x86_only,
+            &RuleContext::new("windows", "x86", "10.0")
+        ));
+        assert!(!rules_allow(
+            &x86_only,
+            &RuleContext::new("windows", "x86_64", "10.0")
+        ));
+
+        let demo = vec![rule(
+            r#"{"action": "allow", "features": {"is_demo_user": true}}"#,
+        )];
+        let ctx = RuleContext::new("linux", "x86_64", "6.1");
+        assert!(!rules_allow(&demo, &ctx));
+        assert!(rules_allow(&demo, &ctx.with_feature("is_demo_user", true)));
+    }
+}
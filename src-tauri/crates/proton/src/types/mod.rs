@@ -1,12 +1,12 @@
 use crate::errors::ProtonError;
-use crate::utilities::get_os_name_runtime;
+use crate::rules::{RuleContext, rules_allow};
+use minecraft_launcher_core::version_manager::endpoints;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
-// URLs de los manifiestos oficiales de Mojang
-pub const MOJANG_MANIFEST_URL: &str =
-    "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
-pub const RESOURCES_BASE_URL: &str = "https://resources.download.minecraft.net/";
+// URLs de los manifiestos oficiales de Mojang. Los mirrors se configuran con `Endpoints`
+pub const MOJANG_MANIFEST_URL: &str = endpoints::VERSION_MANIFEST_URL;
+pub const RESOURCES_BASE_URL: &str = endpoints::RESOURCES_URL;
 
 // Tipos de versión
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -214,6 +214,7 @@ pub struct NormalizedVersion {
     pub natives: Vec<NativeLibrary>,
     pub arguments: NormalizedArguments,
     pub requires_extraction: Vec<ExtractionHint>,
+    pub logging: Option<LoggingConfig>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -231,6 +232,17 @@ pub struct AssetIndex {
     pub size: u64,
 }
 
+/// Configuración de log4j del cliente, se guarda en `assets/log_configs/<id>`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LoggingConfig {
+    pub id: String,
+    pub url: String,
+    pub sha1: String,
+    pub size: u64,
+    /// Argumento JVM, con `${path}` como ruta del archivo
+    pub argument: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Library {
     pub name: String,
@@ -282,6 +294,64 @@ pub enum DownloadProgressType {
     Native,
 }
 
+/// Resultado de descargar un archivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadOutcome {
+    Downloaded,
+    /// El archivo ya existía con el hash correcto
+    AlreadyPresent,
+    /// El archivo se enlazó desde el almacén de objetos compartido, sin descargarlo
+    Linked,
+}
+
+/// Cuántos archivos se descargaron en una etapa, cuántos ya estaban y cuáles fallaron
+#[derive(Debug, Default)]
+pub struct StageSummary {
+    pub downloaded: usize,
+    pub already_present: usize,
+    pub linked: usize,
+    pub failures: Vec<FailedDownload>,
+}
+
+/// Un archivo que no se pudo descargar
+#[derive(Debug)]
+pub struct FailedDownload {
+    pub name: String,
+    pub url: String,
+    pub path: PathBuf,
+    pub sha1: String,
+    pub download_type: DownloadProgressType,
+    pub attempts: usize,
+    pub error: ProtonError,
+}
+
+impl StageSummary {
+    pub fn record(&mut self, outcome: DownloadOutcome) {
+        match outcome {
+            DownloadOutcome::Downloaded => self.downloaded += 1,
+            DownloadOutcome::AlreadyPresent => self.already_present += 1,
+            DownloadOutcome::Linked => self.linked += 1,
+        }
+    }
+
+    /// Si la etapa cambió algo en disco
+    pub fn changed(&self) -> bool {
+        self.downloaded > 0 || self.linked > 0
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl From<DownloadOutcome> for StageSummary {
+    fn from(outcome: DownloadOutcome) -> Self {
+        let mut summary = StageSummary::default();
+        summary.record(outcome);
+        summary
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct VersionAssets {
     pub objects: HashMap<String, Asset>,
@@ -321,8 +391,26 @@ impl TryFrom<MojangVersionDetails> for NormalizedVersion {
     type Error = ProtonError;
 
     fn try_from(mojang_version: MojangVersionDetails) -> Result<Self, Self::Error> {
+        NormalizedVersion::from_details(mojang_version, &RuleContext::current())
+    }
+}
+
+impl NormalizedVersion {
+    /// Normaliza una versión evaluando sus reglas contra `ctx`.
+    /// Las features del contexto deciden los argumentos condicionales (`--demo`, resolución, ...)
+    pub fn from_details(
+        mojang_version: MojangVersionDetails,
+        ctx: &RuleContext,
+    ) -> Result<Self, ProtonError> {
         let assets = mojang_version.asset_index;
         let downloads = mojang_version.downloads;
+        let logging = mojang_version.logging.map(|logging| LoggingConfig {
+            id: logging.client.file.id,
+            url: logging.client.file.url,
+            sha1: logging.client.file.sha1,
+            size: logging.client.file.size,
+            argument: logging.client.argument,
+        });
 
         // Convertir librerías
         let mut libraries = Vec::new();
@@ -331,7 +419,7 @@ impl TryFrom<MojangVersionDetails> for NormalizedVersion {
 
         for lib in mojang_version.libraries {
             // Verificar reglas de la librería
-            if !library_applies(&lib) {
+            if !rules_allow(&lib.rules, ctx) {
                 continue;
             }
 
@@ -347,11 +435,12 @@ impl TryFrom<MojangVersionDetails> for NormalizedVersion {
 
             // Procesar nativos
             if let Some(natives_map) = lib.natives {
-                if let Some(classifier) = natives_map.get(get_os_name_runtime()) {
-                    if let Some(native_artifact) = lib.downloads.classifiers.get(classifier) {
+                if let Some(classifier) = natives_map.get(&ctx.os_name) {
+                    let classifier = classifier.replace("${arch}", ctx.arch_bits());
+                    if let Some(native_artifact) = lib.downloads.classifiers.get(&classifier) {
                         natives.push(NativeLibrary {
                             name: lib.name,
-                            classifier: classifier.clone(),
+                            classifier,
                             url: native_artifact.url.clone(),
                             sha1: native_artifact.sha1.clone(),
                             size: native_artifact.size,
@@ -369,8 +458,8 @@ impl TryFrom<MojangVersionDetails> for NormalizedVersion {
 
         // Convertir argumentos
         let arguments = match (mojang_version.arguments, mojang_version.minecraft_arguments) {
-            (Some(args), _) => normalize_arguments(args),
-            (None, Some(legacy_args)) => parse_legacy_arguments(legacy_args),
+            (Some(args), _) => normalize_arguments(args, ctx),
+            (None, Some(legacy_args)) => parse_legacy_arguments(legacy_args, ctx),
             (None, None) => NormalizedArguments {
                 game: Vec::new(),
                 jvm: Vec::new(),
@@ -405,56 +494,21 @@ impl TryFrom<MojangVersionDetails> for NormalizedVersion {
             natives,
             arguments,
             requires_extraction,
+            logging,
         })
     }
 }
 
 // Funciones helper
-fn library_applies(lib: &MojangLibrary) -> bool {
-    if lib.rules.is_empty() {
-        return true;
-    }
-
-    let os_name = get_os_name_runtime();
-    let mut allow = false;
-
-    for rule in &lib.rules {
-        let applies = match &rule.os {
-            Some(os_rule) => {
-                let name_match = os_rule.name.as_ref().map_or(true, |n| n == os_name);
-                name_match
-            }
-            None => true,
-        };
-
-        match rule.action.as_str() {
-            "allow" => {
-                if applies {
-                    allow = true;
-                }
-            }
-            "disallow" => {
-                if applies {
-                    return false;
-                }
-            }
-            _ => {}
-        }
-    }
-
-    allow
-}
-
-fn normalize_arguments(args: MojangArguments) -> NormalizedArguments {
-    let game = flatten_arguments(args.game);
-    let jvm = flatten_arguments(args.jvm);
+fn normalize_arguments(args: MojangArguments, ctx: &RuleContext) -> NormalizedArguments {
+    let game = flatten_arguments(args.game, ctx);
+    let jvm = flatten_arguments(args.jvm, ctx);
 
     NormalizedArguments { game, jvm }
 }
 
-fn flatten_arguments(args: Vec<MojangArgumentValue>) -> Vec<String> {
+fn flatten_arguments(args: Vec<MojangArgumentValue>, ctx: &RuleContext) -> Vec<String> {
     let mut result = Vec::new();
-    let os_name = get_os_name_runtime();
 
     for arg in args {
         match arg {
@@ -462,7 +516,7 @@ fn flatten_arguments(args: Vec<MojangArgumentValue>) -> Vec<String> {
                 result.push(s);
             }
             MojangArgumentValue::Conditional { rules, value } => {
-                if rule_set_applies(&rules, os_name) {
+                if rules_allow(&rules, ctx) {
                     match value {
                         MojangConditionalValue::Single(s) => result.push(s),
                         MojangConditionalValue::Multiple(v) => result.extend(v),
@@ -475,38 +529,7 @@ fn flatten_arguments(args: Vec<MojangArgumentValue>) -> Vec<String> {
     result
 }
 
-fn rule_set_applies(rules: &[MojangRule], os_name: &str) -> bool {
-    if rules.is_empty() {
-        return true;
-    }
-
-    let mut allow = false;
-
-    for rule in rules {
-        let applies = match &rule.os {
-            Some(os_rule) => os_rule.name.as_ref().map_or(true, |n| n == os_name),
-            None => true,
-        };
-
-        match rule.action.as_str() {
-            "allow" => {
-                if applies {
-                    allow = true;
-                }
-            }
-            "disallow" => {
-                if applies {
-                    return false;
-                }
-            }
-            _ => {}
-        }
-    }
-
-    allow
-}
-
-fn parse_legacy_arguments(args: String) -> NormalizedArguments {
+fn parse_legacy_arguments(args: String, ctx: &RuleContext) -> NormalizedArguments {
     let mut game_args = Vec::new();
     let mut jvm_args = Vec::new();
 
@@ -515,6 +538,27 @@ fn parse_legacy_arguments(args: String) -> NormalizedArguments {
         game_args.push(arg.to_string());
     }
 
+    // El formato antiguo no tiene argumentos condicionales, así que se añaden según las features
+    if ctx.features.get("is_demo_user").copied().unwrap_or(false) {
+        game_args.push("--demo".to_string());
+    }
+    if ctx
+        .features
+        .get("has_custom_resolution")
+        .copied()
+        .unwrap_or(false)
+    {
+        game_args.extend(
+            [
+                "--width",
+                "${resolution_width}",
+                "--height",
+                "${resolution_height}",
+            ]
+            .map(String::from),
+        );
+    }
+
     // Argumentos JVM estándar para versiones antiguas
     jvm_args.push("-Djava.library.path=${natives_directory}".to_string());
     jvm_args.push("-cp".to_string());
@@ -1,32 +1,213 @@
+use crate::control::DownloadControl;
 use crate::errors::ProtonError;
+use crate::types::DownloadOutcome;
 use async_zip::tokio::read::fs::ZipFileReader;
 use futures::TryStreamExt;
 use hex;
 use log::{error, info, warn};
+use minecraft_launcher_core::json::Sha1Sum;
+use minecraft_launcher_core::network::NetworkSettings;
+use minecraft_launcher_core::version_manager::{
+    downloader::{
+        limiter::NetworkLimiter,
+        retry::{RetryPolicy, parse_retry_after},
+    },
+    endpoints::{Endpoints, Service},
+    metadata_cache::MetadataCache,
+    object_store::ObjectStore,
+};
 use once_cell::sync::Lazy;
-use reqwest::Client;
+use reqwest::header::{CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE, RETRY_AFTER};
+use reqwest::{Client, Response, StatusCode};
 use ring::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY};
-use std::path::PathBuf;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
 use tokio::{
-    fs::{File, create_dir_all, remove_file, rename},
+    fs::{File, OpenOptions, create_dir_all, read_to_string, remove_file, rename, write},
     io::{AsyncReadExt, AsyncWriteExt},
     time::Duration,
 };
 
+pub const USER_AGENT: &str = "Cubic Proton/1.0";
+
+/// Cliente con los ajustes de red por defecto, usado si no se configura otro
 pub static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
-    Client::builder()
-        .user_agent("Cubic Proton/1.0")
-        .build()
-        .expect("Failed to build reqwest client")
+    create_client(&NetworkSettings::default()).expect("Failed to build reqwest client")
 });
 
+/// Crea un cliente con `settings` (proxy, timeouts, certificados...). Si no indican
+/// un user agent, se usa el de proton
+pub fn create_client(settings: &NetworkSettings) -> Result<Client, ProtonError> {
+    let client = match settings.user_agent {
+        Some(_) => settings.create_client()?,
+        None => settings
+            .clone()
+            .with_user_agent(USER_AGENT)
+            .create_client()?,
+    };
+    Ok(client)
+}
+
 const MAX_DOWNLOAD_ATTEMPTS: usize = 3;
 
+/// Un lock por archivo de destino, para que dos descargas del mismo archivo (por ejemplo, dos
+/// instalaciones en el mismo `game_path`) no escriban a la vez en su `.part`
+static DOWNLOAD_LOCKS: Lazy<Mutex<HashMap<PathBuf, Weak<tokio::sync::Mutex<()>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn download_lock(path: &Path) -> Arc<tokio::sync::Mutex<()>> {
+    let mut locks = DOWNLOAD_LOCKS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    // Solo se guardan los locks en uso
+    locks.retain(|_, lock| lock.strong_count() > 0);
+    if let Some(lock) = locks.get(path).and_then(Weak::upgrade) {
+        return lock;
+    }
+    let lock = Arc::new(tokio::sync::Mutex::new(()));
+    locks.insert(path.to_path_buf(), Arc::downgrade(&lock));
+    lock
+}
+
+/// Política de reintentos por defecto: 3 intentos, empezando con 100ms de espera
+pub fn default_retry_policy() -> RetryPolicy {
+    RetryPolicy::default()
+        .with_max_attempts(MAX_DOWNLOAD_ATTEMPTS)
+        .with_base_delay(Duration::from_millis(100))
+}
+
+/// Ajustes compartidos por todas las descargas de un downloader o instalación
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Permite cancelar, pausar o reanudar las descargas
+    pub control: DownloadControl,
+    /// Cuántas veces y con qué espera se reintenta cada archivo
+    pub retry_policy: RetryPolicy,
+    /// Límite de ancho de banda y de conexiones por host. Por defecto se comparte
+    /// con el resto de descargas del proceso, incluidas las de `minecraft-launcher-core`
+    pub limiter: NetworkLimiter,
+    /// Mirrors que se prueban, en orden, antes que los servidores de Mojang
+    pub endpoints: Endpoints,
+    /// Copia en disco del manifiesto y los JSON de versión. En modo offline solo se usa
+    /// esta copia y los archivos que ya están descargados
+    pub metadata_cache: MetadataCache,
+    /// Cliente HTTP de todas las peticiones. Por defecto, `HTTP_CLIENT`
+    pub client: Client,
+    /// Almacén compartido entre instancias. Los archivos que ya tiene se enlazan en vez de
+    /// descargarse, y los descargados se añaden a él
+    pub object_store: Option<ObjectStore>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            control: DownloadControl::new(),
+            retry_policy: default_retry_policy(),
+            limiter: NetworkLimiter::global(),
+            endpoints: Endpoints::default(),
+            metadata_cache: MetadataCache::disabled(),
+            client: HTTP_CLIENT.clone(),
+            object_store: None,
+        }
+    }
+}
+
+impl DownloadOptions {
+    pub fn with_control(mut self, control: DownloadControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_limiter(mut self, limiter: NetworkLimiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    pub fn with_metadata_cache(mut self, metadata_cache: MetadataCache) -> Self {
+        self.metadata_cache = metadata_cache;
+        self
+    }
+
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Usa un cliente creado con `settings`
+    pub fn with_network_settings(self, settings: &NetworkSettings) -> Result<Self, ProtonError> {
+        Ok(self.with_client(create_client(settings)?))
+    }
+
+    /// Las referencias se registran a nombre de la instancia del almacén,
+    /// ver `ObjectStore::for_instance`
+    pub fn with_object_store(mut self, object_store: Option<ObjectStore>) -> Self {
+        self.object_store = object_store;
+        self
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.metadata_cache.is_offline()
+    }
+}
+
+/// Descarga y parsea un JSON de metadatos (manifiesto, versión, índice de assets) a través
+/// de la caché de `options`, probando antes los mirrors si `url` apunta a un servidor oficial.
+///
+/// Si se conoce el SHA-1 que lo referencia (`expected_sha1`), una respuesta que no coincide
+/// falla con `ProtonError::ChecksumMismatch` y no se guarda en la caché
+pub async fn fetch_metadata<T: DeserializeOwned>(
+    url: &str,
+    expected_sha1: Option<&str>,
+    options: &DownloadOptions,
+) -> Result<T, ProtonError> {
+    let service = Service::from_url(url).unwrap_or(Service::VersionMeta);
+    let expected = expected_sha1
+        .map(|sha1| {
+            Sha1Sum::try_from(sha1.to_lowercase())
+                .map_err(|_| ProtonError::Other(format!("Invalid SHA-1 for {}: {}", url, sha1)))
+        })
+        .transpose()?;
+    Ok(options
+        .metadata_cache
+        .fetch_json(
+            &options.client,
+            &options.endpoints,
+            service,
+            url,
+            expected.as_ref(),
+        )
+        .await?)
+}
+
 pub async fn download_file(
     url: String,
     path: &PathBuf,
     expected_hash: String,
-) -> Result<(), ProtonError> {
+) -> Result<DownloadOutcome, ProtonError> {
+    download_file_with_options(url, path, expected_hash, &DownloadOptions::default()).await
+}
+
+/// Igual que `download_file`, pero usando `options`: se puede pausar o cancelar,
+/// los reintentos siguen su política y se respetan sus límites de red.
+/// Al cancelar se borra el archivo parcial.
+pub async fn download_file_with_options(
+    url: String,
+    path: &PathBuf,
+    expected_hash: String,
+    options: &DownloadOptions,
+) -> Result<DownloadOutcome, ProtonError> {
     // Validaciones iniciales
     if url.is_empty() || expected_hash.is_empty() {
         return Err(ProtonError::Other(
@@ -34,6 +215,10 @@ pub async fn download_file(
         ));
     }
 
+    // Quien espera encuentra el archivo ya descargado por el otro
+    let lock = download_lock(path);
+    let _guard = lock.lock().await;
+
     // Verificar si el archivo ya existe y tiene el hash correcto
     if path.exists() {
         info!("File already exists, verifying hash: {:?}", path);
@@ -41,32 +226,39 @@ pub async fn download_file(
         match verify_file_hash(path, &expected_hash).await {
             Ok(true) => {
                 info!("File already exists with correct hash: {:?}", path);
-                return Ok(());
+                add_to_store(path, &expected_hash, options).await;
+                return Ok(DownloadOutcome::AlreadyPresent);
             }
+            // El archivo se conserva hasta que la descarga verificada lo reemplaza,
+            // así un JSON instalado no se pierde si la descarga falla
             Ok(false) => {
                 warn!(
                     "File exists but hash doesn't match, re-downloading: {:?}",
                     path
                 );
-                // Eliminar archivo corrupto
-                if let Err(e) = remove_file(path).await {
-                    warn!("Failed to remove corrupted file: {}", e);
-                }
             }
             Err(e) => {
                 warn!("Failed to verify existing file hash: {}, re-downloading", e);
-                // Eliminar archivo que no se puede verificar
-                if let Err(e) = remove_file(path).await {
-                    warn!("Failed to remove unverifiable file: {}", e);
-                }
             }
         }
     }
 
-    // Generar nombre único para archivo temporal
-    let temp_file = path.with_extension(&format!("tmp.{}", uuid::Uuid::new_v4()));
+    // El almacén también sirve sin red
+    if link_from_store(path, &expected_hash, options).await {
+        return Ok(DownloadOutcome::Linked);
+    }
+
+    if options.is_offline() {
+        return Err(ProtonError::Offline { url });
+    }
+
+    // La descarga a medias se guarda con un nombre fijo, para poder continuarla más tarde
+    let part_file = sidecar_path(path, "part");
+    let validator_file = sidecar_path(path, "part.validator");
 
-    for attempt in 1..=MAX_DOWNLOAD_ATTEMPTS {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
         // Crear directorio padre si no existe
         if let Some(parent_dir) = path.parent() {
             if let Err(e) = create_dir_all(parent_dir).await {
@@ -75,117 +267,250 @@ pub async fn download_file(
             }
         }
 
-        // Limpiar archivo temporal si existe de intentos anteriores
-        if temp_file.exists() {
-            let _ = remove_file(&temp_file).await;
-        }
-
-        // Realizar petición HTTP
-        let response = match HTTP_CLIENT.get(&url).send().await {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    warn!("HTTP error on attempt {}: {}", attempt, resp.status());
-                    continue;
-                }
-                resp
-            }
-            Err(e) => {
-                warn!("Request failed on attempt {}: {}", attempt, e);
-                if attempt == MAX_DOWNLOAD_ATTEMPTS {
-                    return Err(ProtonError::RequestError(e));
-                }
-                continue;
-            }
-        };
-
-        // Crear archivo temporal
-        let mut file = match File::create(&temp_file).await {
-            Ok(f) => f,
-            Err(e) => {
-                error!("Failed to create temp file {:?}: {}", temp_file, e);
-                return Err(ProtonError::IoError(e));
-            }
-        };
-
-        // Prepara para cálculo de hash SHA1
-        let mut sha1_context = Context::new(&SHA1_FOR_LEGACY_USE_ONLY);
-        let mut stream = response.bytes_stream();
-        let mut bytes_written = 0u64;
+        let connection = options.limiter.acquire_connection(&url).await;
+        let result = download_to_partial(&url, &part_file, &validator_file, options).await;
+        drop(connection);
 
-        let write_result: Result<(), ProtonError> = async {
-            loop {
-                match stream.try_next().await {
-                    Ok(Some(chunk)) => {
-                        sha1_context.update(&chunk);
-                        file.write_all(&chunk).await?;
-                        bytes_written += chunk.len() as u64;
-                    }
-                    Ok(None) => break,
-                    Err(e) => return Err(ProtonError::RequestError(e)),
-                }
-            }
-            file.flush().await?;
-            Ok(())
-        }
-        .await;
-
-        match write_result {
-            Ok(()) => {
+        let error = match result {
+            Ok(sha1_context) => {
                 // Verificar hash
                 let actual_hash = hex::encode(sha1_context.finish());
                 if actual_hash == expected_hash {
-                    // Mover archivo temporal al destino final
-                    match rename(&temp_file, &path).await {
+                    // Mover archivo parcial al destino final
+                    let _ = remove_file(&validator_file).await;
+                    match rename(&part_file, &path).await {
                         Ok(()) => {
                             info!("File downloaded successfully: {:?}", path);
-                            return Ok(());
+                            add_to_store(path, &expected_hash, options).await;
+                            return Ok(DownloadOutcome::Downloaded);
                         }
                         Err(e) => {
-                            error!("Failed to rename temp file: {}", e);
-                            let _ = remove_file(&temp_file).await;
+                            error!("Failed to rename partial file: {}", e);
+                            let _ = remove_file(&part_file).await;
                             return Err(ProtonError::IoError(e));
                         }
                     }
-                } else {
-                    warn!(
-                        "Hash mismatch on attempt {}: expected {}, got {}",
-                        attempt, expected_hash, actual_hash
-                    );
                 }
+
+                warn!(
+                    "Hash mismatch on attempt {}: expected {}, got {}",
+                    attempt, expected_hash, actual_hash
+                );
+                // El contenido no sirve, el siguiente intento empieza de cero
+                let _ = remove_file(&part_file).await;
+                let _ = remove_file(&validator_file).await;
+                ProtonError::ChecksumMismatch {
+                    url: url.clone(),
+                    expected: expected_hash.clone(),
+                    actual: actual_hash,
+                }
+            }
+            Err(ProtonError::Cancelled) => {
+                info!("Download cancelled: {:?}", path);
+                let _ = remove_file(&part_file).await;
+                let _ = remove_file(&validator_file).await;
+                return Err(ProtonError::Cancelled);
             }
             Err(e) => {
-                warn!("Write error on attempt {}: {}", attempt, e);
-                if attempt == MAX_DOWNLOAD_ATTEMPTS {
-                    // Limpiar archivo temporal antes de retornar error
-                    let _ = remove_file(&temp_file).await;
-                    return Err(e);
-                }
+                // El archivo parcial se conserva, el siguiente intento continúa desde ahí
+                warn!("Download interrupted on attempt {}: {}", attempt, e);
+                e
             }
-        }
+        };
 
-        // Limpiar archivo temporal antes del siguiente intento
-        if temp_file.exists() {
-            if let Err(e) = remove_file(&temp_file).await {
-                warn!("Failed to remove temp file: {}", e);
+        match options
+            .retry_policy
+            .next_delay(attempt, error.failure_kind())
+        {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => {
+                return Err(ProtonError::DownloadFailed {
+                    url,
+                    attempts: attempt,
+                    source: Box::new(error),
+                });
             }
         }
+    }
+}
 
-        // Opcional: delay exponencial entre intentos
-        if attempt < MAX_DOWNLOAD_ATTEMPTS {
-            let delay = Duration::from_millis(100 * (1 << (attempt - 1)));
-            tokio::time::sleep(delay).await;
+/// Coloca en `path` el objeto del almacén con ese hash, si lo tiene.
+/// Un error del almacén solo se registra, y el archivo se descarga
+async fn link_from_store(path: &Path, expected_hash: &str, options: &DownloadOptions) -> bool {
+    let Some((store, sha1)) = store_entry(expected_hash, options) else {
+        return false;
+    };
+    match store.link_into(&sha1, path).await {
+        Ok(Some(kind)) => {
+            info!("Linked {:?} from the object store ({:?})", path, kind);
+            true
+        }
+        Ok(None) => false,
+        Err(e) => {
+            warn!("Failed to link {:?} from the object store: {}", path, e);
+            false
         }
     }
+}
+
+/// Añade al almacén un archivo ya verificado. La descarga ya terminó, así que los errores solo se registran
+async fn add_to_store(path: &Path, expected_hash: &str, options: &DownloadOptions) {
+    let Some((store, sha1)) = store_entry(expected_hash, options) else {
+        return;
+    };
+    if let Err(e) = store.insert(path, &sha1).await {
+        warn!("Failed to add {:?} to the object store: {}", path, e);
+    }
+}
 
-    Err(ProtonError::HashMismatch)
+fn store_entry<'a>(
+    expected_hash: &str,
+    options: &'a DownloadOptions,
+) -> Option<(&'a ObjectStore, Sha1Sum)> {
+    let store = options.object_store.as_ref()?;
+    let sha1 = Sha1Sum::try_from(expected_hash.to_lowercase()).ok()?;
+    Some((store, sha1))
 }
 
-// Función auxiliar para verificar el hash de un archivo existente
-async fn verify_file_hash(path: &PathBuf, expected_hash: &str) -> Result<bool, ProtonError> {
-    let mut file = File::open(path).await.map_err(ProtonError::IoError)?;
+/// Ruta junto a `path` con `extension` añadida al nombre (`client.jar` -> `client.jar.part`)
+fn sidecar_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
 
+/// Descarga `url` en `part_file`, continuando lo que ya haya en disco con una petición `Range`.
+/// Devuelve el SHA-1 de todo el archivo.
+///
+/// `validator_file` guarda el ETag o Last-Modified de la respuesta original, que se envía como
+/// `If-Range` para que el servidor mande el archivo completo si cambió desde entonces.
+async fn download_to_partial(
+    url: &str,
+    part_file: &Path,
+    validator_file: &Path,
+    options: &DownloadOptions,
+) -> Result<Context, ProtonError> {
+    options.control.checkpoint().await?;
     let mut sha1_context = Context::new(&SHA1_FOR_LEGACY_USE_ONLY);
+    let offset = if part_file.exists() {
+        hash_file(part_file, &mut sha1_context).await?
+    } else {
+        0
+    };
+
+    let validator = if offset > 0 {
+        read_to_string(validator_file).await.ok()
+    } else {
+        None
+    };
+    let request = |url: &str| {
+        let mut request = options.client.get(url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+            if let Some(validator) = &validator {
+                request = request.header(IF_RANGE, validator.trim());
+            }
+        }
+        request
+    };
+    let response = match Service::from_url(url) {
+        Some(service) => options.endpoints.send_with(service, url, request).await?,
+        None => request(url).send().await?,
+    };
+
+    // El parcial ya estaba completo
+    if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok(sha1_context);
+    }
+    let response = check_status(url, response)?;
+
+    let mut file = if offset > 0 && response.status() == StatusCode::PARTIAL_CONTENT {
+        if content_range_start(&response) != Some(offset) {
+            let _ = remove_file(part_file).await;
+            return Err(ProtonError::Other(format!(
+                "Unexpected Content-Range resuming {} from byte {}",
+                url, offset
+            )));
+        }
+        info!("Resuming download of {} from byte {}", url, offset);
+        OpenOptions::new().append(true).open(part_file).await?
+    } else {
+        if offset > 0 {
+            info!("Server ignored the range request, restarting {}", url);
+        }
+        sha1_context = Context::new(&SHA1_FOR_LEGACY_USE_ONLY);
+        match response_validator(&response) {
+            Some(validator) => write(validator_file, validator).await?,
+            None => {
+                let _ = remove_file(validator_file).await;
+            }
+        }
+        File::create(part_file).await?
+    };
+
+    let mut stream = response.bytes_stream();
+    let write_result: Result<(), ProtonError> = async {
+        while let Some(chunk) = stream.try_next().await? {
+            options.control.checkpoint().await?;
+            options.limiter.consume(chunk.len()).await;
+            sha1_context.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        Ok(())
+    }
+    .await;
+    // Lo recibido hasta el error se queda en disco
+    file.flush().await?;
+    write_result?;
+
+    Ok(sha1_context)
+}
+
+/// Convierte las respuestas de error en `ProtonError::HttpStatus`, guardando el `Retry-After`
+fn check_status(url: &str, response: Response) -> Result<Response, ProtonError> {
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    Err(ProtonError::HttpStatus {
+        url: url.to_string(),
+        status,
+        retry_after,
+    })
+}
+
+/// Primer byte de una respuesta `206`, según su `Content-Range: bytes <start>-<end>/<total>`
+fn content_range_start(response: &Response) -> Option<u64> {
+    let value = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// Validador fuerte de la respuesta, los ETag débiles no sirven para `If-Range`
+fn response_validator(response: &Response) -> Option<String> {
+    let headers = response.headers();
+    if let Some(etag) = headers.get(ETAG).and_then(|v| v.to_str().ok())
+        && !etag.starts_with("W/")
+    {
+        return Some(etag.to_string());
+    }
+    headers
+        .get(LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .map(ToString::to_string)
+}
+
+/// Añade el contenido de `path` a `sha1_context` y devuelve los bytes leídos
+async fn hash_file(path: &Path, sha1_context: &mut Context) -> Result<u64, ProtonError> {
+    let mut file = File::open(path).await.map_err(ProtonError::IoError)?;
     let mut buffer = [0u8; 8192]; // Buffer de 8KB para lectura eficiente
+    let mut total = 0u64;
 
     loop {
         let bytes_read = file.read(&mut buffer).await.map_err(ProtonError::IoError)?;
@@ -195,8 +520,17 @@ async fn verify_file_hash(path: &PathBuf, expected_hash: &str) -> Result<bool, P
         }
 
         sha1_context.update(&buffer[..bytes_read]);
+        total += bytes_read as u64;
     }
 
+    Ok(total)
+}
+
+// Función auxiliar para verificar el hash de un archivo existente
+async fn verify_file_hash(path: &PathBuf, expected_hash: &str) -> Result<bool, ProtonError> {
+    let mut sha1_context = Context::new(&SHA1_FOR_LEGACY_USE_ONLY);
+    hash_file(path, &mut sha1_context).await?;
+
     let actual_hash = hex::encode(sha1_context.finish());
     Ok(actual_hash == expected_hash)
 }
@@ -252,8 +586,8 @@ pub fn get_os_name_runtime() -> &'static str {
         | Type::Void
         | Type::NixOS => "linux",
 
-        // macOS
-        Type::Macos => "macos",
+        // macOS ("osx" es el nombre que usan los manifiestos de Mojang)
+        Type::Macos => "osx",
 
         // Windows
         Type::Windows => "windows",
@@ -265,3 +599,309 @@ pub fn get_os_name_runtime() -> &'static str {
         }
     }
 }
+
+/// Arquitectura del sistema con los nombres que usan los manifiestos de Mojang
+pub fn get_arch_runtime() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86" => "x86",
+        "x86_64" => "x86_64",
+        "aarch64" => "arm64",
+        other => other,
+    }
+}
+
+/// Versión del sistema, usada por las reglas `os.version`
+pub fn get_os_version_runtime() -> String {
+    os_info::get().version().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minecraft_launcher_core::version_manager::{
+        downloader::retry::FailureKind, endpoints::LIBRARIES_URL,
+    };
+    use tokio::net::TcpListener;
+
+    fn test_body() -> Vec<u8> {
+        (0..64 * 1024).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn sha1(data: &[u8]) -> String {
+        hex::encode(ring::digest::digest(&SHA1_FOR_LEGACY_USE_ONLY, data))
+    }
+
+    fn test_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("proton-{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("file.jar")
+    }
+
+    /// Servidor HTTP mínimo que corta la primera respuesta a la mitad.
+    /// Si `ranges` es true, responde a las peticiones `Range` con `206`.
+    /// Devuelve la URL y la cabecera `Range` de cada petición recibida.
+    async fn serve(body: Vec<u8>, ranges: bool) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.jar", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = socket.read(&mut buffer).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..n]);
+                }
+
+                let request = String::from_utf8_lossy(&request).to_string();
+                let range = request.lines().find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("range")
+                        .then(|| value.trim().to_string())
+                });
+                let first = {
+                    let mut received = received.lock().unwrap();
+                    received.push(range.clone());
+                    received.len() == 1
+                };
+
+                let start = range
+                    .filter(|_| ranges)
+                    .and_then(|r| r.strip_prefix("bytes=")?.strip_suffix('-')?.parse().ok())
+                    .unwrap_or(0usize);
+                let status = if start > 0 {
+                    "206 Partial Content"
+                } else {
+                    "200 OK"
+                };
+                let mut head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nETag: \"v1\"\r\nConnection: close\r\n",
+                    status,
+                    body.len() - start
+                );
+                if start > 0 {
+                    head.push_str(&format!(
+                        "Content-Range: bytes {}-{}/{}\r\n",
+                        start,
+                        body.len() - 1,
+                        body.len()
+                    ));
+                }
+                head.push_str("\r\n");
+
+                let end = if first { body.len() / 2 } else { body.len() };
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&body[start..end]).await;
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_resume_with_range() {
+        let body = test_body();
+        let (url, requests) = serve(body.clone(), true).await;
+        let path = test_path("resume");
+
+        let outcome = download_file(url, &path, sha1(&body)).await.unwrap();
+        assert_eq!(outcome, DownloadOutcome::Downloaded);
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert!(!sidecar_path(&path, "part").exists());
+        assert!(!sidecar_path(&path, "part.validator").exists());
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(
+            requests,
+            vec![None, Some(format!("bytes={}-", body.len() / 2))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_without_ranges() {
+        let body = test_body();
+        let (url, requests) = serve(body.clone(), false).await;
+        let path = test_path("no-ranges");
+
+        let outcome = download_file(url.clone(), &path, sha1(&body))
+            .await
+            .unwrap();
+        assert_eq!(outcome, DownloadOutcome::Downloaded);
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        let outcome = download_file(url, &path, sha1(&body)).await.unwrap();
+        assert_eq!(outcome, DownloadOutcome::AlreadyPresent);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_downloads_share_part_file() {
+        let body = test_body();
+        let (url, requests) = serve(body.clone(), true).await;
+        let path = test_path("concurrent");
+
+        let (first, second) = tokio::join!(
+            download_file(url.clone(), &path, sha1(&body)),
+            download_file(url, &path, sha1(&body))
+        );
+        let mut outcomes = vec![first.unwrap(), second.unwrap()];
+        outcomes.sort_by_key(|outcome| format!("{:?}", outcome));
+        assert_eq!(
+            outcomes,
+            vec![DownloadOutcome::AlreadyPresent, DownloadOutcome::Downloaded]
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failure_reports_attempts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/missing.jar", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0u8; 1024];
+                let _ = socket.read(&mut buffer).await;
+                let _ = socket
+                    .write_all(
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+            }
+        });
+
+        // Un 404 no se arregla reintentando
+        let error = download_file(url.clone(), &test_path("missing"), sha1(b""))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            ProtonError::DownloadFailed { url: failed, attempts: 1, .. } if *failed == url
+        ));
+        assert_eq!(error.failure_kind(), FailureKind::Permanent);
+
+        // Un archivo desactualizado se conserva si la descarga que lo reemplaza falla
+        let path = test_path("outdated");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"outdated").unwrap();
+        download_file(url, &path, sha1(b"")).await.unwrap_err();
+        assert_eq!(std::fs::read(&path).unwrap(), b"outdated");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_is_retried() {
+        let body = test_body();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/limited.jar", listener.local_addr().unwrap());
+        let response = body.clone();
+        tokio::spawn(async move {
+            let mut first = true;
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0u8; 1024];
+                let _ = socket.read(&mut buffer).await;
+                if std::mem::take(&mut first) {
+                    let _ = socket
+                        .write_all(b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                        .await;
+                    continue;
+                }
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    response.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&response).await;
+            }
+        });
+
+        let path = test_path("limited");
+        let outcome = download_file(url, &path, sha1(&body)).await.unwrap();
+        assert_eq!(outcome, DownloadOutcome::Downloaded);
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_offline_only_uses_existing_files() {
+        let body = test_body();
+        let (url, requests) = serve(body.clone(), false).await;
+        let path = test_path("offline");
+        let options = DownloadOptions::default()
+            .with_metadata_cache(MetadataCache::disabled().with_offline(true));
+
+        let error = download_file_with_options(url.clone(), &path, sha1(&body), &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(&error, ProtonError::Offline { url: failed } if *failed == url));
+        assert!(requests.lock().unwrap().is_empty());
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &body).unwrap();
+        let outcome = download_file_with_options(url, &path, sha1(&body), &options)
+            .await
+            .unwrap();
+        assert_eq!(outcome, DownloadOutcome::AlreadyPresent);
+    }
+
+    #[tokio::test]
+    async fn test_official_url_uses_mirror() {
+        let body = test_body();
+        let (mirror_url, requests) = serve(body.clone(), true).await;
+        let mirror = mirror_url.strip_suffix("file.jar").unwrap();
+        let options = DownloadOptions::default().with_endpoints(
+            Endpoints::official()
+                .with_mirror(Service::Libraries, mirror)
+                .with_official_fallback(false),
+        );
+
+        let url = format!("{}file.jar", LIBRARIES_URL);
+        let path = test_path("mirror");
+        let outcome = download_file_with_options(url, &path, sha1(&body), &options)
+            .await
+            .unwrap();
+        assert_eq!(outcome, DownloadOutcome::Downloaded);
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        // La descarga interrumpida se continúa también desde el mirror
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_second_instance_links_from_store() {
+        let body = test_body();
+        let (url, requests) = serve(body.clone(), true).await;
+        let first = test_path("store-first");
+        let second = test_path("store-second");
+        let store_dir = std::env::temp_dir().join("proton-store");
+        let _ = std::fs::remove_dir_all(&store_dir);
+        let store = ObjectStore::new(&store_dir);
+
+        let options = DownloadOptions::default()
+            .with_object_store(Some(store.for_instance(first.parent().unwrap())));
+        let outcome = download_file_with_options(url.clone(), &first, sha1(&body), &options)
+            .await
+            .unwrap();
+        assert_eq!(outcome, DownloadOutcome::Downloaded);
+        let requested = requests.lock().unwrap().len();
+
+        // La segunda instancia no descarga nada, ni siquiera sin red
+        let options = DownloadOptions::default()
+            .with_metadata_cache(MetadataCache::disabled().with_offline(true))
+            .with_object_store(Some(store.for_instance(second.parent().unwrap())));
+        let outcome = download_file_with_options(url, &second, sha1(&body), &options)
+            .await
+            .unwrap();
+        assert_eq!(outcome, DownloadOutcome::Linked);
+        assert_eq!(std::fs::read(&second).unwrap(), body);
+        assert_eq!(requests.lock().unwrap().len(), requested);
+
+        let sha1 = Sha1Sum::try_from(sha1(&body)).unwrap();
+        assert_eq!(store.ref_count(&sha1).await.unwrap(), 2);
+    }
+}
@@ -0,0 +1,31 @@
+// [CubicLauncher]
+// src/commands/network.rs
+// Ajustes de red (proxy, timeouts y certificados) que usan los clientes HTTP del launcher.
+use minecraft_launcher_core::network::NetworkSettings;
+use std::sync::{PoisonError, RwLock};
+
+/// Ajustes de red compartidos por los comandos. Los clientes se crean con ellos
+/// al cargarse, así que un cambio solo afecta a los que se creen después
+pub struct NetworkState(RwLock<NetworkSettings>);
+
+impl NetworkState {
+    pub fn get(&self) -> NetworkSettings {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set(&self, settings: NetworkSettings) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = settings;
+    }
+}
+
+impl Default for NetworkState {
+    fn default() -> Self {
+        let user_agent = format!("CubicLauncher/{}", env!("CARGO_PKG_VERSION"));
+        Self(RwLock::new(
+            NetworkSettings::default().with_user_agent(&user_agent),
+        ))
+    }
+}
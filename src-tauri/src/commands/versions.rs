@@ -0,0 +1,59 @@
+// [CubicLauncher]
+// src/commands/versions.rs
+// Comandos para consultar las versiones de Minecraft disponibles e instaladas.
+use super::network::NetworkState;
+use crate::models::response::{BackendResponse, CubicInternalError, ResponseData};
+use minecraft_launcher_core::{
+    json::{EnvironmentFeatures, ReleaseType},
+    version_manager::{catalog::VersionQuery, VersionManager},
+};
+use tauri::{AppHandle, Manager};
+use tokio::sync::OnceCell;
+
+/// Version manager compartido por los comandos. Se carga la primera vez que se usa,
+/// así abrir el launcher no espera a la lista de versiones.
+/// Todos sus métodos toman `&self`, así que varios comandos pueden usarlo a la vez
+#[derive(Default)]
+pub struct VersionsState(OnceCell<VersionManager>);
+
+/// Lista los ids de las versiones, de la más nueva a la más vieja.
+/// Sin `release_types` se incluyen todos los tipos, y sin `installed` tanto las
+/// instaladas como las que no
+#[tauri::command]
+pub async fn get_minecraft_versions(
+    app: AppHandle,
+    release_types: Option<Vec<ReleaseType>>,
+    installed: Option<bool>,
+) -> BackendResponse {
+    let state = app.state::<VersionsState>();
+    let game_dir = match app.path().app_data_dir() {
+        Ok(dir) => dir.join("minecraft"),
+        Err(err) => return BackendResponse::error(CubicInternalError::FileError, err),
+    };
+    let env_features = EnvironmentFeatures::default();
+    let network_settings = app.state::<NetworkState>().get();
+    let version_manager = state
+        .0
+        .get_or_try_init(|| {
+            VersionManager::load_with_network_settings(&game_dir, &env_features, &network_settings)
+        })
+        .await;
+    let version_manager = match version_manager {
+        Ok(version_manager) => version_manager,
+        Err(err) => return BackendResponse::error(CubicInternalError::NetworkError, err),
+    };
+
+    let mut query = VersionQuery::default();
+    for release_type in release_types.unwrap_or_default() {
+        query = query.with_release_type(release_type);
+    }
+    if let Some(installed) = installed {
+        query = query.with_installed(installed);
+    }
+    let versions = version_manager
+        .query_versions(&query)
+        .into_iter()
+        .map(|entry| entry.id.to_string())
+        .collect();
+    BackendResponse::ok(ResponseData::MinecraftVersions(versions))
+}
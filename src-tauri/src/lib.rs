@@ -1,10 +1,18 @@
+pub mod commands;
 pub mod models;
 
+use commands::{
+    network::NetworkState,
+    versions::{get_minecraft_versions, VersionsState},
+};
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![])
+        .manage(NetworkState::default())
+        .manage(VersionsState::default())
+        .invoke_handler(tauri::generate_handler![get_minecraft_versions])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,87 @@
+// [CubicLauncher]
+// src/models/response.rs
+// Respuestas que reciben los comandos del frontend (`BackendResponse` en src/types.ts).
+use serde::Serialize;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub enum CubicInternalError {
+    WindowMinimizeError,
+    WindowIsNotMinimizable,
+    WindowIsNotMaximizable,
+    WindowMaximizeError,
+    WindowIsNotClosable,
+    WindowCloseError,
+    LauncherError,
+    ConfigError,
+    MinecraftInstanceError,
+    NetworkError,
+    FileError,
+    PermissionError,
+    InstanceEncodeError,
+    InvalidLoader,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClientError {
+    pub error_type: CubicInternalError,
+    // El frontend espera que no esté, no que sea null
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub enum ResponseData {
+    MinecraftVersions(Vec<String>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackendResponse {
+    pub success: bool,
+    pub error: Option<ClientError>,
+    pub data: Option<ResponseData>,
+}
+
+impl BackendResponse {
+    pub fn ok(data: ResponseData) -> Self {
+        Self {
+            success: true,
+            error: None,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(error_type: CubicInternalError, message: impl ToString) -> Self {
+        Self {
+            success: false,
+            error: Some(ClientError {
+                error_type,
+                error_message: Some(message.to_string()),
+            }),
+            data: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_format() {
+        let response = BackendResponse::ok(ResponseData::MinecraftVersions(vec!["1.20.4".into()]));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "success": true,
+                "error": null,
+                "data": { "MinecraftVersions": ["1.20.4"] }
+            })
+        );
+
+        let response = BackendResponse::error(CubicInternalError::NetworkError, "offline");
+        assert_eq!(
+            serde_json::to_value(&response).unwrap()["error"],
+            serde_json::json!({ "error_type": "NetworkError", "error_message": "offline" })
+        );
+    }
+}
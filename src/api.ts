@@ -25,4 +25,8 @@ export async function getInstances(): Promise<BackendResponse> {
 	const response = await invoke("get_instances");
 	console.log(response)
 	return response as BackendResponse;
-}
\ No newline at end of file
+}
+export async function getMinecraftVersions(releaseTypes?: string[], installed?: boolean): Promise<BackendResponse> {
+	const response = await invoke("get_minecraft_versions", { releaseTypes, installed });
+	return response as BackendResponse;
+}